@@ -0,0 +1,35 @@
+use std::hash::BuildHasher;
+use std::time::{Duration, Instant};
+use url_encoded_data::hasher::FnvBuildHasher;
+use url_encoded_data::{DefaultHashBuilder, UrlEncodedData};
+
+// run with: cargo run --release --example hasher_benchmark
+const ROUNDS: usize = 20_000;
+
+fn bench<S: BuildHasher + Clone>(name: &str, s: &str, hash_builder: S) -> Duration {
+    let start = Instant::now();
+    let mut total = 0;
+    for _ in 0..ROUNDS {
+        let q = UrlEncodedData::parse_str_with_hasher(s, hash_builder.clone());
+        total += q.get("k7").map(|v| v.len()).unwrap_or(0);
+        total += q.keys_length();
+    }
+    let elapsed = start.elapsed();
+    assert!(total > 0);
+    println!("{:<24} {:>10.2?} ({} rounds)", name, elapsed, ROUNDS);
+    elapsed
+}
+
+fn main() {
+    let s: String = (0..64)
+        .map(|i| format!("k{}=v{}", i % 16, i))
+        .collect::<Vec<_>>()
+        .join("&");
+
+    let random_state = bench("DefaultHashBuilder", &s, DefaultHashBuilder::default());
+    let fnv = bench("FnvBuildHasher", &s, FnvBuildHasher::default());
+    println!(
+        "FnvBuildHasher / DefaultHashBuilder: {:.2}",
+        fnv.as_secs_f64() / random_state.as_secs_f64()
+    );
+}
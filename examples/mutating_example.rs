@@ -28,15 +28,14 @@ fn main() {
     let url = "https://google.com/?q=rust&ei=code";
     let q = UrlEncodedData::parse_str(url)
         .set_one("q", "rust-lang")
-        .set("vector", &vec!["1", "2"])
+        .set("vector", &["1", "2"])
         .set_one("a", "1")
         .set_one("b", "2")
         .set_one("hello", "world")
-        .set("whole", &vec!["world", "世界"]) // utf-8, auto encoding and decoding
+        .set("whole", &["world", "世界"]) // utf-8, auto encoding and decoding
         .delete("ei") // ei is deleted
         .push("b", "3")
         .done(); // now b is: vec!["1", "2"]
-    let q = q; // mut -> const
 
     // q.keys() // performant
     assert_eq!(q.keys_of_original_order()[0].as_ref(), "q");
@@ -50,7 +50,7 @@ fn simple_example() -> anyhow::Result<()> {
 
         for (k1, v1) in map {
             let v2 = map_of_multiple_values_expected.get(k1.as_ref()).unwrap();
-            for (i, v2i) in v2.into_iter().enumerate() {
+            for (i, v2i) in v2.iter().enumerate() {
                 assert_eq!(v1[i].as_ref(), *v2i);
             }
         }
@@ -0,0 +1,360 @@
+//! # Canonical form, equality and hashing of `UrlEncodedData`
+//!
+//! The canonical form fixes everything that does not change the meaning of the data:
+//! key order, value order, case of percent-encoding hex digits and the encoding of spaces.
+//!
+//! Equality modes:
+//! * `UrlEncodedData` implements `PartialEq`, `Eq` and `Hash` as **multiset-equal**:
+//!   same prefix and same (key, value) pairs, whatever their order.
+//! * `SequenceEq` wraps a `UrlEncodedData` to compare and hash it as **sequence-equal**:
+//!   same prefix and same pairs in the same original order.
+//!
+//! ```rust
+//! use url_encoded_data::canonical::{CanonicalOptions, SequenceEq};
+//! use url_encoded_data::UrlEncodedData;
+//! let a = UrlEncodedData::parse_str("https://abc.com/?b=2&a=1&a=%7e");
+//! let b = UrlEncodedData::parse_str("https://abc.com/?a=~&b=2&a=1");
+//! assert_eq!(a, b);
+//! assert_ne!(SequenceEq(&a), SequenceEq(&b));
+//!
+//! let options = CanonicalOptions::default();
+//! assert_eq!(a.to_canonical_string(&options), "https://abc.com/?a=1&a=%7E&b=2");
+//! assert_eq!(a.to_canonical_string(&options), b.to_canonical_string(&options));
+//! ```
+
+use alloc::borrow::Cow;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::hash::{BuildHasher, Hash, Hasher};
+
+use crate::{codec, UrlEncodedData};
+
+/// # Order of keys in the canonical form
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyOrder {
+    /// order of first occurrence in the original data
+    Original,
+    /// byte order of the decoded keys
+    Sorted,
+}
+
+/// # Order of the values of one key in the canonical form
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ValueOrder {
+    /// order of occurrence in the original data
+    Original,
+    /// byte order of the decoded values
+    Sorted,
+}
+
+/// # Case of the hex digits of `%XX`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HexCase {
+    Upper,
+    Lower,
+}
+
+/// # Encoding of spaces
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SpaceStyle {
+    /// `+`, as `application/x-www-form-urlencoded` does
+    Plus,
+    /// `%20`, as RFC 3986 does
+    Percent20,
+}
+
+/// # Options of the canonical form
+///
+/// The default is: sorted keys, sorted values, uppercase hex, `+` for spaces, prefix included.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CanonicalOptions {
+    pub key_order: KeyOrder,
+    pub value_order: ValueOrder,
+    pub hex_case: HexCase,
+    pub space_style: SpaceStyle,
+    pub include_prefix: bool,
+}
+
+impl Default for CanonicalOptions {
+    fn default() -> Self {
+        Self {
+            key_order: KeyOrder::Sorted,
+            value_order: ValueOrder::Sorted,
+            hex_case: HexCase::Upper,
+            space_style: SpaceStyle::Plus,
+            include_prefix: true,
+        }
+    }
+}
+
+/// Append a component, form-urlencoded with the given hex case and space style
+fn append_canonical_component(out: &mut String, s: &str, options: &CanonicalOptions) {
+    let start = out.len();
+    codec::append_encoded(out, s);
+    if options.space_style == SpaceStyle::Percent20 || options.hex_case == HexCase::Lower {
+        let encoded = out.split_off(start);
+        let mut chars = encoded.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '+' if options.space_style == SpaceStyle::Percent20 => out.push_str("%20"),
+                '%' if options.hex_case == HexCase::Lower => {
+                    out.push('%');
+                    out.extend(chars.by_ref().take(2).map(|h| h.to_ascii_lowercase()));
+                }
+                c => out.push(c),
+            }
+        }
+    }
+}
+
+/// Decoded pairs in the requested orders, borrowed from `data`
+pub(crate) fn ordered_pairs<'r, S: BuildHasher + Clone>(
+    data: &'r UrlEncodedData<'_, S>,
+    key_order: KeyOrder,
+    value_order: ValueOrder,
+) -> Vec<(&'r str, &'r str)> {
+    let mut keys: Vec<&str> = match key_order {
+        KeyOrder::Original => {
+            let mut keys: Vec<&str> = data
+                .original_keys_in_order
+                .iter()
+                .filter(|k| data.map.contains_key(*k))
+                .map(|k| k.as_ref())
+                .collect();
+            let mut added: Vec<&str> = data
+                .map
+                .keys()
+                .map(|k| k.as_ref())
+                .filter(|k| !keys.contains(k))
+                .collect();
+            // keys added after parsing have no original position, sort them to stay deterministic
+            added.sort_unstable();
+            keys.extend(added);
+            keys
+        }
+        KeyOrder::Sorted => data.map.keys().map(|k| k.as_ref()).collect(),
+    };
+    if key_order == KeyOrder::Sorted {
+        keys.sort_unstable();
+    }
+
+    let mut pairs = Vec::with_capacity(data.len());
+    for key in keys {
+        let mut values: Vec<&str> = data
+            .map
+            .get(key)
+            .into_iter()
+            .flatten()
+            .map(|v| v.as_ref())
+            .collect();
+        if value_order == ValueOrder::Sorted {
+            values.sort_unstable();
+        }
+        pairs.extend(values.into_iter().map(|v| (key, v)));
+    }
+    pairs
+}
+
+/// Raw (still encoded) pairs of `original_data_str`, in their sequence, if the map still holds
+/// exactly the pairs they decode to; `None` once pairs were set, pushed or deleted after parsing,
+/// or if the data was not built from its raw string. Does not allocate.
+pub(crate) fn original_raw_pairs<'a, S: BuildHasher + Clone>(
+    data: &UrlEncodedData<'a, S>,
+) -> Option<codec::RawPairs<'a>> {
+    let raw = codec::raw_pairs(data.original_data_str);
+    // `original_data_str` is public: it may have been replaced
+    if data.raw_is_current && raw.count() == data.len() {
+        Some(raw)
+    } else {
+        None
+    }
+}
+
+impl<'a, S: BuildHasher + Clone> UrlEncodedData<'a, S> {
+    /// # Canonical string
+    ///
+    /// Two `UrlEncodedData` with the default options give the same string if and only if they are equal (`==`).
+    /// ```rust
+    /// use url_encoded_data::canonical::{CanonicalOptions, HexCase, KeyOrder, SpaceStyle, ValueOrder};
+    /// use url_encoded_data::UrlEncodedData;
+    /// let q = UrlEncodedData::parse_str("https://abc.com/?q=a%20b&q=%e4%b8%96&c=d");
+    /// assert_eq!(q.to_canonical_string(&CanonicalOptions::default()), "https://abc.com/?c=d&q=a+b&q=%E4%B8%96");
+    ///
+    /// let options = CanonicalOptions {
+    ///     key_order: KeyOrder::Original,
+    ///     value_order: ValueOrder::Original,
+    ///     hex_case: HexCase::Lower,
+    ///     space_style: SpaceStyle::Percent20,
+    ///     include_prefix: false,
+    /// };
+    /// assert_eq!(q.to_canonical_string(&options), "q=a%20b&q=%e4%b8%96&c=d");
+    /// ```
+    pub fn to_canonical_string(&self, options: &CanonicalOptions) -> String {
+        let mut s = String::new();
+        if options.include_prefix {
+            s.push_str(self.prefix);
+        }
+        let start = s.len();
+        for (k, v) in ordered_pairs(self, options.key_order, options.value_order) {
+            if s.len() > start {
+                s.push('&');
+            }
+            append_canonical_component(&mut s, k, options);
+            s.push('=');
+            append_canonical_component(&mut s, v, options);
+        }
+        s
+    }
+}
+
+/// Multiset-equal: same prefix and same (key, value) pairs, whatever their order.
+/// ```rust
+/// use url_encoded_data::UrlEncodedData;
+/// assert_eq!(UrlEncodedData::parse_str("a=1&b=2&a=3"), UrlEncodedData::parse_str("a=3&b=2&a=1"));
+/// assert_ne!(UrlEncodedData::parse_str("a=1&a=1"), UrlEncodedData::parse_str("a=1"));
+/// assert_ne!(UrlEncodedData::parse_str("https://a.com/?a=1"), UrlEncodedData::parse_str("https://b.com/?a=1"));
+/// ```
+impl<'a, 'b, S1, S2> PartialEq<UrlEncodedData<'b, S2>> for UrlEncodedData<'a, S1>
+where
+    S1: BuildHasher + Clone,
+    S2: BuildHasher + Clone,
+{
+    fn eq(&self, other: &UrlEncodedData<'b, S2>) -> bool {
+        self.prefix == other.prefix
+            && self.len() == other.len()
+            && ordered_pairs(self, KeyOrder::Sorted, ValueOrder::Sorted)
+                == ordered_pairs(other, KeyOrder::Sorted, ValueOrder::Sorted)
+    }
+}
+
+impl<'a, S: BuildHasher + Clone> Eq for UrlEncodedData<'a, S> {}
+
+/// Consistent with the multiset-equal `PartialEq`, so `UrlEncodedData` can be used as a `HashMap` key.
+/// ```rust
+/// use std::collections::HashSet;
+/// use url_encoded_data::UrlEncodedData;
+/// let mut seen = HashSet::new();
+/// assert!(seen.insert(UrlEncodedData::parse_str("/?a=1&b=2")));
+/// assert!(!seen.insert(UrlEncodedData::parse_str("/?b=2&a=1")));
+/// ```
+impl<'a, S: BuildHasher + Clone> Hash for UrlEncodedData<'a, S> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.prefix.hash(state);
+        ordered_pairs(self, KeyOrder::Sorted, ValueOrder::Sorted).hash(state);
+    }
+}
+
+/// # Sequence-equal view of a `UrlEncodedData`
+///
+/// Compares and hashes the prefix and the decoded pairs in their original sequence, interleaved keys
+/// included. Once pairs were set, pushed or deleted after parsing, the sequence is no longer known:
+/// the pairs are then taken in original order grouped by key (see
+/// `UrlEncodedData::as_pairs_of_original_order`).
+/// ```rust
+/// use url_encoded_data::canonical::SequenceEq;
+/// use url_encoded_data::UrlEncodedData;
+/// let a = UrlEncodedData::parse_str("a=1&b=2&a=3");
+/// assert_eq!(SequenceEq(&a), SequenceEq(&UrlEncodedData::parse_str("a=%31&b=2&a=3")));
+/// assert_ne!(SequenceEq(&a), SequenceEq(&UrlEncodedData::parse_str("a=1&a=3&b=2")));
+/// assert_ne!(SequenceEq(&a), SequenceEq(&UrlEncodedData::parse_str("a=3&b=2&a=1")));
+/// assert_ne!(SequenceEq(&a), SequenceEq(&UrlEncodedData::parse_str("b=2&a=1&a=3")));
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct SequenceEq<'r, 'a, S = crate::DefaultHashBuilder>(pub &'r UrlEncodedData<'a, S>);
+
+impl<'r, 'a, S: BuildHasher + Clone> SequenceEq<'r, 'a, S> {
+    fn pairs(&self) -> Vec<(Cow<'r, str>, Cow<'r, str>)> {
+        match original_raw_pairs(self.0) {
+            Some(raw) => raw
+                .map(|(k, v)| (codec::decode(k), codec::decode(v)))
+                .collect(),
+            None => ordered_pairs(self.0, KeyOrder::Original, ValueOrder::Original)
+                .into_iter()
+                .map(|(k, v)| (Cow::Borrowed(k), Cow::Borrowed(v)))
+                .collect(),
+        }
+    }
+}
+
+impl<'r1, 'r2, 'a, 'b, S1, S2> PartialEq<SequenceEq<'r2, 'b, S2>> for SequenceEq<'r1, 'a, S1>
+where
+    S1: BuildHasher + Clone,
+    S2: BuildHasher + Clone,
+{
+    fn eq(&self, other: &SequenceEq<'r2, 'b, S2>) -> bool {
+        self.0.prefix == other.0.prefix && self.pairs() == other.pairs()
+    }
+}
+
+impl<'r, 'a, S: BuildHasher + Clone> Eq for SequenceEq<'r, 'a, S> {}
+
+impl<'r, 'a, S: BuildHasher + Clone> Hash for SequenceEq<'r, 'a, S> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.prefix.hash(state);
+        self.pairs().hash(state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hasher::FnvBuildHasher;
+    use std::collections::hash_map::DefaultHasher;
+
+    fn hash_of<T: Hash>(t: &T) -> u64 {
+        let mut h = DefaultHasher::new();
+        t.hash(&mut h);
+        h.finish()
+    }
+
+    #[test]
+    fn test_multiset_equal_and_hash() {
+        let a = UrlEncodedData::parse_str("/?x=1&y=a+b&x=2");
+        let b =
+            UrlEncodedData::parse_str_with_hasher("/?y=a%20b&x=2&x=1", FnvBuildHasher::default());
+        assert!(a == b);
+        assert_eq!(hash_of(&a), hash_of(&b));
+
+        let c = UrlEncodedData::parse_str("/?x=1&y=a+b");
+        assert_ne!(a, c);
+    }
+
+    #[test]
+    fn test_sequence_equal_and_hash() {
+        let a = UrlEncodedData::parse_str("x=1&y=2&x=3");
+        let b = UrlEncodedData::parse_str_with_hasher("x=%31&y=2&x=3", FnvBuildHasher::default());
+        assert_eq!(SequenceEq(&a), SequenceEq(&b));
+        assert_eq!(hash_of(&SequenceEq(&a)), hash_of(&SequenceEq(&b)));
+
+        // interleaving is part of the sequence
+        let c = UrlEncodedData::parse_str("x=1&x=3&y=2");
+        assert_ne!(SequenceEq(&a), SequenceEq(&c));
+        assert_eq!(a, c);
+        let d = UrlEncodedData::parse_str("y=2&x=1&x=3");
+        assert_ne!(SequenceEq(&a), SequenceEq(&d));
+
+        // after a mutation, the pairs are grouped by key in original order
+        let mut e = UrlEncodedData::parse_str("x=1&y=2");
+        e.push("x", "3");
+        assert_eq!(SequenceEq(&e), SequenceEq(&c));
+    }
+
+    #[test]
+    fn test_canonical_string_after_mutation() {
+        let mut q = UrlEncodedData::parse_str("https://abc.com/?b=2&a=1");
+        q.set_one("d", "x y").set_one("c", "3");
+        let options = CanonicalOptions {
+            key_order: KeyOrder::Original,
+            ..CanonicalOptions::default()
+        };
+        // keys added after parsing come last, in sorted order
+        assert_eq!(
+            q.to_canonical_string(&options),
+            "https://abc.com/?b=2&a=1&c=3&d=x+y"
+        );
+        assert_eq!(
+            q.to_canonical_string(&CanonicalOptions::default()),
+            "https://abc.com/?a=1&b=2&c=3&d=x+y"
+        );
+    }
+}
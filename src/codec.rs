@@ -0,0 +1,275 @@
+//! # Percent codec of `application/x-www-form-urlencoded`
+//!
+//! Self-contained (`no_std` + `alloc`) encoding and decoding, byte-for-byte compatible with the
+//! [WHATWG urlencoded parser and serializer](https://url.spec.whatwg.org/#application/x-www-form-urlencoded).
+//!
+//! ```rust
+//! use url_encoded_data::codec;
+//! assert_eq!(codec::encode("bar & baz"), "bar+%26+baz");
+//! assert_eq!(codec::decode("bar+%26+baz"), "bar & baz");
+//!
+//! let pairs: Vec<_> = codec::parse("a=1&b=%E4%B8%96").collect();
+//! assert_eq!(pairs[0], ("a".into(), "1".into()));
+//! assert_eq!(pairs[1], ("b".into(), "世".into()));
+//! ```
+
+use alloc::borrow::Cow;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::Pair;
+
+const UPPER_HEX: &[u8; 16] = b"0123456789ABCDEF";
+
+/// Is the byte kept as-is by the form-urlencoded serializer? (alphanumeric, `*`, `-`, `.`, `_`)
+#[inline]
+fn is_form_unreserved(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || matches!(byte, b'*' | b'-' | b'.' | b'_')
+}
+
+/// Value of an ascii hex digit
+#[inline]
+pub(crate) fn hex_value(byte: u8) -> Option<u8> {
+    match byte {
+        b'0'..=b'9' => Some(byte - b'0'),
+        b'a'..=b'f' => Some(byte - b'a' + 10),
+        b'A'..=b'F' => Some(byte - b'A' + 10),
+        _ => None,
+    }
+}
+
+/// Append `%XX` (uppercase hex) of the byte
+#[inline]
+pub(crate) fn push_percent_encoded(out: &mut String, byte: u8) {
+    out.push('%');
+    out.push(UPPER_HEX[(byte >> 4) as usize] as char);
+    out.push(UPPER_HEX[(byte & 0xf) as usize] as char);
+}
+
+/// # Encode a component (key or value) with form-urlencoded rules
+///
+/// Alphanumerics and `*-._` are kept, space becomes `+`, everything else becomes `%XX`.
+/// ```rust
+/// use url_encoded_data::codec::encode;
+/// assert_eq!(encode("abc"), "abc");
+/// assert_eq!(encode("Été+hiver"), "%C3%89t%C3%A9%2Bhiver");
+/// assert_eq!(encode("a b~"), "a+b%7E");
+/// ```
+pub fn encode(s: &str) -> Cow<'_, str> {
+    if s.bytes().all(is_form_unreserved) {
+        return Cow::Borrowed(s);
+    }
+    let mut out = String::with_capacity(s.len() + s.len() / 2);
+    append_encoded(&mut out, s);
+    Cow::Owned(out)
+}
+
+/// Append the form-urlencoded component to `out`
+pub(crate) fn append_encoded(out: &mut String, s: &str) {
+    for &byte in s.as_bytes() {
+        if is_form_unreserved(byte) {
+            out.push(byte as char);
+        } else if byte == b' ' {
+            out.push('+');
+        } else {
+            push_percent_encoded(out, byte);
+        }
+    }
+}
+
+/// Append `key=value` to `out`, separated from previous pairs by `&`
+pub(crate) fn append_pair(out: &mut String, key: &str, value: &str) {
+    if !out.is_empty() {
+        out.push('&');
+    }
+    append_encoded(out, key);
+    out.push('=');
+    append_encoded(out, value);
+}
+
+/// Append the RFC 3986 encoding of `s`: only unreserved characters (`A-Za-z0-9-._~`) are kept,
+/// and `/` if `encode_slash` is false. Used by signature schemes (SigV4, OAuth 1.0a).
+pub(crate) fn append_rfc3986_encoded(out: &mut String, s: &str, encode_slash: bool) {
+    for &byte in s.as_bytes() {
+        if byte.is_ascii_alphanumeric()
+            || matches!(byte, b'-' | b'.' | b'_' | b'~')
+            || (byte == b'/' && !encode_slash)
+        {
+            out.push(byte as char);
+        } else {
+            push_percent_encoded(out, byte);
+        }
+    }
+}
+
+/// Percent-decode bytes, `%` not followed by two hex digits is kept literally.
+/// Returns `None` if there was nothing to decode.
+pub(crate) fn percent_decode_bytes(input: &[u8], plus_as_space: bool) -> Option<Vec<u8>> {
+    let needs_decoding = input
+        .iter()
+        .any(|&b| b == b'%' || (plus_as_space && b == b'+'));
+    if !needs_decoding {
+        return None;
+    }
+    let mut out = Vec::with_capacity(input.len());
+    let mut i = 0;
+    while i < input.len() {
+        let byte = input[i];
+        if byte == b'%' && i + 2 < input.len() {
+            if let (Some(h), Some(l)) = (hex_value(input[i + 1]), hex_value(input[i + 2])) {
+                out.push(h << 4 | l);
+                i += 3;
+                continue;
+            }
+        }
+        if plus_as_space && byte == b'+' {
+            out.push(b' ');
+        } else {
+            out.push(byte);
+        }
+        i += 1;
+    }
+    Some(out)
+}
+
+/// # Decode a component (key or value) with form-urlencoded rules
+///
+/// `+` becomes space, `%XX` is decoded, invalid UTF-8 is replaced by `U+FFFD`.
+/// ```rust
+/// use url_encoded_data::codec::decode;
+/// assert_eq!(decode("a+b%7E"), "a b~");
+/// assert_eq!(decode("%zz%4"), "%zz%4");
+/// assert_eq!(decode("%FF"), "\u{FFFD}");
+/// ```
+pub fn decode(s: &str) -> Cow<'_, str> {
+    match percent_decode_bytes(s.as_bytes(), true) {
+        None => Cow::Borrowed(s),
+        Some(bytes) => match String::from_utf8(bytes) {
+            Ok(s) => Cow::Owned(s),
+            Err(e) => Cow::Owned(String::from_utf8_lossy(e.as_bytes()).into_owned()),
+        },
+    }
+}
+
+/// Split a raw `key=value` at the first `=`
+#[inline]
+fn split_raw_pair(raw: &str) -> (&str, &str) {
+    match raw.find('=') {
+        Some(idx) => (&raw[..idx], &raw[idx + 1..]),
+        None => (raw, ""),
+    }
+}
+
+/// # Iterator of raw (still encoded) pairs
+///
+/// Splits on `&`, skips empty sequences, splits each sequence at its first `=`.
+/// ```rust
+/// use url_encoded_data::codec::raw_pairs;
+/// let pairs: Vec<_> = raw_pairs("a=%41&&b&=c=d").collect();
+/// assert_eq!(pairs, vec![("a", "%41"), ("b", ""), ("", "c=d")]);
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct RawPairs<'a> {
+    input: &'a str,
+}
+
+impl<'a> Iterator for RawPairs<'a> {
+    type Item = (&'a str, &'a str);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.input.is_empty() {
+                return None;
+            }
+            let (sequence, rest) = match self.input.find('&') {
+                Some(idx) => (&self.input[..idx], &self.input[idx + 1..]),
+                None => (self.input, ""),
+            };
+            self.input = rest;
+            if !sequence.is_empty() {
+                return Some(split_raw_pair(sequence));
+            }
+        }
+    }
+}
+
+/// Raw (still encoded) pairs of `data`, see `RawPairs`
+pub fn raw_pairs(data: &str) -> RawPairs<'_> {
+    RawPairs { input: data }
+}
+
+/// # Iterator of decoded pairs
+///
+/// ```rust
+/// use url_encoded_data::codec::parse;
+/// let pairs: Vec<_> = parse("a=1&b=2+3&c").collect();
+/// assert_eq!(pairs[1], ("b".into(), "2 3".into()));
+/// assert_eq!(pairs[2], ("c".into(), "".into()));
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct Parse<'a> {
+    raw: RawPairs<'a>,
+}
+
+impl<'a> Iterator for Parse<'a> {
+    type Item = Pair<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.raw.next().map(|(k, v)| (decode(k), decode(v)))
+    }
+}
+
+/// Decoded pairs of `data`, see `Parse`
+pub fn parse(data: &str) -> Parse<'_> {
+    Parse {
+        raw: raw_pairs(data),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_same_as_url_crate() {
+        let inputs = [
+            "",
+            "a=1&b=2&c=3&c=4&key_without_value&=value_without_key",
+            "a=b+c&d=%2b&e=%zz&f=%4&g=%",
+            "hello=%e4%bd%a0%e5%a5%bd&world=%E4%B8%96%E7%95%8C",
+            "&&a&&=&=&b==c&",
+            "invalid=%FF%FE&partial=%E4%B8",
+            "utf8=世界&plus=++",
+        ];
+        for input in inputs.iter() {
+            let ours: Vec<_> = parse(input).collect();
+            let theirs: Vec<_> = url::form_urlencoded::parse(input.as_bytes()).collect();
+            assert_eq!(ours, theirs, "input: {:?}", input);
+        }
+    }
+
+    #[test]
+    fn test_encode_same_as_url_crate() {
+        let all_ascii: String = (0u8..128).map(|b| b as char).collect();
+        let inputs = ["", "abc", "a b&c=d", "Été+hiver", "~!@#$%^&*()", &all_ascii];
+        for input in inputs.iter() {
+            let theirs: String = url::form_urlencoded::byte_serialize(input.as_bytes()).collect();
+            assert_eq!(encode(input), theirs, "input: {:?}", input);
+        }
+
+        let mut ours = String::new();
+        let mut theirs = url::form_urlencoded::Serializer::new(String::new());
+        for (k, v) in [("a", "b c"), ("", ""), ("世", "界")].iter() {
+            append_pair(&mut ours, k, v);
+            theirs.append_pair(k, v);
+        }
+        assert_eq!(ours, theirs.finish());
+    }
+
+    #[test]
+    fn test_decode_is_borrowed_when_possible() {
+        assert!(matches!(decode("abc"), Cow::Borrowed(_)));
+        assert!(matches!(decode("a+b"), Cow::Owned(_)));
+        assert!(matches!(encode("abc"), Cow::Borrowed(_)));
+    }
+}
@@ -0,0 +1,224 @@
+//! # Delimited multi-values: `ids=1,2,3`
+//!
+//! Lists come as repeated keys (`ids=1&ids=2`) or as one delimited value (`ids=1,2`).
+//! `get_split` reads both, `set_joined` writes a delimited value, and `normalize_multi_values`
+//! converts chosen keys to one `MultiValueForm` or the other.
+//!
+//! Inside a delimited value, `\` escapes the delimiter and itself: `a\,b,c` is `["a,b", "c"]`.
+//! A `\` before any other character is kept literally. The delimiter cannot be `\`.
+//!
+//! ```rust
+//! use url_encoded_data::delimited::MultiValueForm;
+//! use url_encoded_data::UrlEncodedData;
+//!
+//! let mut q = UrlEncodedData::parse_str("ids=1,2&ids=3&tags=a%5C%2Cb,c");
+//! assert_eq!(q.get_split("ids", ',').unwrap(), ["1", "2", "3"]);
+//! assert_eq!(q.get_split("tags", ',').unwrap(), ["a,b", "c"]);
+//!
+//! q.normalize_multi_values(&["ids"], ',', MultiValueForm::Repeated);
+//! assert_eq!(q.get("ids").unwrap(), ["1", "2", "3"]);
+//! q.set_joined("names", &["x,y", "z"], ',');
+//! assert_eq!(q.get_first("names").unwrap(), "x\\,y,z");
+//! ```
+
+use alloc::borrow::Cow;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::hash::BuildHasher;
+
+use crate::UrlEncodedData;
+
+const ESCAPE: char = '\\';
+
+/// # Representation of a list
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MultiValueForm {
+    /// `ids=1&ids=2&ids=3`
+    Repeated,
+    /// `ids=1,2,3`
+    Delimited,
+}
+
+/// # Split a delimited value, unescaping `\` sequences; an empty value is an empty list
+/// ```rust
+/// use url_encoded_data::delimited::split;
+/// assert_eq!(split("a|b\\|c|d\\\\|", '|'), ["a", "b|c", "d\\", ""]);
+/// assert_eq!(split("C:\\dir,x", ','), ["C:\\dir", "x"]);
+/// assert!(split("", ',').is_empty());
+/// ```
+pub fn split(value: &str, delimiter: char) -> Vec<Cow<'_, str>> {
+    assert_ne!(
+        delimiter, ESCAPE,
+        "the delimiter cannot be the escape character"
+    );
+    let mut items = Vec::new();
+    if value.is_empty() {
+        return items;
+    }
+    let mut start = 0;
+    let mut unescaped: Option<String> = None;
+    let mut chars = value.char_indices().peekable();
+    while let Some((idx, c)) = chars.next() {
+        if c == ESCAPE {
+            if let Some(&(next_idx, next)) = chars.peek() {
+                if next == delimiter || next == ESCAPE {
+                    let item = unescaped.get_or_insert_with(String::new);
+                    item.push_str(&value[start..idx]);
+                    item.push(next);
+                    chars.next();
+                    start = next_idx + next.len_utf8();
+                }
+            }
+        } else if c == delimiter {
+            items.push(match unescaped.take() {
+                None => Cow::Borrowed(&value[start..idx]),
+                Some(mut item) => {
+                    item.push_str(&value[start..idx]);
+                    Cow::Owned(item)
+                }
+            });
+            start = idx + c.len_utf8();
+        }
+    }
+    items.push(match unescaped {
+        None => Cow::Borrowed(&value[start..]),
+        Some(mut item) => {
+            item.push_str(&value[start..]);
+            Cow::Owned(item)
+        }
+    });
+    items
+}
+
+/// # Join values with the delimiter, escaping it and `\` inside values
+/// ```rust
+/// use url_encoded_data::delimited::join;
+/// assert_eq!(join(&["a|b", "c\\d", "e"], '|'), "a\\|b|c\\\\d|e");
+/// ```
+pub fn join<T: AsRef<str>>(values: &[T], delimiter: char) -> String {
+    assert_ne!(
+        delimiter, ESCAPE,
+        "the delimiter cannot be the escape character"
+    );
+    let mut joined = String::new();
+    for (idx, value) in values.iter().enumerate() {
+        if idx > 0 {
+            joined.push(delimiter);
+        }
+        for c in value.as_ref().chars() {
+            if c == delimiter || c == ESCAPE {
+                joined.push(ESCAPE);
+            }
+            joined.push(c);
+        }
+    }
+    joined
+}
+
+impl<'a, S: BuildHasher + Clone> UrlEncodedData<'a, S> {
+    /// # Items of all values of `key`, each value split with `delimiter`, see `delimited::split`
+    pub fn get_split(&self, key: &str, delimiter: char) -> Option<Vec<Cow<'_, str>>> {
+        let values = self.map.get(key)?;
+        Some(
+            values
+                .iter()
+                .flat_map(|value| split(value, delimiter))
+                .collect(),
+        )
+    }
+
+    /// # Set `key` to one value: `values` joined with `delimiter`, see `delimited::join`
+    pub fn set_joined<T: AsRef<str>>(
+        &mut self,
+        key: &'a str,
+        values: &[T],
+        delimiter: char,
+    ) -> &mut Self {
+        self.raw_is_current = false;
+        self.map.insert(
+            Cow::from(key),
+            alloc::vec![Cow::Owned(join(values, delimiter))],
+        );
+        self
+    }
+
+    /// # Convert the values of `keys` to repeated keys, or to one delimited value
+    ///
+    /// Both forms, and mixes of them, are read. Absent keys are left absent.
+    pub fn normalize_multi_values(
+        &mut self,
+        keys: &[&str],
+        delimiter: char,
+        form: MultiValueForm,
+    ) -> &mut Self {
+        for key in keys {
+            let values = match self.map.get_mut(*key) {
+                Some(values) => values,
+                None => continue,
+            };
+            self.raw_is_current = false;
+            let items: Vec<String> = values
+                .iter()
+                .flat_map(|value| split(value, delimiter))
+                .map(Cow::into_owned)
+                .collect();
+            *values = match form {
+                // an empty list stays an empty value
+                MultiValueForm::Repeated if items.is_empty() => alloc::vec![Cow::Borrowed("")],
+                MultiValueForm::Repeated => items.into_iter().map(Cow::Owned).collect(),
+                MultiValueForm::Delimited => alloc::vec![Cow::Owned(join(&items, delimiter))],
+            };
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        for values in [
+            &["a", "b"][..],
+            &["", ""],
+            &["a,b", "\\", "\\,", "x\\y"],
+            &["世,界", ","],
+        ] {
+            let joined = join(values, ',');
+            assert_eq!(split(&joined, ','), values, "{}", joined);
+        }
+        // a single empty value joins to the empty list
+        assert!(split(&join(&[""], ','), ',').is_empty());
+    }
+
+    #[test]
+    fn test_normalize_both_ways() {
+        let mut q =
+            UrlEncodedData::parse_str("https://abc.com/?ids=1|2&ids=3&tags=a&tags=b%7Cc&q=x|y");
+        q.normalize_multi_values(&["ids", "tags", "absent"], '|', MultiValueForm::Delimited);
+        assert_eq!(
+            q.to_string_of_original_order(),
+            "https://abc.com/?ids=1%7C2%7C3&tags=a%7Cb%7Cc&q=x%7Cy"
+        );
+        q.normalize_multi_values(&["ids", "tags"], '|', MultiValueForm::Repeated);
+        assert_eq!(
+            q.to_string_of_original_order(),
+            "https://abc.com/?ids=1&ids=2&ids=3&tags=a&tags=b&tags=c&q=x%7Cy"
+        );
+        assert!(!q.exists("absent"));
+
+        let mut q = UrlEncodedData::parse_str("ids=");
+        q.normalize_multi_values(&["ids"], ',', MultiValueForm::Repeated);
+        assert_eq!(q.to_string(), "ids=");
+    }
+
+    #[test]
+    fn test_escaped_round_trip_through_query() {
+        let mut q = UrlEncodedData::parse_str("");
+        q.set_joined("k", &["a b", "c,d"], ' ');
+        let encoded = q.to_string();
+        let parsed = UrlEncodedData::parse_str(&encoded);
+        assert_eq!(parsed.get_split("k", ' ').unwrap(), ["a b", "c,d"]);
+    }
+}
@@ -0,0 +1,368 @@
+//! # Double-encoding detection and repair
+//!
+//! Misbehaving clients encode twice: `" "` becomes `%2520` instead of `%20`, `"世"` becomes
+//! `%25E4%25B8%2596`. Once decoded by `UrlEncodedDataPairScanner`, such values still contain `%XX`.
+//!
+//! * `analyze` flags values that look multiply encoded, with a `Confidence`.
+//! * `repair` decodes them again, pass after pass, until the result stabilizes (or `max_passes` is reached),
+//!   and reports what changed.
+//!
+//! ```rust
+//! use url_encoded_data::double_encoding::{Confidence, RepairOptions};
+//! use url_encoded_data::UrlEncodedDataPairScanner;
+//! let scanner = UrlEncodedDataPairScanner::from("q=hello%2520world&ok=1");
+//!
+//! let findings = scanner.analyze_double_encoding();
+//! assert_eq!(findings.len(), 1);
+//! assert_eq!(findings[0].key, "q");
+//! assert_eq!(findings[0].confidence, Confidence::High);
+//!
+//! let repaired = scanner.repair_double_encoding(&RepairOptions::default());
+//! assert_eq!(repaired.pairs[0].1, "hello world");
+//! assert_eq!(repaired.changes[0].passes, 1);
+//! ```
+
+use alloc::borrow::Cow;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::codec::{hex_value, percent_decode_bytes};
+use crate::{Pair, UrlEncodedData, UrlEncodedDataPairScanner};
+
+/// Passes tried by `analyze` before giving up
+const MAX_ANALYSIS_PASSES: usize = 8;
+
+/// # How likely a value is multiply encoded
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Confidence {
+    /// `%XX` mixed with `%` that are not followed by two hex digits, eg: `100% of %2F`
+    Low,
+    /// other `%XX` which decode to ASCII, eg: `a%2Fb`
+    Medium,
+    /// still encoded after one more pass (`%2520`), or `%XX` which decode to non-ASCII UTF-8 (`%E4%B8%96`),
+    /// or an encoded space (`%20`), or several `%XX` which all decode to delimiters (`%2F%3D`, ...)
+    High,
+}
+
+/// # A value which looks multiply encoded
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Finding<'a> {
+    /// index of the pair in the scanner output
+    pub index: usize,
+    pub key: Cow<'a, str>,
+    /// the value as decoded once by the scanner
+    pub value: Cow<'a, str>,
+    /// extra decoding passes until the value is stable
+    pub passes: usize,
+    pub confidence: Confidence,
+}
+
+/// # Options of `repair`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RepairOptions {
+    /// maximum extra decoding passes per value
+    pub max_passes: usize,
+    /// values flagged below this confidence are left as they are
+    pub min_confidence: Confidence,
+    /// decode `+` as space in the extra passes, only right if the client form-encoded twice
+    pub plus_as_space: bool,
+}
+
+impl Default for RepairOptions {
+    fn default() -> Self {
+        Self {
+            max_passes: 3,
+            min_confidence: Confidence::Medium,
+            plus_as_space: false,
+        }
+    }
+}
+
+/// # A value changed by `repair`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RepairedValue<'a> {
+    pub index: usize,
+    pub key: Cow<'a, str>,
+    pub before: Cow<'a, str>,
+    pub after: String,
+    /// decoding passes applied
+    pub passes: usize,
+    /// `false` if `max_passes` was reached while the value still changed
+    pub stabilized: bool,
+}
+
+/// # Result of `repair`: all pairs (repaired or not) and the report of what changed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Repaired<'a> {
+    pub prefix: &'a str,
+    pub pairs: Vec<Pair<'a>>,
+    pub changes: Vec<RepairedValue<'a>>,
+}
+
+impl<'a> Repaired<'a> {
+    /// # Into `UrlEncodedData`
+    /// ```rust
+    /// use url_encoded_data::double_encoding::RepairOptions;
+    /// use url_encoded_data::UrlEncodedDataPairScanner;
+    /// let scanner = UrlEncodedDataPairScanner::from("https://abc.com/?q=%25E4%25B8%2596");
+    /// let q = scanner.repair_double_encoding(&RepairOptions::default()).into_url_encoded_data();
+    /// assert_eq!(q.get_first("q").unwrap(), "世");
+    /// assert_eq!(q.to_string(), "https://abc.com/?q=%E4%B8%96");
+    /// ```
+    pub fn into_url_encoded_data(self) -> UrlEncodedData<'a> {
+        UrlEncodedData::from_pairs_with_hasher(self.prefix, "", self.pairs, Default::default())
+    }
+}
+
+/// One more decoding pass, `None` if there is no `%XX` to decode or the result is not UTF-8
+fn decode_pass(s: &str, plus_as_space: bool) -> Option<String> {
+    if !(has_percent_triplet(s) || plus_as_space && s.contains('+')) {
+        return None;
+    }
+    let bytes = percent_decode_bytes(s.as_bytes(), plus_as_space)?;
+    String::from_utf8(bytes).ok()
+}
+
+fn has_percent_triplet(s: &str) -> bool {
+    let bytes = s.as_bytes();
+    bytes
+        .windows(3)
+        .any(|w| w[0] == b'%' && hex_value(w[1]).is_some() && hex_value(w[2]).is_some())
+}
+
+/// (count of `%XX`, count of `%` not followed by two hex digits)
+fn count_percents(s: &str) -> (usize, usize) {
+    let bytes = s.as_bytes();
+    let (mut triplets, mut stray) = (0, 0);
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let is_triplet = bytes.get(i + 1).copied().and_then(hex_value).is_some()
+                && bytes.get(i + 2).copied().and_then(hex_value).is_some();
+            if is_triplet {
+                triplets += 1;
+                i += 3;
+                continue;
+            }
+            stray += 1;
+        }
+        i += 1;
+    }
+    (triplets, stray)
+}
+
+fn is_delimiter(c: char) -> bool {
+    matches!(
+        c,
+        ' ' | '/' | ':' | '?' | '#' | '&' | '=' | '+' | ',' | ';' | '@' | '"' | '%'
+    )
+}
+
+/// Extra passes and confidence of a decoded value, `None` if it does not look multiply encoded
+pub fn analyze_value(value: &str) -> Option<(usize, Confidence)> {
+    let first = decode_pass(value, false)?;
+    let mut passes = 1;
+    let mut current = first.clone();
+    while passes < MAX_ANALYSIS_PASSES {
+        match decode_pass(&current, false) {
+            Some(next) => {
+                current = next;
+                passes += 1;
+            }
+            None => break,
+        }
+    }
+
+    let (triplets, stray) = count_percents(value);
+    let confidence = if stray > 0 {
+        Confidence::Low
+    } else if passes >= 2 || !first.is_ascii() {
+        Confidence::High
+    } else {
+        // the bytes of the `%XX` sequences
+        let encoded = || {
+            value
+                .split('%')
+                .skip(1)
+                .filter_map(|s| s.get(..2))
+                .filter_map(|hex| u8::from_str_radix(hex, 16).ok())
+        };
+        let has_space = encoded().any(|b| b == b' ');
+        let delimiters_only = encoded().all(|b| is_delimiter(b as char));
+        if has_space || (triplets >= 2 && delimiters_only) {
+            Confidence::High
+        } else {
+            Confidence::Medium
+        }
+    };
+    Some((passes, confidence))
+}
+
+/// # Flag the values of the scanner output which look multiply encoded
+pub fn analyze<'a>(scanner: &UrlEncodedDataPairScanner<'a>) -> Vec<Finding<'a>> {
+    let mut findings = Vec::new();
+    for (index, (key, value)) in scanner.pairs_iterator.enumerate() {
+        if let Some((passes, confidence)) = analyze_value(&value) {
+            findings.push(Finding {
+                index,
+                key,
+                value,
+                passes,
+                confidence,
+            });
+        }
+    }
+    findings
+}
+
+/// # Decode again the values flagged with at least `options.min_confidence`
+pub fn repair<'a>(
+    scanner: &UrlEncodedDataPairScanner<'a>,
+    options: &RepairOptions,
+) -> Repaired<'a> {
+    let mut pairs = Vec::new();
+    let mut changes = Vec::new();
+    for (index, (key, value)) in scanner.pairs_iterator.enumerate() {
+        let flagged = analyze_value(&value)
+            .is_some_and(|(_, confidence)| confidence >= options.min_confidence);
+        if !flagged {
+            pairs.push((key, value));
+            continue;
+        }
+
+        let mut current: String = value.clone().into_owned();
+        let mut passes = 0;
+        let mut stabilized = false;
+        while passes < options.max_passes {
+            match decode_pass(&current, options.plus_as_space) {
+                Some(next) if next != current => {
+                    current = next;
+                    passes += 1;
+                }
+                _ => {
+                    stabilized = true;
+                    break;
+                }
+            }
+        }
+        if !stabilized {
+            stabilized = decode_pass(&current, options.plus_as_space).is_none();
+        }
+
+        if passes == 0 {
+            pairs.push((key, value));
+            continue;
+        }
+        changes.push(RepairedValue {
+            index,
+            key: key.clone(),
+            before: value,
+            after: current.clone(),
+            passes,
+            stabilized,
+        });
+        pairs.push((key, Cow::Owned(current)));
+    }
+    Repaired {
+        prefix: scanner.prefix,
+        pairs,
+        changes,
+    }
+}
+
+impl<'a> UrlEncodedDataPairScanner<'a> {
+    /// # Values which look multiply encoded, see `double_encoding::analyze`
+    pub fn analyze_double_encoding(&self) -> Vec<Finding<'a>> {
+        analyze(self)
+    }
+
+    /// # Repair multiply encoded values, see `double_encoding::repair`
+    pub fn repair_double_encoding(&self, options: &RepairOptions) -> Repaired<'a> {
+        repair(self, options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_confidence() {
+        assert_eq!(analyze_value("plain"), None);
+        assert_eq!(analyze_value("100%"), None);
+        assert_eq!(analyze_value("a%2Fb"), Some((1, Confidence::Medium)));
+        assert_eq!(analyze_value("a%2Fb%3Dc"), Some((1, Confidence::High)));
+        assert_eq!(analyze_value("a%20b"), Some((1, Confidence::High)));
+        assert_eq!(analyze_value("a%2Fb%41c"), Some((1, Confidence::Medium)));
+        assert_eq!(analyze_value("%E4%B8%96"), Some((1, Confidence::High)));
+        assert_eq!(analyze_value("%2520"), Some((2, Confidence::High)));
+        assert_eq!(analyze_value("%252520"), Some((3, Confidence::High)));
+        assert_eq!(analyze_value("50% of %20"), Some((1, Confidence::Low)));
+        // not UTF-8 once decoded: not an encoding of text
+        assert_eq!(analyze_value("%FF%FE"), None);
+    }
+
+    #[test]
+    fn test_repair_report() {
+        let scanner =
+            UrlEncodedDataPairScanner::from("a=%25252F&b=ok&c=50%25+of+%2520&d=%25E4%25B8%2596");
+        let findings = scanner.analyze_double_encoding();
+        let flagged: Vec<_> = findings
+            .iter()
+            .map(|f| (f.index, f.passes, f.confidence))
+            .collect();
+        assert_eq!(
+            flagged,
+            [
+                (0, 2, Confidence::High),
+                (2, 1, Confidence::Low),
+                (3, 1, Confidence::High)
+            ]
+        );
+
+        let repaired = scanner.repair_double_encoding(&RepairOptions {
+            max_passes: 1,
+            ..RepairOptions::default()
+        });
+        let changes: Vec<_> = repaired
+            .changes
+            .iter()
+            .map(|c| {
+                (
+                    c.index,
+                    c.before.as_ref(),
+                    c.after.as_str(),
+                    c.passes,
+                    c.stabilized,
+                )
+            })
+            .collect();
+        assert_eq!(
+            changes,
+            [
+                (0, "%252F", "%2F", 1, false),
+                (3, "%E4%B8%96", "世", 1, true)
+            ]
+        );
+        // the low confidence value is left alone
+        assert_eq!(repaired.pairs[2].1, "50% of %20");
+        assert_eq!(repaired.pairs.len(), 4);
+
+        let repaired = scanner.repair_double_encoding(&RepairOptions::default());
+        assert_eq!(repaired.pairs[0].1, "/");
+        assert!(repaired.changes[0].stabilized);
+    }
+
+    #[test]
+    fn test_repair_plus_as_space() {
+        // "a b/c" form-encoded twice: "a+b%2Fc" -> "a%2Bb%252Fc"
+        let scanner = UrlEncodedDataPairScanner::from("q=a%2Bb%252Fc");
+        let repaired = scanner.repair_double_encoding(&RepairOptions::default());
+        assert_eq!(repaired.pairs[0].1, "a+b/c");
+        let repaired = scanner.repair_double_encoding(&RepairOptions {
+            plus_as_space: true,
+            ..RepairOptions::default()
+        });
+        assert_eq!(repaired.pairs[0].1, "a b/c");
+    }
+}
@@ -0,0 +1,657 @@
+//! # Filter operators: `price[gte]=10`, `status[in]=a,b`, `price=gte:10`
+//!
+//! A `Filter` is a conjunction of `Condition`s parsed from the query, each one a whitelisted field,
+//! an `Operator` and operands converted to the field's `Type`. Operators come in bracket keys
+//! (`price[gte]=10`) or, if `FilterConfig::prefix` is on, as a value prefix (`price=gte:10`);
+//! a plain `price=10` is `eq`.
+//!
+//! Operator names are a `Vocabulary`: the default one is `eq`, `ne`, `gt`, `gte`, `lt`, `lte`, `in`,
+//! `nin`, `like` and `exists`, and can be replaced (`ge`, `>=`, ...) or restricted.
+//! `in` and `nin` take a comma separated list (see `delimited::split`), `exists` a boolean
+//! (an empty value is `true`), `like` a string pattern.
+//!
+//! Bracket keys whose suffix is not an operator name (`fields[articles]`) and plain keys out of the
+//! whitelist are other parameters, and are ignored; an operator on a field out of the whitelist is
+//! an error.
+//!
+//! ```rust
+//! use url_encoded_data::filter::{Filter, FilterConfig, Notation, Operand, Operands, Operator, Type};
+//! use url_encoded_data::UrlEncodedData;
+//!
+//! let config = FilterConfig::new()
+//!     .field("price", Type::Float)
+//!     .field("status", Type::String)
+//!     .field("archived", Type::Boolean);
+//! let q = UrlEncodedData::parse_str("/items?price%5Bgte%5D=10&status%5Bin%5D=new,paid&archived=false&page=2");
+//! let filter = Filter::from_query(&q, &config).unwrap();
+//!
+//! assert_eq!(filter.0[0].field, "price");
+//! assert_eq!(filter.0[0].operator, Operator::Gte);
+//! assert_eq!(filter.0[0].operands, Operands::One(Operand::Float(10.0)));
+//! assert_eq!(filter.0[2].operands, Operands::One(Operand::Boolean(false)));
+//!
+//! assert_eq!(
+//!     filter.to_query_string(&config, Notation::Prefix).unwrap(),
+//!     "price=gte:10&status=in:new,paid&archived=false"
+//! );
+//! assert!(Filter::from_query(&UrlEncodedData::parse_str("secret[ne]=1"), &config).is_err());
+//! ```
+
+use alloc::borrow::Cow;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::{Display, Formatter};
+use core::hash::BuildHasher;
+
+use crate::canonical::{ordered_pairs, KeyOrder, ValueOrder};
+use crate::codec::append_pair;
+use crate::delimited::{join, split};
+use crate::{Pair, UrlEncodedData};
+
+const LIST_DELIMITER: char = ',';
+const PREFIX_DELIMITER: char = ':';
+
+/// # Comparison operators
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Operator {
+    Eq,
+    Ne,
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+    /// one of a list
+    In,
+    /// none of a list
+    Nin,
+    /// pattern match
+    Like,
+    /// the field is set, or not
+    Exists,
+}
+
+impl Operator {
+    pub const ALL: [Operator; 10] = [
+        Operator::Eq,
+        Operator::Ne,
+        Operator::Gt,
+        Operator::Gte,
+        Operator::Lt,
+        Operator::Lte,
+        Operator::In,
+        Operator::Nin,
+        Operator::Like,
+        Operator::Exists,
+    ];
+
+    /// The name in the default vocabulary
+    pub fn name(self) -> &'static str {
+        match self {
+            Operator::Eq => "eq",
+            Operator::Ne => "ne",
+            Operator::Gt => "gt",
+            Operator::Gte => "gte",
+            Operator::Lt => "lt",
+            Operator::Lte => "lte",
+            Operator::In => "in",
+            Operator::Nin => "nin",
+            Operator::Like => "like",
+            Operator::Exists => "exists",
+        }
+    }
+
+    fn takes_list(self) -> bool {
+        matches!(self, Operator::In | Operator::Nin)
+    }
+}
+
+impl Display for Operator {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// # Operator names; several names may map to one operator, the first one is used to serialize
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Vocabulary {
+    names: Vec<(Cow<'static, str>, Operator)>,
+}
+
+impl Default for Vocabulary {
+    /// `eq`, `ne`, `gt`, `gte`, `lt`, `lte`, `in`, `nin`, `like`, `exists`
+    fn default() -> Self {
+        Self {
+            names: Operator::ALL
+                .iter()
+                .map(|&op| (Cow::Borrowed(op.name()), op))
+                .collect(),
+        }
+    }
+}
+
+impl Vocabulary {
+    /// No operator at all
+    pub fn empty() -> Self {
+        Self { names: Vec::new() }
+    }
+
+    /// Add a name of `operator`
+    /// ```rust
+    /// use url_encoded_data::filter::{Operator, Vocabulary};
+    /// let v = Vocabulary::empty().name(">=", Operator::Gte).name("ge", Operator::Gte);
+    /// assert_eq!(v.operator("ge"), Some(Operator::Gte));
+    /// assert_eq!(v.name_of(Operator::Gte), Some(">="));
+    /// assert_eq!(v.operator("gte"), None);
+    /// ```
+    pub fn name(mut self, name: impl Into<Cow<'static, str>>, operator: Operator) -> Self {
+        self.names.push((name.into(), operator));
+        self
+    }
+
+    /// The operator named `name`
+    pub fn operator(&self, name: &str) -> Option<Operator> {
+        self.names
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|&(_, op)| op)
+    }
+
+    /// The first name of `operator`
+    pub fn name_of(&self, operator: Operator) -> Option<&str> {
+        self.names
+            .iter()
+            .find(|&&(_, op)| op == operator)
+            .map(|(n, _)| n.as_ref())
+    }
+}
+
+/// # Type of a field, its operands are converted to
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Type {
+    String,
+    /// `i64`
+    Integer,
+    /// finite `f64`
+    Float,
+    /// `true` or `false`
+    Boolean,
+}
+
+impl Type {
+    /// Operators making sense on the type: all of them on strings, no `like` on numbers,
+    /// only `eq`, `ne` and `exists` on booleans
+    pub fn operators(self) -> &'static [Operator] {
+        match self {
+            Type::String => &Operator::ALL,
+            Type::Integer | Type::Float => &[
+                Operator::Eq,
+                Operator::Ne,
+                Operator::Gt,
+                Operator::Gte,
+                Operator::Lt,
+                Operator::Lte,
+                Operator::In,
+                Operator::Nin,
+                Operator::Exists,
+            ],
+            Type::Boolean => &[Operator::Eq, Operator::Ne, Operator::Exists],
+        }
+    }
+
+    fn parse(self, value: &str) -> Option<Operand> {
+        match self {
+            Type::String => Some(Operand::String(value.to_string())),
+            Type::Integer => value.parse().ok().map(Operand::Integer),
+            Type::Float => value
+                .parse::<f64>()
+                .ok()
+                .filter(|n| n.is_finite())
+                .map(Operand::Float),
+            Type::Boolean => match value {
+                "true" => Some(Operand::Boolean(true)),
+                "false" => Some(Operand::Boolean(false)),
+                _ => None,
+            },
+        }
+    }
+}
+
+impl Display for Type {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.write_str(match self {
+            Type::String => "string",
+            Type::Integer => "integer",
+            Type::Float => "float",
+            Type::Boolean => "boolean",
+        })
+    }
+}
+
+/// # A typed operand
+#[derive(Clone, Debug, PartialEq)]
+pub enum Operand {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+}
+
+impl Display for Operand {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Operand::String(s) => f.write_str(s),
+            Operand::Integer(n) => write!(f, "{}", n),
+            Operand::Float(n) => write!(f, "{}", n),
+            Operand::Boolean(b) => write!(f, "{}", b),
+        }
+    }
+}
+
+/// # Operands of a condition: a list for `in` and `nin`, one otherwise
+#[derive(Clone, Debug, PartialEq)]
+pub enum Operands {
+    One(Operand),
+    List(Vec<Operand>),
+}
+
+/// # A condition on a field
+#[derive(Clone, Debug, PartialEq)]
+pub struct Condition {
+    pub field: String,
+    pub operator: Operator,
+    pub operands: Operands,
+}
+
+/// # A filterable field, its type and allowed operators
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Field {
+    pub name: Cow<'static, str>,
+    pub ty: Type,
+    pub operators: Vec<Operator>,
+}
+
+/// # Notation of operators in the query
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Notation {
+    /// `price[gte]=10`
+    Bracket,
+    /// `price=gte:10`
+    Prefix,
+}
+
+/// # Errors of parsing and serializing
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// an operator on a field out of the whitelist
+    UnknownField(String),
+    /// `price[foo]=1`, `foo` out of the vocabulary
+    UnknownOperator { field: String, operator: String },
+    /// an operator not allowed on the field
+    DisallowedOperator { field: String, operator: Operator },
+    /// an operand not of the field's type
+    InvalidOperand {
+        field: String,
+        value: String,
+        expected: Type,
+    },
+    /// serializing an operator without a name in the vocabulary
+    UnnamedOperator(Operator),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::UnknownField(field) => write!(f, "field {:?} is not filterable", field),
+            Error::UnknownOperator { field, operator } => {
+                write!(f, "unknown operator on {}: {:?}", field, operator)
+            }
+            Error::DisallowedOperator { field, operator } => {
+                write!(f, "operator {} is not allowed on {}", operator, field)
+            }
+            Error::InvalidOperand {
+                field,
+                value,
+                expected,
+            } => write!(f, "invalid {} operand of {}: {:?}", expected, field, value),
+            Error::UnnamedOperator(operator) => {
+                write!(f, "operator {} has no name in the vocabulary", operator)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
+/// # Whitelist of fields, vocabulary and notations accepted
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FilterConfig {
+    pub vocabulary: Vocabulary,
+    pub fields: Vec<Field>,
+    /// accept `price=gte:10`
+    pub prefix: bool,
+}
+
+impl Default for FilterConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FilterConfig {
+    /// Default vocabulary, no field, prefix notation accepted
+    pub fn new() -> Self {
+        Self {
+            vocabulary: Vocabulary::default(),
+            fields: Vec::new(),
+            prefix: true,
+        }
+    }
+
+    /// Allow `name`, with the operators of its type
+    pub fn field(self, name: impl Into<Cow<'static, str>>, ty: Type) -> Self {
+        self.field_with(name, ty, ty.operators())
+    }
+
+    /// Allow `name`, with `operators` only
+    pub fn field_with(
+        mut self,
+        name: impl Into<Cow<'static, str>>,
+        ty: Type,
+        operators: &[Operator],
+    ) -> Self {
+        self.fields.push(Field {
+            name: name.into(),
+            ty,
+            operators: operators.to_vec(),
+        });
+        self
+    }
+
+    pub fn vocabulary(mut self, vocabulary: Vocabulary) -> Self {
+        self.vocabulary = vocabulary;
+        self
+    }
+
+    pub fn prefix(mut self, prefix: bool) -> Self {
+        self.prefix = prefix;
+        self
+    }
+
+    fn field_of(&self, name: &str) -> Option<&Field> {
+        self.fields.iter().find(|field| field.name == name)
+    }
+
+    /// `price=gte:10` is `(gte, "10")`; a value without a known prefix is `eq`
+    fn split_prefix<'v>(&self, value: &'v str) -> Option<(Operator, &'v str)> {
+        let (name, rest) = value.split_once(PREFIX_DELIMITER)?;
+        self.vocabulary.operator(name).map(|op| (op, rest))
+    }
+}
+
+/// `field[op]`: (`field`, `op`)
+fn split_bracket(key: &str) -> Option<(&str, &str)> {
+    let inner = key.strip_suffix(']')?;
+    let open = inner.rfind('[')?;
+    Some((&inner[..open], &inner[open + 1..]))
+}
+
+impl Condition {
+    fn parse(field: &Field, operator: Operator, value: &str) -> Result<Self, Error> {
+        if !field.operators.contains(&operator) {
+            return Err(Error::DisallowedOperator {
+                field: field.name.to_string(),
+                operator,
+            });
+        }
+        let invalid = |value: &str, expected: Type| Error::InvalidOperand {
+            field: field.name.to_string(),
+            value: value.to_string(),
+            expected,
+        };
+        let operands = match operator {
+            Operator::Exists if value.is_empty() => Operands::One(Operand::Boolean(true)),
+            Operator::Exists => Operands::One(
+                Type::Boolean
+                    .parse(value)
+                    .ok_or_else(|| invalid(value, Type::Boolean))?,
+            ),
+            Operator::Like => Operands::One(Operand::String(value.to_string())),
+            op if op.takes_list() => Operands::List(
+                split(value, LIST_DELIMITER)
+                    .iter()
+                    .map(|item| field.ty.parse(item).ok_or_else(|| invalid(item, field.ty)))
+                    .collect::<Result<_, _>>()?,
+            ),
+            _ => Operands::One(
+                field
+                    .ty
+                    .parse(value)
+                    .ok_or_else(|| invalid(value, field.ty))?,
+            ),
+        };
+        Ok(Self {
+            field: field.name.to_string(),
+            operator,
+            operands,
+        })
+    }
+
+    fn value(&self) -> String {
+        match &self.operands {
+            Operands::One(operand) => operand.to_string(),
+            Operands::List(operands) => {
+                let items: Vec<String> = operands.iter().map(Operand::to_string).collect();
+                join(&items, LIST_DELIMITER)
+            }
+        }
+    }
+}
+
+/// # Conditions, all of them have to match
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Filter(pub Vec<Condition>);
+
+impl Filter {
+    /// # Conditions of the query, in the original order of the keys
+    pub fn from_query<S: BuildHasher + Clone>(
+        data: &UrlEncodedData<'_, S>,
+        config: &FilterConfig,
+    ) -> Result<Self, Error> {
+        let mut conditions = Vec::new();
+        for (key, value) in ordered_pairs(data, KeyOrder::Original, ValueOrder::Original) {
+            if let Some((name, op_name)) = split_bracket(key) {
+                let field = config.field_of(name);
+                let operator = config.vocabulary.operator(op_name);
+                match (field, operator) {
+                    (Some(field), Some(operator)) => {
+                        conditions.push(Condition::parse(field, operator, value)?)
+                    }
+                    (Some(_), None) => {
+                        return Err(Error::UnknownOperator {
+                            field: name.to_string(),
+                            operator: op_name.to_string(),
+                        })
+                    }
+                    (None, Some(_)) => return Err(Error::UnknownField(name.to_string())),
+                    // not a filter: `fields[articles]=title`
+                    (None, None) => {}
+                }
+            } else if let Some(field) = config.field_of(key) {
+                let (operator, value) = match config.split_prefix(value) {
+                    Some(prefixed) if config.prefix => prefixed,
+                    _ => (Operator::Eq, value),
+                };
+                conditions.push(Condition::parse(field, operator, value)?);
+            }
+        }
+        Ok(Self(conditions))
+    }
+
+    /// # Pairs of the conditions in `notation`; they parse back to the same filter
+    ///
+    /// `eq` is a plain pair, unless its value would read as another operator with `config`.
+    /// `Notation::Prefix` falls back to `Notation::Bracket` if `config.prefix` is off, since
+    /// value prefixes would then be read as part of the value.
+    pub fn to_pairs(
+        &self,
+        config: &FilterConfig,
+        notation: Notation,
+    ) -> Result<Vec<Pair<'static>>, Error> {
+        let name_of = |operator: Operator| {
+            config
+                .vocabulary
+                .name_of(operator)
+                .ok_or(Error::UnnamedOperator(operator))
+        };
+        let notation = match notation {
+            Notation::Prefix if !config.prefix => Notation::Bracket,
+            notation => notation,
+        };
+        let mut pairs = Vec::with_capacity(self.0.len());
+        for condition in &self.0 {
+            let value = condition.value();
+            let explicit = condition.operator != Operator::Eq
+                || (config.prefix && config.split_prefix(&value).is_some());
+            let (key, value) = match notation {
+                _ if !explicit => (condition.field.clone(), value),
+                Notation::Bracket => (
+                    [&condition.field, "[", name_of(condition.operator)?, "]"].concat(),
+                    value,
+                ),
+                Notation::Prefix => (
+                    condition.field.clone(),
+                    [name_of(condition.operator)?, ":", &value].concat(),
+                ),
+            };
+            pairs.push((Cow::Owned(key), Cow::Owned(value)));
+        }
+        Ok(pairs)
+    }
+
+    /// # `to_pairs`, form-encoded, with `,` `:` `[` `]` kept literal
+    pub fn to_query_string(
+        &self,
+        config: &FilterConfig,
+        notation: Notation,
+    ) -> Result<String, Error> {
+        let mut out = String::new();
+        for (key, value) in self.to_pairs(config, notation)? {
+            append_pair(&mut out, &key, &value);
+        }
+        Ok(out
+            .replace("%2C", ",")
+            .replace("%3A", ":")
+            .replace("%5B", "[")
+            .replace("%5D", "]"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> FilterConfig {
+        FilterConfig::new()
+            .field("price", Type::Float)
+            .field("qty", Type::Integer)
+            .field("name", Type::String)
+            .field_with("status", Type::String, &[Operator::Eq, Operator::In])
+    }
+
+    #[test]
+    fn test_errors() {
+        let parse = |s: &str| Filter::from_query(&UrlEncodedData::parse_str(s), &config());
+        assert_eq!(
+            parse("qty[gte]=1.5").unwrap_err(),
+            Error::InvalidOperand {
+                field: "qty".to_string(),
+                value: "1.5".to_string(),
+                expected: Type::Integer
+            }
+        );
+        assert_eq!(
+            parse("status[ne]=x").unwrap_err(),
+            Error::DisallowedOperator {
+                field: "status".to_string(),
+                operator: Operator::Ne
+            }
+        );
+        assert!(matches!(
+            parse("price[foo]=1").unwrap_err(),
+            Error::UnknownOperator { .. }
+        ));
+        assert!(matches!(
+            parse("price[like]=1").unwrap_err(),
+            Error::DisallowedOperator { .. }
+        ));
+        assert!(parse("price=inf").is_err());
+        assert!(parse("qty[in]=1,x").is_err());
+        // not filters
+        assert_eq!(
+            parse("fields[a]=b&page=2&other=gt:1").unwrap(),
+            Filter::default()
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let q = UrlEncodedData::parse_str(
+            "name=gt:x&name=eq:lt:y&name%5Blike%5D=a%25&qty%5Bexists%5D=&status%5Bin%5D=a%5C%2Cb,c&price%5Bnin%5D=&price=lte:0.5",
+        );
+        let config = config();
+        let filter = Filter::from_query(&q, &config).unwrap();
+        assert_eq!(filter.0.len(), 7);
+        assert_eq!(filter.0[1].operator, Operator::Eq);
+        assert_eq!(
+            filter.0[1].operands,
+            Operands::One(Operand::String("lt:y".to_string()))
+        );
+        assert_eq!(
+            filter.0[4].operands,
+            Operands::List(alloc::vec![
+                Operand::String("a,b".to_string()),
+                Operand::String("c".to_string())
+            ])
+        );
+        for notation in [Notation::Bracket, Notation::Prefix] {
+            let s = filter.to_query_string(&config, notation).unwrap();
+            let parsed = Filter::from_query(&UrlEncodedData::parse_str(&s), &config).unwrap();
+            assert_eq!(parsed, filter, "{}", s);
+        }
+        assert_eq!(
+            filter.to_query_string(&config, Notation::Bracket).unwrap(),
+            "name[gt]=x&name[eq]=lt:y&name[like]=a%25&qty[exists]=true&status[in]=a%5C,b,c&price[nin]=&price[lte]=0.5"
+        );
+
+        // without value prefixes, `Prefix` is written as `Bracket`
+        let config = config.prefix(false);
+        let q = UrlEncodedData::parse_str("name%5Bne%5D=x&qty%5Bgte%5D=3");
+        let filter = Filter::from_query(&q, &config).unwrap();
+        let s = filter.to_query_string(&config, Notation::Prefix).unwrap();
+        assert_eq!(s, "name[ne]=x&qty[gte]=3");
+        let parsed = Filter::from_query(&UrlEncodedData::parse_str(&s), &config).unwrap();
+        assert_eq!(parsed, filter);
+    }
+
+    #[test]
+    fn test_custom_vocabulary() {
+        let config = FilterConfig::new()
+            .vocabulary(Vocabulary::empty().name(">=", Operator::Gte))
+            .prefix(false)
+            .field("qty", Type::Integer);
+        let q = UrlEncodedData::parse_str("qty%5B%3E%3D%5D=3&qty%5Bgte%5D=3");
+        assert!(Filter::from_query(&q, &config).is_err());
+        let q = UrlEncodedData::parse_str("qty%5B%3E%3D%5D=3");
+        let filter = Filter::from_query(&q, &config).unwrap();
+        assert_eq!(
+            filter.to_query_string(&config, Notation::Bracket).unwrap(),
+            "qty[%3E%3D]=3"
+        );
+        let eq = Filter::from_query(&UrlEncodedData::parse_str("qty=3"), &config).unwrap();
+        assert_eq!(
+            eq.to_pairs(&config, Notation::Prefix).unwrap(),
+            [(Cow::from("qty"), Cow::from("3"))]
+        );
+    }
+}
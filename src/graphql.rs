@@ -0,0 +1,345 @@
+//! # GraphQL over HTTP: GET requests
+//!
+//! A GraphQL GET request carries `query`, `operationName`, `variables` and `extensions` in its query
+//! string, the last two JSON-encoded. `Request::from_query` reads and decodes them; `Request::to_url`
+//! writes them back, form-encoded and always in that order, so one request gives one url (cacheable).
+//!
+//! Automatic persisted queries (APQ): `with_persisted_query` adds the `persistedQuery` extension,
+//! `{"version":1,"sha256Hash":"<hex SHA-256 of the query>"}`. A client first sends the hash alone
+//! (`without_query`), then the query with its hash if the server does not know it yet.
+//! `from_query` accepts a request without a query if it has a hash, and rejects a query which does
+//! not match its hash.
+//!
+//! GET must not be used for mutations: checking the operation is left to the executor.
+//!
+//! ```rust
+//! use serde_json::json;
+//! use url_encoded_data::graphql::{sha256_hash, Request};
+//! use url_encoded_data::UrlEncodedData;
+//!
+//! let query = "query Hero($episode: Episode) { hero(episode: $episode) { name } }";
+//! let request = Request::new(query)
+//!     .operation_name("Hero")
+//!     .variable("episode", "JEDI")
+//!     .with_persisted_query();
+//!
+//! // first try of APQ: the hash only
+//! let url = request.clone().without_query().to_url("https://api.com/graphql");
+//! assert_eq!(
+//!     url,
+//!     format!(
+//!         "https://api.com/graphql?operationName=Hero&variables=%7B%22episode%22%3A%22JEDI%22%7D\
+//!          &extensions=%7B%22persistedQuery%22%3A%7B%22sha256Hash%22%3A%22{}%22%2C%22version%22%3A1%7D%7D",
+//!         sha256_hash(query)
+//!     )
+//! );
+//!
+//! let parsed = Request::from_query(&UrlEncodedData::parse_str(&request.to_url("https://api.com/graphql"))).unwrap();
+//! assert_eq!(parsed, request);
+//! assert_eq!(parsed.variables.unwrap()["episode"], json!("JEDI"));
+//!
+//! assert!(Request::from_query(&UrlEncodedData::parse_str("query={a}&variables=%7Bx")).is_err());
+//! ```
+
+use std::fmt::{Display, Formatter, Write};
+use std::hash::BuildHasher;
+
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+
+use crate::codec::append_pair;
+use crate::UrlEncodedData;
+
+const PERSISTED_QUERY: &str = "persistedQuery";
+
+/// # Errors of parsing
+#[derive(Debug)]
+pub enum Error {
+    /// neither a query nor a persisted-query hash
+    MissingQuery,
+    /// a parameter present more than once
+    Duplicated(&'static str),
+    /// `variables` or `extensions` is not valid JSON
+    Json {
+        key: &'static str,
+        error: serde_json::Error,
+    },
+    /// `variables` or `extensions` is valid JSON, but not an object
+    NotAnObject(&'static str),
+    /// a malformed `persistedQuery` extension, or of an unsupported version
+    PersistedQuery(String),
+    /// the query does not match its persisted-query hash
+    HashMismatch,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::MissingQuery => f.write_str("no query and no persisted query"),
+            Error::Duplicated(key) => write!(f, "{} is present more than once", key),
+            Error::Json { key, error } => write!(f, "invalid JSON in {}: {}", key, error),
+            Error::NotAnObject(key) => write!(f, "{} is not a JSON object", key),
+            Error::PersistedQuery(e) => write!(f, "invalid persisted query: {}", e),
+            Error::HashMismatch => f.write_str("the query does not match its sha256Hash"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Json { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// # Lowercase hex SHA-256 of a query, its APQ `sha256Hash`
+pub fn sha256_hash(query: &str) -> String {
+    let mut out = String::with_capacity(64);
+    for byte in Sha256::digest(query.as_bytes()) {
+        write!(out, "{:02x}", byte).expect("writing to a String cannot fail");
+    }
+    out
+}
+
+/// The only value of `key`; an empty value is absent
+fn single<'d, S: BuildHasher + Clone>(
+    data: &'d UrlEncodedData<'_, S>,
+    key: &'static str,
+) -> Result<Option<&'d str>, Error> {
+    match data.map.get(key).map(|values| values.as_slice()) {
+        None | Some([]) => Ok(None),
+        Some([value]) if value.is_empty() => Ok(None),
+        Some([value]) => Ok(Some(value.as_ref())),
+        Some(_) => Err(Error::Duplicated(key)),
+    }
+}
+
+/// A JSON object; `null` is absent
+fn json_object(
+    key: &'static str,
+    value: Option<&str>,
+) -> Result<Option<Map<String, Value>>, Error> {
+    let value = match value {
+        None => return Ok(None),
+        Some(value) => value,
+    };
+    match serde_json::from_str(value).map_err(|error| Error::Json { key, error })? {
+        Value::Null => Ok(None),
+        Value::Object(map) => Ok(Some(map)),
+        _ => Err(Error::NotAnObject(key)),
+    }
+}
+
+/// # A GraphQL request
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Request {
+    pub query: Option<String>,
+    pub operation_name: Option<String>,
+    pub variables: Option<Map<String, Value>>,
+    pub extensions: Option<Map<String, Value>>,
+}
+
+impl Request {
+    pub fn new(query: impl Into<String>) -> Self {
+        Self {
+            query: Some(query.into()),
+            ..Self::default()
+        }
+    }
+
+    pub fn operation_name(mut self, name: impl Into<String>) -> Self {
+        self.operation_name = Some(name.into());
+        self
+    }
+
+    /// Set a variable
+    pub fn variable(mut self, name: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.variables
+            .get_or_insert_with(Map::new)
+            .insert(name.into(), value.into());
+        self
+    }
+
+    /// Set an extension
+    pub fn extension(mut self, name: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.extensions
+            .get_or_insert_with(Map::new)
+            .insert(name.into(), value.into());
+        self
+    }
+
+    /// # Add the `persistedQuery` extension of the query; nothing without a query
+    pub fn with_persisted_query(self) -> Self {
+        let hash = match &self.query {
+            None => return self,
+            Some(query) => sha256_hash(query),
+        };
+        let mut persisted = Map::new();
+        persisted.insert("version".to_string(), Value::from(1));
+        persisted.insert("sha256Hash".to_string(), Value::from(hash));
+        self.extension(PERSISTED_QUERY, persisted)
+    }
+
+    /// # Drop the query, to send the persisted-query hash alone
+    pub fn without_query(mut self) -> Self {
+        self.query = None;
+        self
+    }
+
+    /// # The `sha256Hash` of the `persistedQuery` extension, if any
+    pub fn persisted_query(&self) -> Result<Option<&str>, Error> {
+        let persisted = match self
+            .extensions
+            .as_ref()
+            .and_then(|extensions| extensions.get(PERSISTED_QUERY))
+        {
+            None => return Ok(None),
+            Some(persisted) => persisted,
+        };
+        let invalid = |e: &str| Error::PersistedQuery(e.to_string());
+        let persisted = persisted
+            .as_object()
+            .ok_or_else(|| invalid("not an object"))?;
+        match persisted.get("version") {
+            Some(version) if *version == 1 => {}
+            Some(version) => {
+                return Err(Error::PersistedQuery(format!(
+                    "unsupported version {}",
+                    version
+                )))
+            }
+            None => return Err(invalid("no version")),
+        }
+        let hash = persisted
+            .get("sha256Hash")
+            .and_then(Value::as_str)
+            .ok_or_else(|| invalid("no sha256Hash"))?;
+        if hash.len() != 64 || !hash.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(invalid("sha256Hash is not a hex SHA-256"));
+        }
+        Ok(Some(hash))
+    }
+
+    /// # Parse a GET request; empty parameters are absent
+    pub fn from_query<S: BuildHasher + Clone>(data: &UrlEncodedData<'_, S>) -> Result<Self, Error> {
+        let request = Self {
+            query: single(data, "query")?.map(String::from),
+            operation_name: single(data, "operationName")?.map(String::from),
+            variables: json_object("variables", single(data, "variables")?)?,
+            extensions: json_object("extensions", single(data, "extensions")?)?,
+        };
+        match (&request.query, request.persisted_query()?) {
+            (None, None) => Err(Error::MissingQuery),
+            (Some(query), Some(hash)) if !hash.eq_ignore_ascii_case(&sha256_hash(query)) => {
+                Err(Error::HashMismatch)
+            }
+            _ => Ok(request),
+        }
+    }
+
+    /// # `query`, `operationName`, `variables`, `extensions`, form-encoded
+    pub fn to_query_string(&self) -> String {
+        let mut out = String::new();
+        if let Some(query) = &self.query {
+            append_pair(&mut out, "query", query);
+        }
+        if let Some(name) = &self.operation_name {
+            append_pair(&mut out, "operationName", name);
+        }
+        for (key, map) in [
+            ("variables", &self.variables),
+            ("extensions", &self.extensions),
+        ] {
+            if let Some(map) = map {
+                let json = serde_json::to_string(map).expect("a JSON object always serializes");
+                append_pair(&mut out, key, &json);
+            }
+        }
+        out
+    }
+
+    /// # The GET url of the request at `endpoint`, which may have a query already
+    pub fn to_url(&self, endpoint: &str) -> String {
+        let separator = match endpoint.find('?') {
+            None => "?",
+            Some(_) if endpoint.ends_with(['?', '&']) => "",
+            Some(_) => "&",
+        };
+        [endpoint, separator, &self.to_query_string()].concat()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TYPENAME_HASH: &str = "ecf4edb46db40b5132295c0291d62fb65d6759a9eedfa4d5d612dd5ec54a6b38";
+
+    fn parse(s: &str) -> Result<Request, Error> {
+        Request::from_query(&UrlEncodedData::parse_str(s))
+    }
+
+    #[test]
+    fn test_persisted_query() {
+        assert_eq!(sha256_hash("{__typename}"), TYPENAME_HASH);
+        let request = Request::new("{__typename}").with_persisted_query();
+        assert_eq!(request.persisted_query().unwrap(), Some(TYPENAME_HASH));
+
+        let hash_only = request.clone().without_query();
+        let url = hash_only.to_url("/graphql?v=2");
+        assert!(url.starts_with("/graphql?v=2&extensions="));
+        assert_eq!(parse(&url).unwrap(), hash_only);
+        assert_eq!(parse(&request.to_url("/graphql")).unwrap(), request);
+
+        let mismatch = Request::new("{me}").extension(
+            PERSISTED_QUERY,
+            request.extensions.unwrap()[PERSISTED_QUERY].clone(),
+        );
+        assert!(matches!(
+            parse(&mismatch.to_url("")),
+            Err(Error::HashMismatch)
+        ));
+        let v2 = Request::default().extension(
+            PERSISTED_QUERY,
+            serde_json::json!({"version": 2, "sha256Hash": TYPENAME_HASH}),
+        );
+        assert_eq!(
+            parse(&v2.to_url("")).unwrap_err().to_string(),
+            "invalid persisted query: unsupported version 2"
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(matches!(parse("operationName=A"), Err(Error::MissingQuery)));
+        assert!(matches!(
+            parse("query=&variables=null"),
+            Err(Error::MissingQuery)
+        ));
+        assert!(matches!(
+            parse("query={a}&query={b}"),
+            Err(Error::Duplicated("query"))
+        ));
+        assert!(matches!(
+            parse("query={a}&variables=[1]"),
+            Err(Error::NotAnObject("variables"))
+        ));
+        let err = parse("query={a}&extensions={").unwrap_err();
+        assert!(matches!(
+            err,
+            Error::Json {
+                key: "extensions",
+                ..
+            }
+        ));
+        assert!(std::error::Error::source(&err).is_some());
+
+        let request = parse("query=%7Ba%7D&variables=%7B%22n%22%3A1%7D&variables=").unwrap_err();
+        assert!(matches!(request, Error::Duplicated("variables")));
+        let request = parse("query={a}&variables=%7B%22n%22:+1%7D&operationName=").unwrap();
+        assert_eq!(request.variables.unwrap()["n"], 1);
+        assert_eq!(request.operation_name, None);
+    }
+}
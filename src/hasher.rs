@@ -0,0 +1,92 @@
+//! # Hashers for the inner map of `UrlEncodedData`
+//!
+//! `UrlEncodedData` defaults to `DefaultHashBuilder` (std's randomly seeded SipHash), which resists
+//! HashDoS attacks from crafted keys. That is what public endpoints want.
+//!
+//! For trusted traffic (eg: internal services), a fast non-cryptographic hasher can be plugged in
+//! with `UrlEncodedData::parse_str_with_hasher`. `FnvBuildHasher` is shipped for that purpose.
+//!
+//! ```rust
+//! use url_encoded_data::hasher::FnvBuildHasher;
+//! use url_encoded_data::UrlEncodedData;
+//! let q = UrlEncodedData::parse_str_with_hasher("a=1&b=2", FnvBuildHasher::default());
+//! assert_eq!(q.get_first("a").unwrap(), "1");
+//! ```
+
+use core::hash::{BuildHasherDefault, Hasher};
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// # FNV-1a (64 bits) hasher
+///
+/// Fast for the short keys of query strings, but **not** HashDoS resistant: never use it on untrusted input.
+///
+/// ```rust
+/// use core::hash::Hasher;
+/// use url_encoded_data::hasher::FnvHasher;
+/// let mut h = FnvHasher::default();
+/// h.write(b"a");
+/// assert_eq!(h.finish(), 0xaf63dc4c8601ec8c);
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct FnvHasher(u64);
+
+impl Default for FnvHasher {
+    fn default() -> Self {
+        FnvHasher(FNV_OFFSET_BASIS)
+    }
+}
+
+impl Hasher for FnvHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(FNV_PRIME);
+        }
+    }
+}
+
+/// `BuildHasher` of `FnvHasher`, to be used with `UrlEncodedData::parse_str_with_hasher`
+pub type FnvBuildHasher = BuildHasherDefault<FnvHasher>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::UrlEncodedData;
+
+    #[test]
+    fn test_fnv_known_values() {
+        let hash = |bytes: &[u8]| {
+            let mut h = FnvHasher::default();
+            h.write(bytes);
+            h.finish()
+        };
+        assert_eq!(hash(b""), 0xcbf29ce484222325);
+        assert_eq!(hash(b"a"), 0xaf63dc4c8601ec8c);
+        assert_eq!(hash(b"foobar"), 0x85944171f73967e8);
+    }
+
+    #[test]
+    fn test_parse_with_fnv() {
+        let s = "https://abc.com/?c=3&a=1&b=2&c=4";
+        let q = UrlEncodedData::parse_str_with_hasher(s, FnvBuildHasher::default());
+        let d = UrlEncodedData::parse_str(s);
+        assert_eq!(
+            q.to_string_of_original_order(),
+            d.to_string_of_original_order()
+        );
+        assert_eq!(q.to_string_of_sorted_order(), d.to_string_of_sorted_order());
+        assert_eq!(q.get("c").unwrap(), vec!["3", "4"]);
+
+        let q = UrlEncodedData::builder_with_hasher(s, FnvBuildHasher::default())
+            .set_one("a", "100")
+            .done();
+        assert_eq!(q.get_first("a").unwrap(), "100");
+        assert_eq!(q.get_last_occurrence_value("c").unwrap(), "4");
+    }
+}
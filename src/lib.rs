@@ -40,6 +40,20 @@
 //! # Notes
 //! * UrlEncodedDataPairScanner: Pairs Iterator, yields pairs only. (high performant)
 //! * UrlEncodedData: eager version
+//! * search_params::UrlSearchParams: WHATWG `URLSearchParams` compatible, keeps pairs in order
+//! * limits::Limits: bounded parsing (`parse_str_with_limits`, `iter_with_limits`) for public endpoints
+//!
+//! # Cargo features
+//! * `std` (default): `UrlEncodedData` is backed by `HashMap`.
+//! * without `std`: `no_std` + `alloc`. `UrlEncodedDataPairScanner`, the percent codec (`codec`)
+//!   and an ordered-vector-backed (`vec_map::VecMap`) `UrlEncodedData` are available.
+//! * `sigv4` (default): AWS Signature V4 canonical query and presigned urls (`sigv4` module).
+//! * `oauth1` (default): OAuth 1.0a signature base string and signing (`oauth1` module).
+//! * `oauth2` (default): OAuth 2.0 authorization urls with PKCE, callbacks and token requests (`oauth2` module).
+//! * `signed-url` (default): HMAC-signed, expiring urls (`signed_url` module).
+//! * `tracking` (default, needs `std`): tracking-parameter stripping with ClearURLs rules (`tracking` module).
+//! * `webhook` (default): signature verification of form-encoded webhooks (`webhook` module).
+//! * `graphql` (default, needs `std`): GraphQL-over-HTTP GET requests with persisted queries (`graphql` module).
 //!
 //! # Sample
 //! ## Sample of url query string
@@ -365,22 +379,80 @@
 //!
 //!
 
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
 #[cfg(test)]
 #[macro_use]
 extern crate maplit;
 
-use std::borrow::Cow;
-use std::collections::hash_map::Entry;
-use std::collections::HashMap;
-use std::fmt::{Debug, Display, Formatter};
-use url as url_lib;
-use url::form_urlencoded::Parse;
+pub mod canonical;
+pub mod codec;
+pub mod delimited;
+pub mod double_encoding;
+pub mod filter;
+#[cfg(feature = "graphql")]
+pub mod graphql;
+pub mod hasher;
+pub mod limits;
+pub mod link_header;
+pub mod lint;
+pub mod listing;
+pub mod normalize;
+#[cfg(feature = "oauth1")]
+pub mod oauth1;
+#[cfg(feature = "oauth2")]
+pub mod oauth2;
+pub mod odata;
+pub mod openapi;
+pub mod pollution;
+pub mod redact;
+pub mod redirect;
+pub mod rsql;
+pub mod search_params;
+#[cfg(feature = "signed-url")]
+pub mod signed_url;
+#[cfg(feature = "sigv4")]
+pub mod sigv4;
+#[cfg(feature = "tracking")]
+pub mod tracking;
+pub mod vec_map;
+#[cfg(feature = "webhook")]
+pub mod webhook;
+
+use alloc::borrow::{Cow, ToOwned};
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use codec::Parse;
+use core::fmt::{Debug, Display, Formatter};
+use core::hash::BuildHasher;
 
 pub type Pair<'a> = (Cow<'a, str>, Cow<'a, str>);
 // type StringPair = (String, String);
 type StrPair<'a> = (&'a str, &'a str);
 type RefPair<'a> = (&'a Cow<'a, str>, &'a Cow<'a, str>);
 
+/// The `BuildHasher` used by `UrlEncodedData` unless another one is given.
+///
+/// It is std's randomly seeded SipHash, which resists HashDoS on untrusted input.
+/// For trusted traffic, a faster hasher can be plugged in, eg: `hasher::FnvBuildHasher`.
+#[cfg(feature = "std")]
+pub type DefaultHashBuilder = std::collections::hash_map::RandomState;
+
+/// Without `std`, keys are not hashed at all (see `vec_map::VecMap`), this is only a placeholder.
+#[cfg(not(feature = "std"))]
+pub type DefaultHashBuilder = hasher::FnvBuildHasher;
+
+/// The map of `UrlEncodedData`: `HashMap` with `std`, ordered-vector-backed `vec_map::VecMap` without it.
+#[cfg(feature = "std")]
+pub type Map<K, V, S = DefaultHashBuilder> = std::collections::HashMap<K, V, S>;
+
+/// The map of `UrlEncodedData`: `HashMap` with `std`, ordered-vector-backed `vec_map::VecMap` without it.
+#[cfg(not(feature = "std"))]
+pub type Map<K, V, S = DefaultHashBuilder> = vec_map::VecMap<K, V, S>;
+
 /// # Algorithm
 /// 1. If param: `str` contains '?', then url_encoded_string = <there_after>.trim_start('?')
 /// 2. Else, url_encoded_string = param: `str`
@@ -479,11 +551,11 @@ pub fn split_url_encoded_string(s: &str) -> (&str, &str) {
 ///
 /// Panics if called more than once.
 pub fn stringify<'a>(pairs: &'a [StrPair<'a>]) -> String {
-    let mut s = url_lib::form_urlencoded::Serializer::new(String::new());
+    let mut s = String::new();
     for &(k, v) in pairs.iter() {
-        s.append_pair(k, v);
+        codec::append_pair(&mut s, k, v);
     }
-    s.finish()
+    s
 }
 
 /// # A scanner which iterates (decoded_key, decoded_value) pairs in order.
@@ -502,7 +574,7 @@ impl<'a> Display for UrlEncodedDataPairScanner<'a> {
     /// let display = format!("got qs: {}", q);
     /// assert!(display.len() > 3)
     /// ```
-    fn fmt(&self, f: &mut Formatter<'_>) -> ::std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> ::core::fmt::Result {
         write!(f, "{}{}", self.prefix, self.original_data_str)
     }
 }
@@ -584,7 +656,7 @@ impl<'a> UrlEncodedDataPairScanner<'a> {
     /// ```
     pub fn parse_from_str(s: &'a str) -> Self {
         let (prefix, original_data_str) = split_url_encoded_string(s);
-        let pairs_iterator = url_lib::form_urlencoded::parse(original_data_str.as_bytes());
+        let pairs_iterator = codec::parse(original_data_str);
         Self {
             prefix,
             original_data_str,
@@ -609,9 +681,9 @@ impl<'a> From<&'a str> for UrlEncodedDataPairScanner<'a> {
     }
 }
 
-pub struct UrlEncodedDataBuilder<'a>(UrlEncodedData<'a>);
+pub struct UrlEncodedDataBuilder<'a, S = DefaultHashBuilder>(UrlEncodedData<'a, S>);
 
-impl<'a> UrlEncodedDataBuilder<'a> {
+impl<'a, S: BuildHasher + Clone> UrlEncodedDataBuilder<'a, S> {
     pub fn set_one<'b>(&'b mut self, key: &'a str, value: &'a str) -> &'b mut Self {
         // self.0.map.insert(Cow::from(key), vec![Cow::from(value)]);
         // self
@@ -620,14 +692,16 @@ impl<'a> UrlEncodedDataBuilder<'a> {
         self
     }
 
-    pub fn done(&self) -> UrlEncodedData<'a> {
+    pub fn done(&self) -> UrlEncodedData<'a, S> {
         self.0.clone()
     }
 }
 
 /// Represents the form-urlencoded data: eg: url query string, or application/x-www-form-urlencoded of the body.
+///
+/// `S` is the `BuildHasher` of the inner map, see `DefaultHashBuilder` and `parse_str_with_hasher`.
 #[derive(Clone, Debug)]
-pub struct UrlEncodedData<'a> {
+pub struct UrlEncodedData<'a, S = DefaultHashBuilder> {
     // original prefix of the input string before query_string.
     prefix: &'a str,
 
@@ -638,7 +712,10 @@ pub struct UrlEncodedData<'a> {
     original_keys_in_order: Vec<Cow<'a, str>>,
 
     // map: 1 -> many, one key to multiple values.
-    map: HashMap<Cow<'a, str>, Vec<Cow<'a, str>>>,
+    map: Map<Cow<'a, str>, Vec<Cow<'a, str>>, S>,
+
+    // the map holds the pairs of original_data_str, unchanged since parsing.
+    raw_is_current: bool,
     // pairs: Vec<Pair<'a>>,
 }
 
@@ -690,14 +767,14 @@ impl<'a> From<&'a str> for UrlEncodedData<'a> {
     }
 }
 
-impl<'a> Display for UrlEncodedData<'a> {
+impl<'a, S: BuildHasher + Clone> Display for UrlEncodedData<'a, S> {
     /// ```rust
     /// use url_encoded_data::*;
     /// let q = UrlEncodedData::from("abcd=efg");
     /// let display = format!("got qs: {}", q);
     /// assert!(display.len() > 3)
     /// ```
-    fn fmt(&self, f: &mut Formatter<'_>) -> ::std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> ::core::fmt::Result {
         write!(f, "{}", self.to_final_string())
     }
 }
@@ -741,10 +818,40 @@ impl<'a> UrlEncodedData<'a> {
     /// assert_eq!(v.as_ref(), "efg");
     /// ```
     pub fn parse_str(s: &'a str) -> Self {
+        Self::parse_str_with_hasher(s, DefaultHashBuilder::default())
+    }
+
+    pub fn builder(s: &'a str) -> UrlEncodedDataBuilder<'a> {
+        UrlEncodedDataBuilder(Self::parse_str(s))
+    }
+}
+
+impl<'a, S: BuildHasher + Clone> UrlEncodedData<'a, S> {
+    /// # UrlEncodedData from &str, the inner map uses `hash_builder` to hash keys
+    /// ```rust
+    /// use url_encoded_data::hasher::FnvBuildHasher;
+    /// use url_encoded_data::UrlEncodedData;
+    /// let q = UrlEncodedData::parse_str_with_hasher("a=1&b=2&a=3", FnvBuildHasher::default());
+    /// assert_eq!(q.get("a").unwrap(), vec!["1", "3"]);
+    /// assert_eq!(q.get_first("b").unwrap(), "2");
+    /// ```
+    pub fn parse_str_with_hasher(s: &'a str, hash_builder: S) -> Self {
         let (prefix, data_str) = split_url_encoded_string(s);
-        let parse = url_lib::form_urlencoded::parse(data_str.as_bytes());
-        let pairs: Vec<Pair> = parse.into_iter().collect();
-        let mut map: HashMap<Cow<'_, str>, Vec<Cow<'_, str>>> = HashMap::new();
+        let mut data =
+            Self::from_pairs_with_hasher(prefix, data_str, codec::parse(data_str), hash_builder);
+        data.raw_is_current = true;
+        data
+    }
+
+    /// Build from decoded pairs, `original_data_str` is kept as the raw data they came from.
+    /// The pairs are not taken to be those of `original_data_str`: set `raw_is_current` if they are.
+    pub(crate) fn from_pairs_with_hasher(
+        prefix: &'a str,
+        original_data_str: &'a str,
+        pairs: impl IntoIterator<Item = Pair<'a>>,
+        hash_builder: S,
+    ) -> Self {
+        let mut map: Map<Cow<'_, str>, Vec<Cow<'_, str>>, S> = Map::with_hasher(hash_builder);
         let mut original_keys_in_order: Vec<Cow<str>> = vec![];
         for (k, v) in pairs {
             map.entry(k.clone()).or_default().push(v);
@@ -755,14 +862,26 @@ impl<'a> UrlEncodedData<'a> {
 
         Self {
             prefix,
-            original_data_str: data_str,
+            original_data_str,
             map,
             original_keys_in_order,
+            raw_is_current: false,
         }
     }
 
-    pub fn builder(s: &'a str) -> UrlEncodedDataBuilder {
-        UrlEncodedDataBuilder(Self::parse_str(s))
+    /// # The prefix before the url-encoded data, eg: `https://google.com/?`
+    /// ```rust
+    /// use url_encoded_data::UrlEncodedData;
+    /// assert_eq!(UrlEncodedData::parse_str("https://google.com/?q=rust").prefix(), "https://google.com/?");
+    /// assert_eq!(UrlEncodedData::parse_str("q=rust").prefix(), "");
+    /// ```
+    pub fn prefix(&self) -> &'a str {
+        self.prefix
+    }
+
+    /// # Builder of UrlEncodedData with a custom `BuildHasher`
+    pub fn builder_with_hasher(s: &'a str, hash_builder: S) -> UrlEncodedDataBuilder<'a, S> {
+        UrlEncodedDataBuilder(Self::parse_str_with_hasher(s, hash_builder))
     }
 
     /// # As pairs slice in random order, better performance than `as_pairs_of_original_order` and `as_pairs_of_sorted_order`
@@ -929,7 +1048,6 @@ impl<'a> UrlEncodedData<'a> {
 
         let mut keys_in_sorted_order: Vec<_> = self.map.keys().collect();
         keys_in_sorted_order.sort_unstable();
-        dbg!(&keys_in_sorted_order);
 
         for key in keys_in_sorted_order {
             for element in self.map.get(key).unwrap() {
@@ -1017,11 +1135,11 @@ impl<'a> UrlEncodedData<'a> {
     /// assert_eq!(encoded, "hello=%E4%BD%A0%E5%A5%BD&world=%E4%B8%96%E7%95%8C");
     /// ```
     pub fn stringify(pairs: &[RefPair]) -> String {
-        let mut s = url_lib::form_urlencoded::Serializer::new(String::new());
+        let mut s = String::new();
         for (k, v) in pairs.iter() {
-            s.append_pair(k.as_ref(), v.as_ref());
+            codec::append_pair(&mut s, k.as_ref(), v.as_ref());
         }
-        s.finish()
+        s
     }
 
     /// You can just use `.to_string()` instead. (alloc::string::ToString trait is auto-implemented on `T: fmt::Display + ?Sized`).
@@ -1104,7 +1222,7 @@ impl<'a> UrlEncodedData<'a> {
     /// eg: "a=b&a=c&d=&e" => {"a" : ["b", "c"], "d: [""], "": ["e"]}
     pub fn as_map_of_single_key_to_multiple_values(
         &'a self,
-    ) -> &'a HashMap<Cow<'a, str>, Vec<Cow<'a, str>>> {
+    ) -> &'a Map<Cow<'a, str>, Vec<Cow<'a, str>>, S> {
         &self.map
     }
 
@@ -1171,8 +1289,8 @@ impl<'a> UrlEncodedData<'a> {
     /// eg: "a=b&a=c" => {"a" : "b"}
     pub fn as_map_of_single_key_to_first_occurrence_value(
         &'a self,
-    ) -> HashMap<&'a Cow<str>, &'a Cow<str>> {
-        let mut m = HashMap::new();
+    ) -> Map<&'a Cow<'a, str>, &'a Cow<'a, str>, S> {
+        let mut m = Map::with_hasher(self.map.hasher().clone());
         for (k, v) in self.as_pairs() {
             m.entry(k).or_insert(v);
         }
@@ -1225,8 +1343,8 @@ impl<'a> UrlEncodedData<'a> {
     /// eg: "a=b&a=c" => {"a" : "b"}
     pub fn as_map_of_single_key_to_last_occurrence_value(
         &'a self,
-    ) -> HashMap<&'a Cow<str>, &'a Cow<str>> {
-        let mut m = HashMap::new();
+    ) -> Map<&'a Cow<'a, str>, &'a Cow<'a, str>, S> {
+        let mut m = Map::with_hasher(self.map.hasher().clone());
         for (k, v) in self.as_pairs() {
             m.insert(k, v);
         }
@@ -1254,7 +1372,7 @@ impl<'a> UrlEncodedData<'a> {
     ///     assert_eq!(q.get_multiple_values("non-exist"), None);
     /// }
     /// ```
-    pub fn get_multiple_values<'b>(&'a self, key: &'b str) -> Option<&Vec<Cow<str>>> {
+    pub fn get_multiple_values<'b>(&'a self, key: &'b str) -> Option<&'a Vec<Cow<'a, str>>> {
         self.map.get(key)
 
         // Some(
@@ -1282,7 +1400,7 @@ impl<'a> UrlEncodedData<'a> {
     /// assert_eq!(q.get_first_occurrence_value("c").unwrap().as_ref(), "3");
     /// assert_eq!(q.get_first_occurrence_value("non-exist"), None);
     /// ```
-    pub fn get_first_occurrence_value<'b>(&'a self, key: &'b str) -> Option<&'a Cow<str>> {
+    pub fn get_first_occurrence_value<'b>(&'a self, key: &'b str) -> Option<&'a Cow<'a, str>> {
         self.as_map_of_single_key_to_first_occurrence_value()
             .get(&Cow::from(key))
             .copied()
@@ -1310,7 +1428,7 @@ impl<'a> UrlEncodedData<'a> {
     ///     assert_eq!(q.get_last_occurrence_value("non-exist"), None);
     /// }
     /// ```
-    pub fn get_last_occurrence_value<'b>(&'a self, key: &'b str) -> Option<&'a Cow<str>> {
+    pub fn get_last_occurrence_value<'b>(&'a self, key: &'b str) -> Option<&'a Cow<'a, str>> {
         self.as_map_of_single_key_to_last_occurrence_value()
             .get(&Cow::from(key))
             .copied()
@@ -1328,6 +1446,7 @@ impl<'a> UrlEncodedData<'a> {
     /// assert_eq!(q.get("a").unwrap(), vec!["100", "200"]);
     /// ```
     pub fn set(&mut self, key: &'a str, value: &[&'a str]) -> &mut Self {
+        self.raw_is_current = false;
         self.map.insert(
             Cow::from(key),
             value.iter().map(|x| Cow::from(*x)).collect::<Vec<_>>(),
@@ -1347,6 +1466,7 @@ impl<'a> UrlEncodedData<'a> {
     /// assert_eq!(q.get_first("a").unwrap(), "100");
     /// ```
     pub fn set_one<'b>(&'b mut self, key: &'a str, value: &'a str) -> &'b mut Self {
+        self.raw_is_current = false;
         self.map.insert(Cow::from(key), vec![Cow::from(value)]);
         self
     }
@@ -1365,12 +1485,11 @@ impl<'a> UrlEncodedData<'a> {
     /// assert_eq!(q.get_first("hello").unwrap(), "world");
     /// ```
     pub fn push(&mut self, key: &'a str, value: &'a str) -> &mut Self {
-        match self.map.entry(Cow::from(key)) {
-            Entry::Occupied(mut entry) => entry.get_mut().push(Cow::from(value)),
-            Entry::Vacant(entry) => {
-                entry.insert(vec![Cow::from(value)]);
-            }
-        }
+        self.raw_is_current = false;
+        self.map
+            .entry(Cow::from(key))
+            .or_default()
+            .push(Cow::from(value));
         self
     }
 
@@ -1480,6 +1599,7 @@ impl<'a> UrlEncodedData<'a> {
     ///
     // pub fn delete<'b>(&'a mut self, key: &'a str) -> Option<Vec<Cow<'a, str>>> {
     pub fn delete(&mut self, key: &'a str) -> &mut Self {
+        self.raw_is_current = false;
         self.map.remove(&Cow::from(key));
         self
     }
@@ -1506,6 +1626,7 @@ impl<'a> UrlEncodedData<'a> {
     /// ```
     ///
     pub fn clear(mut self) -> Self {
+        self.raw_is_current = false;
         self.map.clear();
         self
     }
@@ -1526,7 +1647,7 @@ impl<'a> UrlEncodedData<'a> {
     /// ```
     ///
     pub fn len(&self) -> usize {
-        self.map.values().into_iter().map(|v| v.len()).sum()
+        self.map.values().map(|v| v.len()).sum()
     }
 
     /// # length of keys
@@ -1622,7 +1743,7 @@ impl<'a> UrlEncodedData<'a> {
     /// assert_eq!(q.keys_of_original_order(), vec!["c", "b", "a", "d"]);
     /// ```
     ///
-    pub fn keys_of_original_order(&self) -> Vec<Cow<str>> {
+    pub fn keys_of_original_order(&self) -> Vec<Cow<'_, str>> {
         let mut ks: Vec<_> = self.map.keys().cloned().collect();
         let mut elements_need_to_insert_at_front = vec![];
         for i in self.original_keys_in_order.iter() {
@@ -1649,7 +1770,7 @@ impl<'a> UrlEncodedData<'a> {
     /// assert_eq!(q.keys_of_sorted_order(), vec!["a", "b", "c", "d"]);
     /// ```
     ///
-    pub fn keys_of_sorted_order(&self) -> Vec<Cow<str>> {
+    pub fn keys_of_sorted_order(&self) -> Vec<Cow<'_, str>> {
         let mut ks: Vec<_> = self.map.keys().cloned().collect();
         ks.sort_unstable();
         ks
@@ -1856,11 +1977,11 @@ mod test_qs {
     #[test]
     fn test_to_string() {
         let scanner = UrlEncodedDataPairScanner::from("a=b");
-        println!("{}", scanner.to_string());
+        println!("{}", scanner);
 
         // test clone, then 100% test coverage.
         let scanner_clone = scanner.clone();
-        println!("{}", scanner_clone.to_string())
+        println!("{}", scanner_clone)
     }
 
     #[test]
@@ -0,0 +1,374 @@
+//! # Resource limits on parsing
+//!
+//! `UrlEncodedData::parse_str` and `UrlEncodedDataPairScanner::iter` accept inputs of any size.
+//! For public endpoints, parse with `Limits`: pair count, key and value length, distinct keys and total
+//! decoded size are bounded, and `Limits::default()` is safe for typical query strings and forms.
+//!
+//! Exceeding a limit is an `Error`, or, with `OnExceed::Truncate`, the end of the data:
+//! the pairs before the offending one are kept, and the error is reported in `truncated`.
+//! Lengths are in bytes of the decoded text; oversized raw components are rejected before being decoded.
+//!
+//! ```rust
+//! use url_encoded_data::limits::{Error, Limits, OnExceed};
+//! use url_encoded_data::UrlEncodedData;
+//!
+//! let limits = Limits::default().max_pairs(2);
+//! let q = UrlEncodedData::parse_str_with_limits("a=1&b=2", &limits).unwrap();
+//! assert_eq!(q.data.get_first("b").unwrap(), "2");
+//! assert_eq!(
+//!     UrlEncodedData::parse_str_with_limits("a=1&b=2&c=3", &limits).unwrap_err(),
+//!     Error::TooManyPairs { index: 2, limit: 2 }
+//! );
+//!
+//! let truncating = limits.on_exceed(OnExceed::Truncate);
+//! let q = UrlEncodedData::parse_str_with_limits("a=1&b=2&c=3", &truncating).unwrap();
+//! assert_eq!(q.data.to_string_of_original_order(), "a=1&b=2");
+//! assert_eq!(q.truncated, Some(Error::TooManyPairs { index: 2, limit: 2 }));
+//! ```
+
+use alloc::borrow::Cow;
+use alloc::vec::Vec;
+use core::fmt::{Display, Formatter};
+use core::hash::BuildHasher;
+
+use crate::codec::{self, RawPairs};
+use crate::{
+    split_url_encoded_string, DefaultHashBuilder, Pair, UrlEncodedData, UrlEncodedDataPairScanner,
+};
+
+/// # A limit was exceeded by the pair at `index`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Error {
+    TooManyPairs { index: usize, limit: usize },
+    KeyTooLong { index: usize, limit: usize },
+    ValueTooLong { index: usize, limit: usize },
+    TooManyDistinctKeys { index: usize, limit: usize },
+    DecodedSizeExceeded { index: usize, limit: usize },
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::TooManyPairs { index, limit } => {
+                write!(f, "pair {}: more than {} pairs", index, limit)
+            }
+            Error::KeyTooLong { index, limit } => {
+                write!(f, "pair {}: key longer than {} bytes", index, limit)
+            }
+            Error::ValueTooLong { index, limit } => {
+                write!(f, "pair {}: value longer than {} bytes", index, limit)
+            }
+            Error::TooManyDistinctKeys { index, limit } => {
+                write!(f, "pair {}: more than {} distinct keys", index, limit)
+            }
+            Error::DecodedSizeExceeded { index, limit } => {
+                write!(f, "pair {}: more than {} decoded bytes", index, limit)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
+/// # What to do when a limit is exceeded
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum OnExceed {
+    Error,
+    /// stop at the offending pair, keep the pairs before it
+    Truncate,
+}
+
+/// # Limits of parsing
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Limits {
+    pub max_pairs: usize,
+    /// bytes of the decoded key
+    pub max_key_len: usize,
+    /// bytes of the decoded value
+    pub max_value_len: usize,
+    pub max_distinct_keys: usize,
+    /// bytes of all decoded keys and values
+    pub max_decoded_size: usize,
+    pub on_exceed: OnExceed,
+}
+
+impl Default for Limits {
+    /// 1000 pairs, 512 distinct keys, keys of 1 KiB, values of 64 KiB, 1 MiB in total, errors
+    fn default() -> Self {
+        Self {
+            max_pairs: 1000,
+            max_key_len: 1024,
+            max_value_len: 64 * 1024,
+            max_distinct_keys: 512,
+            max_decoded_size: 1024 * 1024,
+            on_exceed: OnExceed::Error,
+        }
+    }
+}
+
+impl Limits {
+    /// No limit, the behavior of `parse_str`
+    pub fn unlimited() -> Self {
+        Self {
+            max_pairs: usize::MAX,
+            max_key_len: usize::MAX,
+            max_value_len: usize::MAX,
+            max_distinct_keys: usize::MAX,
+            max_decoded_size: usize::MAX,
+            on_exceed: OnExceed::Error,
+        }
+    }
+
+    pub fn max_pairs(mut self, max_pairs: usize) -> Self {
+        self.max_pairs = max_pairs;
+        self
+    }
+
+    pub fn max_key_len(mut self, max_key_len: usize) -> Self {
+        self.max_key_len = max_key_len;
+        self
+    }
+
+    pub fn max_value_len(mut self, max_value_len: usize) -> Self {
+        self.max_value_len = max_value_len;
+        self
+    }
+
+    pub fn max_distinct_keys(mut self, max_distinct_keys: usize) -> Self {
+        self.max_distinct_keys = max_distinct_keys;
+        self
+    }
+
+    pub fn max_decoded_size(mut self, max_decoded_size: usize) -> Self {
+        self.max_decoded_size = max_decoded_size;
+        self
+    }
+
+    pub fn on_exceed(mut self, on_exceed: OnExceed) -> Self {
+        self.on_exceed = on_exceed;
+        self
+    }
+}
+
+/// Decode a raw component if its decoded length may be within `limit`: `%XX` is the longest encoding of a byte
+fn decode_within<'a>(raw: &'a str, limit: usize) -> Option<Cow<'a, str>> {
+    if raw.len() / 3 > limit {
+        return None;
+    }
+    let decoded = codec::decode(raw);
+    if decoded.len() > limit {
+        None
+    } else {
+        Some(decoded)
+    }
+}
+
+/// # Iterator of decoded pairs within `Limits`
+///
+/// With `OnExceed::Error`, yields the error and ends; with `OnExceed::Truncate`, ends and keeps the error
+/// for `truncated`.
+#[derive(Clone, Debug)]
+pub struct LimitedPairs<'a> {
+    raw: RawPairs<'a>,
+    limits: Limits,
+    index: usize,
+    decoded_size: usize,
+    /// sorted
+    distinct_keys: Vec<Cow<'a, str>>,
+    exceeded: Option<Error>,
+}
+
+impl<'a> LimitedPairs<'a> {
+    pub fn new(data: &'a str, limits: &Limits) -> Self {
+        Self {
+            raw: codec::raw_pairs(data),
+            limits: *limits,
+            index: 0,
+            decoded_size: 0,
+            distinct_keys: Vec::new(),
+            exceeded: None,
+        }
+    }
+
+    /// The limit which ended the iteration
+    pub fn truncated(&self) -> Option<Error> {
+        self.exceeded
+    }
+
+    fn check(&mut self, raw_key: &'a str, raw_value: &'a str) -> Result<Pair<'a>, Error> {
+        let (index, limits) = (self.index, &self.limits);
+        if index >= limits.max_pairs {
+            return Err(Error::TooManyPairs {
+                index,
+                limit: limits.max_pairs,
+            });
+        }
+        let key = decode_within(raw_key, limits.max_key_len).ok_or(Error::KeyTooLong {
+            index,
+            limit: limits.max_key_len,
+        })?;
+        let value = decode_within(raw_value, limits.max_value_len).ok_or(Error::ValueTooLong {
+            index,
+            limit: limits.max_value_len,
+        })?;
+        let decoded_size = self.decoded_size + key.len() + value.len();
+        if decoded_size > limits.max_decoded_size {
+            return Err(Error::DecodedSizeExceeded {
+                index,
+                limit: limits.max_decoded_size,
+            });
+        }
+        if let Err(position) = self.distinct_keys.binary_search(&key) {
+            if self.distinct_keys.len() >= limits.max_distinct_keys {
+                return Err(Error::TooManyDistinctKeys {
+                    index,
+                    limit: limits.max_distinct_keys,
+                });
+            }
+            self.distinct_keys.insert(position, key.clone());
+        }
+        self.decoded_size = decoded_size;
+        self.index += 1;
+        Ok((key, value))
+    }
+}
+
+impl<'a> Iterator for LimitedPairs<'a> {
+    type Item = Result<Pair<'a>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.exceeded.is_some() {
+            return None;
+        }
+        let (raw_key, raw_value) = self.raw.next()?;
+        match self.check(raw_key, raw_value) {
+            Ok(pair) => Some(Ok(pair)),
+            Err(error) => {
+                self.exceeded = Some(error);
+                match self.limits.on_exceed {
+                    OnExceed::Error => Some(Err(error)),
+                    OnExceed::Truncate => None,
+                }
+            }
+        }
+    }
+}
+
+/// # Result of parsing within limits, `truncated` by the limit exceeded with `OnExceed::Truncate`
+#[derive(Clone, Debug)]
+pub struct Limited<'a, S = DefaultHashBuilder> {
+    pub data: UrlEncodedData<'a, S>,
+    pub truncated: Option<Error>,
+}
+
+impl<'a> UrlEncodedData<'a> {
+    /// # Parse within `limits`, see `limits::Limits`
+    pub fn parse_str_with_limits(s: &'a str, limits: &Limits) -> Result<Limited<'a>, Error> {
+        Self::parse_str_with_limits_and_hasher(s, limits, DefaultHashBuilder::default())
+    }
+}
+
+impl<'a, S: BuildHasher + Clone> UrlEncodedData<'a, S> {
+    /// # Parse within `limits` with a custom `BuildHasher`
+    pub fn parse_str_with_limits_and_hasher(
+        s: &'a str,
+        limits: &Limits,
+        hash_builder: S,
+    ) -> Result<Limited<'a, S>, Error> {
+        let (prefix, data_str) = split_url_encoded_string(s);
+        let mut pairs_iterator = LimitedPairs::new(data_str, limits);
+        let pairs = pairs_iterator.by_ref().collect::<Result<Vec<_>, _>>()?;
+        let truncated = pairs_iterator.truncated();
+        let mut data = Self::from_pairs_with_hasher(prefix, data_str, pairs, hash_builder);
+        data.raw_is_current = truncated.is_none();
+        Ok(Limited { data, truncated })
+    }
+}
+
+impl<'a> UrlEncodedDataPairScanner<'a> {
+    /// # Iterator of pairs within `limits`, see `limits::LimitedPairs`
+    /// ```rust
+    /// use url_encoded_data::limits::{Error, Limits};
+    /// use url_encoded_data::UrlEncodedDataPairScanner;
+    /// let scanner = UrlEncodedDataPairScanner::from("https://abc.com/?a=1&b=%41%42%43&c=3");
+    /// let pairs: Vec<_> = scanner.iter_with_limits(&Limits::default().max_value_len(2)).collect();
+    /// assert_eq!(pairs.len(), 2);
+    /// assert_eq!(pairs[0], Ok(("a".into(), "1".into())));
+    /// assert_eq!(pairs[1], Err(Error::ValueTooLong { index: 1, limit: 2 }));
+    /// ```
+    pub fn iter_with_limits(&self, limits: &Limits) -> LimitedPairs<'a> {
+        LimitedPairs::new(self.original_data_str, limits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::String;
+
+    fn first_error(data: &str, limits: Limits) -> Option<Error> {
+        UrlEncodedData::parse_str_with_limits(data, &limits).err()
+    }
+
+    #[test]
+    fn test_each_limit() {
+        let limits = Limits::unlimited();
+        assert_eq!(first_error("a=1&&b=2", limits.max_pairs(2)), None);
+        assert_eq!(
+            first_error("abc=1&abcd=2", limits.max_key_len(3)),
+            Some(Error::KeyTooLong { index: 1, limit: 3 })
+        );
+        // the decoded length counts
+        assert_eq!(first_error("k=%41%42%43", limits.max_value_len(3)), None);
+        assert_eq!(
+            first_error("a=1&a=2&b=3&a=4&c=5", limits.max_distinct_keys(2)),
+            Some(Error::TooManyDistinctKeys { index: 4, limit: 2 })
+        );
+        assert_eq!(
+            first_error("ab=cd&e=f", limits.max_decoded_size(5)),
+            Some(Error::DecodedSizeExceeded { index: 1, limit: 5 })
+        );
+    }
+
+    #[test]
+    fn test_huge_input() {
+        let huge = "a=1&".repeat(100_000) + "k=" + &String::from("%41").repeat(1_000_000);
+        assert_eq!(
+            first_error(&huge, Limits::default()),
+            Some(Error::TooManyPairs {
+                index: 1000,
+                limit: 1000
+            })
+        );
+        let q = UrlEncodedData::parse_str_with_limits(
+            &huge,
+            &Limits::default()
+                .max_pairs(usize::MAX)
+                .on_exceed(OnExceed::Truncate),
+        )
+        .unwrap();
+        assert_eq!(q.data.len(), 100_000);
+        assert_eq!(
+            q.truncated,
+            Some(Error::ValueTooLong {
+                index: 100_000,
+                limit: 64 * 1024
+            })
+        );
+    }
+
+    #[test]
+    fn test_scanner_truncates() {
+        let scanner = UrlEncodedDataPairScanner::from("a=1&b=2&c=3");
+        let limits = Limits::default().max_pairs(1).on_exceed(OnExceed::Truncate);
+        let mut pairs = scanner.iter_with_limits(&limits);
+        assert_eq!(pairs.next(), Some(Ok(("a".into(), "1".into()))));
+        assert_eq!(pairs.next(), None);
+        assert_eq!(pairs.next(), None);
+        assert_eq!(
+            pairs.truncated(),
+            Some(Error::TooManyPairs { index: 1, limit: 1 })
+        );
+    }
+}
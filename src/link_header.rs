@@ -0,0 +1,321 @@
+//! # Pagination `Link` header (RFC 8288)
+//!
+//! From the current request and a total count (or the next cursor), `Links` builds the `first`, `prev`,
+//! `next` and `last` links of a list endpoint, as parsed by `listing`.
+//! Only the page, offset or cursor parameter changes: other parameters keep their values and their
+//! sequence in the request (once pairs were changed after parsing: grouped by key in original
+//! order, keys added after parsing last, sorted). Links are always in the order
+//! `first`, `prev`, `next`, `last`, so the same request gives the same header.
+//!
+//! ```rust
+//! use url_encoded_data::link_header::{Links, Rel};
+//! use url_encoded_data::listing::PaginationConfig;
+//! use url_encoded_data::UrlEncodedData;
+//!
+//! let q = UrlEncodedData::parse_str("https://api.com/articles?q=rust&page=2&per_page=10&sort=-id");
+//! let links = Links::for_pages(&q, &PaginationConfig::default(), 35).unwrap();
+//! assert_eq!(links.get(Rel::Next).unwrap(), "https://api.com/articles?q=rust&page=3&per_page=10&sort=-id");
+//! assert_eq!(
+//!     links.to_string(),
+//!     "<https://api.com/articles?q=rust&page=1&per_page=10&sort=-id>; rel=\"first\", \
+//!      <https://api.com/articles?q=rust&page=1&per_page=10&sort=-id>; rel=\"prev\", \
+//!      <https://api.com/articles?q=rust&page=3&per_page=10&sort=-id>; rel=\"next\", \
+//!      <https://api.com/articles?q=rust&page=4&per_page=10&sort=-id>; rel=\"last\""
+//! );
+//! ```
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::{Display, Formatter};
+use core::hash::BuildHasher;
+
+use crate::canonical::{ordered_pairs, original_raw_pairs, KeyOrder, ValueOrder};
+use crate::codec;
+use crate::listing::{Cursor, Error, OffsetLimit, Page, PaginationConfig};
+use crate::search_params::UrlSearchParams;
+use crate::UrlEncodedData;
+
+/// # Relation type of a pagination link
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Rel {
+    First,
+    Prev,
+    Next,
+    Last,
+}
+
+impl Display for Rel {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.write_str(match self {
+            Rel::First => "first",
+            Rel::Prev => "prev",
+            Rel::Next => "next",
+            Rel::Last => "last",
+        })
+    }
+}
+
+/// # A link: target url and relation type
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Link {
+    pub rel: Rel,
+    pub target: String,
+}
+
+impl Display for Link {
+    /// `<target>; rel="next"`
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "<{}>; rel=\"{}\"", self.target, self.rel)
+    }
+}
+
+/// # Pagination links, in `Rel` order; `Display` is the value of the `Link` header
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Links(pub Vec<Link>);
+
+impl Display for Links {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        for (idx, link) in self.0.iter().enumerate() {
+            if idx > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{}", link)?;
+        }
+        Ok(())
+    }
+}
+
+/// Builds the target urls of one request, changing only the pagination parameters
+struct Targets<'d> {
+    prefix: &'d str,
+    params: UrlSearchParams<'d>,
+}
+
+impl<'d> Targets<'d> {
+    fn new<S: BuildHasher + Clone>(data: &'d UrlEncodedData<'_, S>) -> Self {
+        let mut params = UrlSearchParams::default();
+        match original_raw_pairs(data) {
+            Some(raw) => {
+                for (k, v) in raw {
+                    params.append(codec::decode(k), codec::decode(v));
+                }
+            }
+            None => {
+                for (k, v) in ordered_pairs(data, KeyOrder::Original, ValueOrder::Original) {
+                    params.append(k, v);
+                }
+            }
+        }
+        Self {
+            prefix: data.prefix(),
+            params,
+        }
+    }
+
+    /// `changes`: (key, `Some(value)` to set, `None` to delete)
+    fn target(&self, changes: &[(&str, Option<String>)]) -> String {
+        let mut params = self.params.clone();
+        for (key, value) in changes {
+            match value {
+                Some(value) => params.set(key.to_string(), value.clone()),
+                None => params.delete(key, None),
+            };
+        }
+        let query = params.to_string();
+        match (self.prefix, query.is_empty()) {
+            ("", true) => "?".to_string(),
+            ("", false) => ["?", &query].concat(),
+            (prefix, _) if prefix.ends_with('?') => [prefix, &query].concat(),
+            (prefix, _) => [prefix, "?", &query].concat(),
+        }
+    }
+}
+
+impl Links {
+    /// The target of `rel`
+    pub fn get(&self, rel: Rel) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|link| link.rel == rel)
+            .map(|link| link.target.as_str())
+    }
+
+    fn push(&mut self, rel: Rel, target: String) {
+        self.0.push(Link { rel, target });
+    }
+
+    /// # Links of page-number pagination, `total` items; `last` is at least the first page
+    pub fn for_pages<S: BuildHasher + Clone>(
+        data: &UrlEncodedData<'_, S>,
+        config: &PaginationConfig,
+        total: u64,
+    ) -> Result<Self, Error> {
+        let page = Page::from_query(data, config)?;
+        let last = total.div_ceil(page.size).max(1);
+        let targets = Targets::new(data);
+        let to_page = |number: u64| targets.target(&[(config.page_key, Some(number.to_string()))]);
+
+        let mut links = Links::default();
+        links.push(Rel::First, to_page(1));
+        if let Some(prev) = page.prev() {
+            links.push(Rel::Prev, to_page(prev.number.min(last)));
+        }
+        if page.number < last {
+            links.push(Rel::Next, to_page(page.next().number));
+        }
+        links.push(Rel::Last, to_page(last));
+        Ok(links)
+    }
+
+    /// # Links of offset pagination, `total` items
+    /// ```rust
+    /// use url_encoded_data::link_header::{Links, Rel};
+    /// use url_encoded_data::listing::PaginationConfig;
+    /// use url_encoded_data::UrlEncodedData;
+    /// let q = UrlEncodedData::parse_str("/items?limit=20&offset=50");
+    /// let links = Links::for_offsets(&q, &PaginationConfig::default(), 95).unwrap();
+    /// let targets: Vec<_> = links.0.iter().map(|l| (l.rel, l.target.as_str())).collect();
+    /// assert_eq!(
+    ///     targets,
+    ///     [
+    ///         (Rel::First, "/items?limit=20&offset=0"),
+    ///         (Rel::Prev, "/items?limit=20&offset=30"),
+    ///         (Rel::Next, "/items?limit=20&offset=70"),
+    ///         (Rel::Last, "/items?limit=20&offset=80"),
+    ///     ]
+    /// );
+    /// ```
+    pub fn for_offsets<S: BuildHasher + Clone>(
+        data: &UrlEncodedData<'_, S>,
+        config: &PaginationConfig,
+        total: u64,
+    ) -> Result<Self, Error> {
+        let window = OffsetLimit::from_query(data, config)?;
+        let last = total.saturating_sub(1) / window.limit * window.limit;
+        let targets = Targets::new(data);
+        let to_offset =
+            |offset: u64| targets.target(&[(config.offset_key, Some(offset.to_string()))]);
+
+        let mut links = Links::default();
+        links.push(Rel::First, to_offset(0));
+        if let Some(prev) = window.prev() {
+            links.push(Rel::Prev, to_offset(prev.offset.min(last)));
+        }
+        if window.next().offset < total {
+            links.push(Rel::Next, to_offset(window.next().offset));
+        }
+        links.push(Rel::Last, to_offset(last));
+        Ok(links)
+    }
+
+    /// # Links of cursor pagination: `first`, and `next` if there is a `next_cursor`
+    /// ```rust
+    /// use url_encoded_data::link_header::Links;
+    /// use url_encoded_data::listing::PaginationConfig;
+    /// use url_encoded_data::UrlEncodedData;
+    /// let q = UrlEncodedData::parse_str("https://api.com/events?cursor=abc&type=push");
+    /// let links = Links::for_cursor(&q, &PaginationConfig::default(), Some("def")).unwrap();
+    /// assert_eq!(
+    ///     links.to_string(),
+    ///     "<https://api.com/events?type=push>; rel=\"first\", <https://api.com/events?cursor=def&type=push>; rel=\"next\""
+    /// );
+    /// ```
+    pub fn for_cursor<S: BuildHasher + Clone>(
+        data: &UrlEncodedData<'_, S>,
+        config: &PaginationConfig,
+        next_cursor: Option<&str>,
+    ) -> Result<Self, Error> {
+        // validates the request like the list endpoint does
+        Cursor::from_query(data, config)?;
+        let targets = Targets::new(data);
+
+        let mut links = Links::default();
+        links.push(Rel::First, targets.target(&[(config.cursor_key, None)]));
+        if let Some(next_cursor) = next_cursor {
+            links.push(
+                Rel::Next,
+                targets.target(&[(config.cursor_key, Some(next_cursor.to_string()))]),
+            );
+        }
+        Ok(links)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rels(links: &Links) -> Vec<(Rel, &str)> {
+        links.0.iter().map(|l| (l.rel, l.target.as_str())).collect()
+    }
+
+    #[test]
+    fn test_pages_edges() {
+        let config = PaginationConfig::default();
+        // first page of an empty list
+        let q = UrlEncodedData::parse_str("");
+        assert_eq!(
+            rels(&Links::for_pages(&q, &config, 0).unwrap()),
+            [(Rel::First, "?page=1"), (Rel::Last, "?page=1")]
+        );
+        // beyond the last page: prev goes back to the last one
+        let q = UrlEncodedData::parse_str("/a?x=1&x=2&page=9&y=%20");
+        assert_eq!(
+            rels(&Links::for_pages(&q, &config, 45).unwrap()),
+            [
+                (Rel::First, "/a?x=1&x=2&page=1&y=+"),
+                (Rel::Prev, "/a?x=1&x=2&page=3&y=+"),
+                (Rel::Last, "/a?x=1&x=2&page=3&y=+"),
+            ]
+        );
+        assert!(Links::for_pages(&UrlEncodedData::parse_str("page=x"), &config, 1).is_err());
+
+        // a zero size in the config is a size of 1
+        let zero = PaginationConfig {
+            default_size: 0,
+            max_size: 0,
+            ..config
+        };
+        let q = UrlEncodedData::parse_str("page=2");
+        assert_eq!(
+            Links::for_pages(&q, &zero, 3).unwrap().get(Rel::Last),
+            Some("?page=3")
+        );
+        let q = UrlEncodedData::parse_str("offset=1");
+        assert_eq!(
+            Links::for_offsets(&q, &zero, 3).unwrap().get(Rel::Last),
+            Some("?offset=2")
+        );
+    }
+
+    #[test]
+    fn test_interleaved_keys_keep_their_sequence() {
+        let q = UrlEncodedData::parse_str("/a?tag=x&page=2&tag=y");
+        assert_eq!(
+            Links::for_pages(&q, &PaginationConfig::default(), 100)
+                .unwrap()
+                .get(Rel::Next),
+            Some("/a?tag=x&page=3&tag=y")
+        );
+    }
+
+    #[test]
+    fn test_deterministic_with_added_keys() {
+        let config = PaginationConfig::default();
+        let mut q = UrlEncodedData::parse_str("https://api.com/?b=1");
+        q.set_one("z", "1").set_one("c", "1").set_one("a", "1");
+        let header = Links::for_offsets(&q, &config, 10).unwrap().to_string();
+        for _ in 0..10 {
+            let mut again = UrlEncodedData::parse_str("https://api.com/?b=1");
+            again.set_one("a", "1").set_one("z", "1").set_one("c", "1");
+            assert_eq!(
+                Links::for_offsets(&again, &config, 10).unwrap().to_string(),
+                header
+            );
+        }
+        assert_eq!(
+            header,
+            "<https://api.com/?b=1&a=1&c=1&z=1&offset=0>; rel=\"first\", <https://api.com/?b=1&a=1&c=1&z=1&offset=0>; rel=\"last\""
+        );
+    }
+}
@@ -0,0 +1,601 @@
+//! # Security lint of parameter content
+//!
+//! A `Linter` runs its `Rule`s over every key and value, and reports a `Finding` (pair index, key,
+//! rule id and severity) for each match. Rules are pluggable: implement `Rule` and add it with `Linter::with`.
+//!
+//! Built-in rules: `Crlf`, `Nul`, `PathTraversal`, `SqlInjection`, `Xss` and `OverlongUtf8`.
+//! Rules see the bytes of a component decoded lazily (`Text::bytes`), so linting a clean input allocates nothing.
+//! Overlong UTF-8 only survives in raw data: once pairs of `UrlEncodedData` were changed after
+//! parsing, they are linted decoded, where it has already been replaced by `U+FFFD`.
+//!
+//! ```rust
+//! use url_encoded_data::lint::{Linter, Location, Severity};
+//! use url_encoded_data::UrlEncodedDataPairScanner;
+//!
+//! let linter = Linter::default();
+//! let scanner = UrlEncodedDataPairScanner::from("page=2&file=..%2F..%2Fetc%2Fpasswd&next=%0D%0ASet-Cookie:x");
+//! let findings: Vec<_> = scanner
+//!     .lint(&linter)
+//!     .into_iter()
+//!     .map(|f| (f.index, f.key, f.location, f.rule, f.severity))
+//!     .collect();
+//! assert_eq!(
+//!     findings,
+//!     [
+//!         (1, "file".into(), Location::Value, "path-traversal", Severity::High),
+//!         (2, "next".into(), Location::Value, "crlf", Severity::High),
+//!     ]
+//! );
+//! assert!(UrlEncodedDataPairScanner::from("q=rust+lint&page=2").lint(&linter).is_empty());
+//! ```
+
+use alloc::borrow::Cow;
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::hash::BuildHasher;
+
+use crate::canonical::original_raw_pairs;
+use crate::codec::{self, hex_value};
+use crate::{UrlEncodedData, UrlEncodedDataPairScanner};
+
+/// # How serious a finding is
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+/// # Where the rule matched in the pair
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Location {
+    Key,
+    Value,
+}
+
+/// # A rule matched a pair
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Finding<'a> {
+    /// index of the pair: in the scanner output, or in the original data for `UrlEncodedData`
+    pub index: usize,
+    pub key: Cow<'a, str>,
+    pub location: Location,
+    pub rule: &'static str,
+    pub severity: Severity,
+}
+
+/// # A key or a value, as written (still encoded) or already decoded
+#[derive(Clone, Copy, Debug)]
+pub struct Text<'t> {
+    s: &'t str,
+    encoded: bool,
+}
+
+impl<'t> Text<'t> {
+    /// still form-urlencoded, eg: from `UrlEncodedDataPairScanner`
+    pub fn encoded(s: &'t str) -> Self {
+        Self { s, encoded: true }
+    }
+
+    pub fn decoded(s: &'t str) -> Self {
+        Self { s, encoded: false }
+    }
+
+    pub fn is_encoded(&self) -> bool {
+        self.encoded
+    }
+
+    /// the text as written
+    pub fn as_str(&self) -> &'t str {
+        self.s
+    }
+
+    /// the decoded bytes, decoded on the fly
+    pub fn bytes(&self) -> Bytes<'t> {
+        Bytes {
+            input: self.s.as_bytes(),
+            decode: self.encoded,
+        }
+    }
+
+    /// # Does the decoded text contain `needle`, ascii case-insensitive?
+    ///
+    /// A space in `needle` matches any run of ascii whitespace.
+    /// ```rust
+    /// use url_encoded_data::lint::Text;
+    /// assert!(Text::encoded("1+UNION%09%09Select+2").contains("union select"));
+    /// assert!(!Text::encoded("unionselect").contains("union select"));
+    /// ```
+    pub fn contains(&self, needle: &str) -> bool {
+        let mut haystack = self.bytes();
+        loop {
+            if matches_at(haystack.clone(), needle.as_bytes()) {
+                return true;
+            }
+            if haystack.next().is_none() {
+                return false;
+            }
+        }
+    }
+}
+
+fn matches_at(mut haystack: Bytes<'_>, needle: &[u8]) -> bool {
+    for &expected in needle {
+        match haystack.next() {
+            Some(b) if expected == b' ' && b.is_ascii_whitespace() => {
+                while haystack
+                    .clone()
+                    .next()
+                    .is_some_and(|b| b.is_ascii_whitespace())
+                {
+                    haystack.next();
+                }
+            }
+            Some(b) if expected != b' ' && b.eq_ignore_ascii_case(&expected) => {}
+            _ => return false,
+        }
+    }
+    true
+}
+
+/// # Iterator of the decoded bytes of a `Text`
+///
+/// `+` is a space and `%XX` a byte, `%` not followed by two hex digits is kept literally (see `codec::decode`).
+#[derive(Clone, Debug)]
+pub struct Bytes<'t> {
+    input: &'t [u8],
+    decode: bool,
+}
+
+impl<'t> Iterator for Bytes<'t> {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<u8> {
+        let (&byte, rest) = self.input.split_first()?;
+        if self.decode {
+            if byte == b'%' && rest.len() >= 2 {
+                if let (Some(h), Some(l)) = (hex_value(rest[0]), hex_value(rest[1])) {
+                    self.input = &rest[2..];
+                    return Some(h << 4 | l);
+                }
+            }
+            if byte == b'+' {
+                self.input = rest;
+                return Some(b' ');
+            }
+        }
+        self.input = rest;
+        Some(byte)
+    }
+}
+
+/// # A lint rule
+pub trait Rule {
+    /// stable identifier, reported in findings
+    fn id(&self) -> &'static str;
+
+    fn severity(&self) -> Severity;
+
+    /// Does the key or value match? Should not allocate.
+    fn matches(&self, text: Text<'_>) -> bool;
+}
+
+/// Carriage return or line feed: header or log injection
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Crlf;
+
+impl Rule for Crlf {
+    fn id(&self) -> &'static str {
+        "crlf"
+    }
+
+    fn severity(&self) -> Severity {
+        Severity::High
+    }
+
+    fn matches(&self, text: Text<'_>) -> bool {
+        text.bytes().any(|b| b == b'\r' || b == b'\n')
+    }
+}
+
+/// NUL byte: truncation in C strings and file names
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Nul;
+
+impl Rule for Nul {
+    fn id(&self) -> &'static str {
+        "nul"
+    }
+
+    fn severity(&self) -> Severity {
+        Severity::High
+    }
+
+    fn matches(&self, text: Text<'_>) -> bool {
+        text.bytes().any(|b| b == 0)
+    }
+}
+
+/// `../` or `..\`, also once more encoded (`%2e%2e%2f`, `..%5c`, ...)
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PathTraversal;
+
+impl Rule for PathTraversal {
+    fn id(&self) -> &'static str {
+        "path-traversal"
+    }
+
+    fn severity(&self) -> Severity {
+        Severity::High
+    }
+
+    fn matches(&self, text: Text<'_>) -> bool {
+        const SIGNATURES: &[&str] = &[
+            "../",
+            "..\\",
+            "..%2f",
+            "..%5c",
+            "%2e%2e/",
+            "%2e%2e\\",
+            "%2e%2e%2f",
+            "%2e%2e%5c",
+        ];
+        SIGNATURES.iter().any(|s| text.contains(s))
+            || text.bytes().eq(b"..".iter().copied())
+            || ends_with_dot_dot(text)
+    }
+}
+
+/// `/..` or `\..` at the end of the decoded text
+fn ends_with_dot_dot(text: Text<'_>) -> bool {
+    let mut last = [0u8; 3];
+    for b in text.bytes() {
+        last = [last[1], last[2], b];
+    }
+    matches!(last, [b'/' | b'\\', b'.', b'.'])
+}
+
+/// Common SQL injection signatures: tautologies, `UNION SELECT`, stacked queries, comments, timing functions
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SqlInjection;
+
+impl Rule for SqlInjection {
+    fn id(&self) -> &'static str {
+        "sqli"
+    }
+
+    fn severity(&self) -> Severity {
+        Severity::High
+    }
+
+    fn matches(&self, text: Text<'_>) -> bool {
+        const SIGNATURES: &[&str] = &[
+            "' or '",
+            "' or 1",
+            "\" or \"",
+            "\" or 1",
+            " or 1=1",
+            "'--",
+            "' --",
+            "';",
+            "union select",
+            "union all select",
+            "; drop ",
+            "; delete ",
+            "; insert ",
+            "; update ",
+            "sleep(",
+            "benchmark(",
+            "pg_sleep(",
+            "waitfor delay",
+            "xp_cmdshell",
+            "information_schema",
+        ];
+        SIGNATURES.iter().any(|s| text.contains(s))
+    }
+}
+
+/// Common XSS signatures: script tags, event handlers, `javascript:` urls
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Xss;
+
+impl Rule for Xss {
+    fn id(&self) -> &'static str {
+        "xss"
+    }
+
+    fn severity(&self) -> Severity {
+        Severity::High
+    }
+
+    fn matches(&self, text: Text<'_>) -> bool {
+        const SIGNATURES: &[&str] = &[
+            "<script",
+            "</script",
+            "<iframe",
+            "<svg",
+            "<img",
+            "<body",
+            "javascript:",
+            "vbscript:",
+            "data:text/html",
+            "onerror=",
+            "onload=",
+            "onmouseover=",
+            "onfocus=",
+            "document.cookie",
+            "alert(",
+        ];
+        SIGNATURES.iter().any(|s| text.contains(s))
+    }
+}
+
+/// Overlong UTF-8 encodings (`%C0%AE` for `.`, `%E0%80%AF` for `/`, ...), which evade filters
+#[derive(Clone, Copy, Debug, Default)]
+pub struct OverlongUtf8;
+
+impl Rule for OverlongUtf8 {
+    fn id(&self) -> &'static str {
+        "overlong-utf8"
+    }
+
+    fn severity(&self) -> Severity {
+        Severity::Critical
+    }
+
+    fn matches(&self, text: Text<'_>) -> bool {
+        let mut bytes = text.bytes().peekable();
+        while let Some(lead) = bytes.next() {
+            let next = match bytes.peek() {
+                Some(&next) => next,
+                None => return false,
+            };
+            let overlong = match lead {
+                0xC0 | 0xC1 => (0x80..=0xBF).contains(&next),
+                0xE0 => (0x80..=0x9F).contains(&next),
+                0xF0 => (0x80..=0x8F).contains(&next),
+                _ => false,
+            };
+            if overlong {
+                return true;
+            }
+        }
+        false
+    }
+}
+
+/// # Rules run over keys and values
+pub struct Linter {
+    rules: Vec<Box<dyn Rule + Send + Sync>>,
+}
+
+impl core::fmt::Debug for Linter {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_list()
+            .entries(self.rules.iter().map(|rule| rule.id()))
+            .finish()
+    }
+}
+
+impl Default for Linter {
+    /// All the built-in rules
+    fn default() -> Self {
+        Self::empty()
+            .with(Crlf)
+            .with(Nul)
+            .with(PathTraversal)
+            .with(SqlInjection)
+            .with(Xss)
+            .with(OverlongUtf8)
+    }
+}
+
+impl Linter {
+    /// No rule
+    pub fn empty() -> Self {
+        Self { rules: Vec::new() }
+    }
+
+    pub fn with(mut self, rule: impl Rule + Send + Sync + 'static) -> Self {
+        self.rules.push(Box::new(rule));
+        self
+    }
+
+    /// ids of the rules, in order
+    pub fn rule_ids(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.rules.iter().map(|rule| rule.id())
+    }
+
+    fn lint_pair<'a>(
+        &self,
+        findings: &mut Vec<Finding<'a>>,
+        index: usize,
+        key: Text<'_>,
+        value: Text<'_>,
+        key_for_finding: impl Fn() -> Cow<'a, str>,
+    ) {
+        for rule in self.rules.iter() {
+            for (location, text) in [(Location::Key, key), (Location::Value, value)] {
+                if rule.matches(text) {
+                    findings.push(Finding {
+                        index,
+                        key: key_for_finding(),
+                        location,
+                        rule: rule.id(),
+                        severity: rule.severity(),
+                    });
+                }
+            }
+        }
+    }
+
+    /// # Lint the raw pairs of the scanner, in order
+    pub fn lint_scanner<'a>(&self, scanner: &UrlEncodedDataPairScanner<'a>) -> Vec<Finding<'a>> {
+        let mut findings = Vec::new();
+        for (index, (key, value)) in codec::raw_pairs(scanner.original_data_str).enumerate() {
+            self.lint_pair(
+                &mut findings,
+                index,
+                Text::encoded(key),
+                Text::encoded(value),
+                || codec::decode(key),
+            );
+        }
+        findings
+    }
+
+    /// # Lint the pairs, in their original sequence
+    ///
+    /// The raw pairs are linted, like `lint_scanner` does: a clean input allocates nothing.
+    /// Once pairs were set, pushed or deleted after parsing, the raw data and its sequence are lost:
+    /// the decoded pairs are then linted grouped by key, in order of first occurrence (keys added
+    /// after parsing last, sorted).
+    pub fn lint<'a, S: BuildHasher + Clone>(
+        &self,
+        data: &UrlEncodedData<'a, S>,
+    ) -> Vec<Finding<'a>> {
+        let mut findings = Vec::new();
+        if let Some(raw) = original_raw_pairs(data) {
+            for (index, (key, value)) in raw.enumerate() {
+                self.lint_pair(
+                    &mut findings,
+                    index,
+                    Text::encoded(key),
+                    Text::encoded(value),
+                    || codec::decode(key),
+                );
+            }
+            return findings;
+        }
+        let mut index = 0;
+        let mut lint_key = |findings: &mut Vec<Finding<'a>>, key: &Cow<'a, str>| {
+            for value in data.map.get(key.as_ref()).into_iter().flatten() {
+                self.lint_pair(
+                    findings,
+                    index,
+                    Text::decoded(key),
+                    Text::decoded(value),
+                    || key.clone(),
+                );
+                index += 1;
+            }
+        };
+        for key in data.original_keys_in_order.iter() {
+            lint_key(&mut findings, key);
+        }
+        // only allocates if keys were added after parsing
+        let mut added: Vec<&Cow<'a, str>> = data
+            .map
+            .keys()
+            .filter(|k| !data.original_keys_in_order.contains(k))
+            .collect();
+        added.sort_unstable();
+        for key in added {
+            lint_key(&mut findings, key);
+        }
+        findings
+    }
+}
+
+impl<'a, S: BuildHasher + Clone> UrlEncodedData<'a, S> {
+    /// # Security lint, see `lint::Linter::lint`
+    pub fn lint(&self, linter: &Linter) -> Vec<Finding<'a>> {
+        linter.lint(self)
+    }
+}
+
+impl<'a> UrlEncodedDataPairScanner<'a> {
+    /// # Security lint, see `lint::Linter::lint_scanner`
+    pub fn lint(&self, linter: &Linter) -> Vec<Finding<'a>> {
+        linter.lint_scanner(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(text: Text<'_>) -> Vec<&'static str> {
+        let linter = Linter::default();
+        linter
+            .rules
+            .iter()
+            .filter(|rule| rule.matches(text))
+            .map(|rule| rule.id())
+            .collect()
+    }
+
+    #[test]
+    fn test_builtin_rules() {
+        assert_eq!(ids(Text::encoded("a%0Db")), ["crlf"]);
+        assert_eq!(ids(Text::encoded("file.txt%00.jpg")), ["nul"]);
+        assert_eq!(ids(Text::encoded("..")), ["path-traversal"]);
+        assert_eq!(ids(Text::encoded("a%2F..")), ["path-traversal"]);
+        assert_eq!(ids(Text::encoded("%252e%252e%252f")), ["path-traversal"]);
+        assert_eq!(ids(Text::encoded("1'+OR+'1'='1")), ["sqli"]);
+        assert_eq!(ids(Text::encoded("1%3B+DROP+TABLE+users")), ["sqli"]);
+        assert_eq!(
+            ids(Text::encoded("%3CScRiPt%3Ealert(1)%3C/script%3E")),
+            ["xss"]
+        );
+        assert_eq!(ids(Text::encoded("%C0%AE%C0%AE%C0%AF")), ["overlong-utf8"]);
+        assert_eq!(ids(Text::encoded("%E0%80%AF")), ["overlong-utf8"]);
+        // valid UTF-8 and ordinary text
+        for clean in [
+            "%E4%B8%96",
+            "%C3%A9",
+            "rock+%26+roll",
+            "v1.2.3",
+            "a/b/c",
+            "it's+orange",
+            "select+a+union",
+            "100%",
+        ] {
+            assert!(ids(Text::encoded(clean)).is_empty(), "{}", clean);
+        }
+    }
+
+    #[test]
+    fn test_keys_and_custom_rules() {
+        struct TooLong;
+        impl Rule for TooLong {
+            fn id(&self) -> &'static str {
+                "too-long"
+            }
+            fn severity(&self) -> Severity {
+                Severity::Low
+            }
+            fn matches(&self, text: Text<'_>) -> bool {
+                text.bytes().count() > 8
+            }
+        }
+        let linter = Linter::default().with(TooLong);
+        let q = UrlEncodedData::parse_str("a=1&%3Cscript%3E=x&a=0123456789");
+        let findings: Vec<_> = q
+            .lint(&linter)
+            .into_iter()
+            .map(|f| (f.index, f.key, f.location, f.rule, f.severity))
+            .collect();
+        assert_eq!(
+            findings,
+            [
+                (1, "<script>".into(), Location::Key, "xss", Severity::High),
+                (2, "a".into(), Location::Value, "too-long", Severity::Low),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_added_keys_come_last() {
+        let mut q = UrlEncodedData::parse_str("a=1");
+        q.set_one("z", "%00");
+        q.set_one("b", "\r\n");
+        let findings: Vec<_> = q
+            .lint(&Linter::default())
+            .into_iter()
+            .map(|f| (f.index, f.key, f.rule))
+            .collect();
+        assert_eq!(findings, [(1, "b".into(), "crlf")]);
+    }
+}
@@ -0,0 +1,608 @@
+//! # Sorting, pagination and sparse fieldsets of list endpoints
+//!
+//! * `Sort`: `sort=-created_at,name`, fields in order with their `Direction` (`-` is descending).
+//! * `Page` (`page`, `per_page`), `OffsetLimit` (`offset`, `limit`) and `Cursor` (`cursor`, `limit`):
+//!   parameter names, default and maximum size in a `PaginationConfig`; sizes above the maximum are clamped.
+//! * `Fieldsets`: `fields[articles]=title,body`, fields per type.
+//!
+//! Each one parses from `UrlEncodedData` and writes itself back with `write_to` into the
+//! `UrlSearchParams` of the next request, other parameters untouched.
+//!
+//! ```rust
+//! use url_encoded_data::listing::{Direction, Fieldsets, Page, PaginationConfig, Sort};
+//! use url_encoded_data::search_params::UrlSearchParams;
+//! use url_encoded_data::UrlEncodedData;
+//!
+//! let q = UrlEncodedData::parse_str("/articles?sort=-created_at,name&page=2&per_page=500&fields%5Barticles%5D=title,body&q=rust");
+//! let sort = Sort::from_query(&q, "sort").unwrap().unwrap();
+//! assert_eq!(sort.0[0].field, "created_at");
+//! assert_eq!(sort.0[0].direction, Direction::Descending);
+//! sort.check_fields("sort", &["created_at", "name"]).unwrap();
+//!
+//! let config = PaginationConfig::default();
+//! let page = Page::from_query(&q, &config).unwrap();
+//! assert_eq!((page.number, page.size, page.offset()), (2, 100, 100));
+//!
+//! let fields = Fieldsets::from_query(&q, "fields").unwrap();
+//! assert_eq!(fields.get("articles").unwrap(), ["title", "body"]);
+//!
+//! let mut next = UrlSearchParams::from(&q);
+//! page.next().write_to(&mut next, &config);
+//! assert_eq!(
+//!     next.to_string(),
+//!     "sort=-created_at%2Cname&page=3&per_page=100&fields%5Barticles%5D=title%2Cbody&q=rust"
+//! );
+//! ```
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::{Display, Formatter};
+use core::hash::BuildHasher;
+
+use crate::canonical::{ordered_pairs, KeyOrder, ValueOrder};
+use crate::search_params::UrlSearchParams;
+use crate::UrlEncodedData;
+
+/// # Errors of parsing
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// not a positive integer, an empty sort field, ...
+    Invalid { key: String, value: String },
+    /// a single-valued parameter present more than once
+    Duplicated(String),
+    /// a field out of the allowed ones
+    UnknownField { key: String, field: String },
+    /// a field sorted twice
+    RepeatedField { key: String, field: String },
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::Invalid { key, value } => write!(f, "invalid {}: {:?}", key, value),
+            Error::Duplicated(key) => write!(f, "{} is present more than once", key),
+            Error::UnknownField { key, field } => {
+                write!(f, "unknown field in {}: {:?}", key, field)
+            }
+            Error::RepeatedField { key, field } => {
+                write!(f, "field repeated in {}: {:?}", key, field)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
+/// The only value of `key`
+fn single<'d, S: BuildHasher + Clone>(
+    data: &'d UrlEncodedData<'_, S>,
+    key: &str,
+) -> Result<Option<&'d str>, Error> {
+    match data.map.get(key).map(|values| values.as_slice()) {
+        None | Some([]) => Ok(None),
+        Some([value]) => Ok(Some(value.as_ref())),
+        Some(_) => Err(Error::Duplicated(key.to_string())),
+    }
+}
+
+/// A positive integer, or `None` if absent
+fn positive<S: BuildHasher + Clone>(
+    data: &UrlEncodedData<'_, S>,
+    key: &str,
+    allow_zero: bool,
+) -> Result<Option<u64>, Error> {
+    let value = match single(data, key)? {
+        None => return Ok(None),
+        Some(value) => value,
+    };
+    let invalid = || Error::Invalid {
+        key: key.to_string(),
+        value: value.to_string(),
+    };
+    let digits = value.trim();
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(invalid());
+    }
+    match digits.parse::<u64>() {
+        Ok(0) if !allow_zero => Err(invalid()),
+        Ok(n) => Ok(Some(n)),
+        Err(_) => Err(invalid()),
+    }
+}
+
+/// # Direction of a sort field
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Direction {
+    Ascending,
+    Descending,
+}
+
+/// # A sort field and its direction
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SortKey {
+    pub field: String,
+    pub direction: Direction,
+}
+
+/// # Sort fields, by priority
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Sort(pub Vec<SortKey>);
+
+impl Sort {
+    /// # Parse `-created_at,name`: `-` is descending, `+` (or a space, a decoded `+`) ascending
+    /// ```rust
+    /// use url_encoded_data::listing::{Direction, Sort};
+    /// let sort = Sort::parse("sort", " name,-id").unwrap();
+    /// assert_eq!(sort.0[0].direction, Direction::Ascending);
+    /// assert_eq!(sort.to_string(), "name,-id");
+    /// assert!(Sort::parse("sort", "name,,id").is_err());
+    /// assert!(Sort::parse("sort", "name,-name").is_err());
+    /// ```
+    pub fn parse(key: &str, value: &str) -> Result<Self, Error> {
+        let mut keys: Vec<SortKey> = Vec::new();
+        for item in value.split(',') {
+            let (direction, field) = match item.strip_prefix('-') {
+                Some(field) => (Direction::Descending, field),
+                None => (
+                    Direction::Ascending,
+                    item.strip_prefix(['+', ' ']).unwrap_or(item),
+                ),
+            };
+            if field.is_empty() || field.starts_with(['-', '+', ' ']) {
+                return Err(Error::Invalid {
+                    key: key.to_string(),
+                    value: value.to_string(),
+                });
+            }
+            if keys.iter().any(|k| k.field == field) {
+                return Err(Error::RepeatedField {
+                    key: key.to_string(),
+                    field: field.to_string(),
+                });
+            }
+            keys.push(SortKey {
+                field: field.to_string(),
+                direction,
+            });
+        }
+        Ok(Sort(keys))
+    }
+
+    /// # The sort in `key`, `None` if absent
+    pub fn from_query<S: BuildHasher + Clone>(
+        data: &UrlEncodedData<'_, S>,
+        key: &str,
+    ) -> Result<Option<Self>, Error> {
+        single(data, key)?
+            .map(|value| Self::parse(key, value))
+            .transpose()
+    }
+
+    /// # Only `allowed` fields may be sorted, errors name `key`
+    pub fn check_fields(&self, key: &str, allowed: &[&str]) -> Result<(), Error> {
+        match self.0.iter().find(|k| !allowed.contains(&k.field.as_str())) {
+            None => Ok(()),
+            Some(k) => Err(Error::UnknownField {
+                key: key.to_string(),
+                field: k.field.clone(),
+            }),
+        }
+    }
+
+    /// # Set `key` in `params`, removed if there is no field
+    pub fn write_to(&self, params: &mut UrlSearchParams<'_>, key: &str) {
+        if self.0.is_empty() {
+            params.delete(key, None);
+        } else {
+            params.set(key.to_string(), self.to_string());
+        }
+    }
+}
+
+impl Display for Sort {
+    /// `-created_at,name`
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        for (idx, key) in self.0.iter().enumerate() {
+            if idx > 0 {
+                f.write_str(",")?;
+            }
+            if key.direction == Direction::Descending {
+                f.write_str("-")?;
+            }
+            f.write_str(&key.field)?;
+        }
+        Ok(())
+    }
+}
+
+/// # Parameter names and page sizes of pagination
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PaginationConfig {
+    /// page size when absent
+    pub default_size: u64,
+    /// larger sizes are clamped; a size is at least 1, whatever the config
+    pub max_size: u64,
+    pub page_key: &'static str,
+    pub per_page_key: &'static str,
+    pub offset_key: &'static str,
+    pub limit_key: &'static str,
+    pub cursor_key: &'static str,
+}
+
+impl Default for PaginationConfig {
+    /// Pages of 20, 100 at most: `page`, `per_page`, `offset`, `limit`, `cursor`
+    fn default() -> Self {
+        Self {
+            default_size: 20,
+            max_size: 100,
+            page_key: "page",
+            per_page_key: "per_page",
+            offset_key: "offset",
+            limit_key: "limit",
+            cursor_key: "cursor",
+        }
+    }
+}
+
+impl PaginationConfig {
+    fn size<S: BuildHasher + Clone>(
+        &self,
+        data: &UrlEncodedData<'_, S>,
+        key: &str,
+    ) -> Result<u64, Error> {
+        Ok(positive(data, key, false)?
+            .unwrap_or(self.default_size)
+            .min(self.max_size)
+            .max(1))
+    }
+}
+
+/// # Page-number pagination, pages numbered from 1
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Page {
+    pub number: u64,
+    pub size: u64,
+}
+
+impl Page {
+    /// # Page from `page` and `per_page`: first page and default size when absent
+    pub fn from_query<S: BuildHasher + Clone>(
+        data: &UrlEncodedData<'_, S>,
+        config: &PaginationConfig,
+    ) -> Result<Self, Error> {
+        Ok(Self {
+            number: positive(data, config.page_key, false)?.unwrap_or(1),
+            size: config.size(data, config.per_page_key)?,
+        })
+    }
+
+    /// Items before this page
+    pub fn offset(&self) -> u64 {
+        self.number.saturating_sub(1).saturating_mul(self.size)
+    }
+
+    pub fn next(&self) -> Self {
+        Self {
+            number: self.number.saturating_add(1),
+            ..*self
+        }
+    }
+
+    /// `None` on the first page
+    pub fn prev(&self) -> Option<Self> {
+        (self.number > 1).then(|| Self {
+            number: self.number - 1,
+            ..*self
+        })
+    }
+
+    pub fn write_to(&self, params: &mut UrlSearchParams<'_>, config: &PaginationConfig) {
+        params.set(config.page_key, self.number.to_string());
+        params.set(config.per_page_key, self.size.to_string());
+    }
+}
+
+/// # Offset pagination
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct OffsetLimit {
+    pub offset: u64,
+    pub limit: u64,
+}
+
+impl OffsetLimit {
+    /// # From `offset` and `limit`: offset 0 and default size when absent
+    /// ```rust
+    /// use url_encoded_data::listing::{OffsetLimit, PaginationConfig};
+    /// use url_encoded_data::UrlEncodedData;
+    /// let config = PaginationConfig::default();
+    /// let q = UrlEncodedData::parse_str("offset=40&limit=20");
+    /// let window = OffsetLimit::from_query(&q, &config).unwrap();
+    /// assert_eq!(window.next(), OffsetLimit { offset: 60, limit: 20 });
+    /// assert_eq!(window.prev(), Some(OffsetLimit { offset: 20, limit: 20 }));
+    /// assert!(OffsetLimit::from_query(&UrlEncodedData::parse_str("offset=-1"), &config).is_err());
+    /// ```
+    pub fn from_query<S: BuildHasher + Clone>(
+        data: &UrlEncodedData<'_, S>,
+        config: &PaginationConfig,
+    ) -> Result<Self, Error> {
+        Ok(Self {
+            offset: positive(data, config.offset_key, true)?.unwrap_or(0),
+            limit: config.size(data, config.limit_key)?,
+        })
+    }
+
+    pub fn next(&self) -> Self {
+        Self {
+            offset: self.offset.saturating_add(self.limit),
+            ..*self
+        }
+    }
+
+    /// `None` at offset 0
+    pub fn prev(&self) -> Option<Self> {
+        (self.offset > 0).then(|| Self {
+            offset: self.offset.saturating_sub(self.limit),
+            ..*self
+        })
+    }
+
+    pub fn write_to(&self, params: &mut UrlSearchParams<'_>, config: &PaginationConfig) {
+        params.set(config.offset_key, self.offset.to_string());
+        params.set(config.limit_key, self.limit.to_string());
+    }
+}
+
+/// # Cursor pagination: an opaque cursor, none for the first page
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Cursor {
+    pub cursor: Option<String>,
+    pub limit: u64,
+}
+
+impl Cursor {
+    /// # From `cursor` and `limit`, an empty cursor is the first page
+    pub fn from_query<S: BuildHasher + Clone>(
+        data: &UrlEncodedData<'_, S>,
+        config: &PaginationConfig,
+    ) -> Result<Self, Error> {
+        Ok(Self {
+            cursor: single(data, config.cursor_key)?
+                .filter(|cursor| !cursor.is_empty())
+                .map(ToString::to_string),
+            limit: config.size(data, config.limit_key)?,
+        })
+    }
+
+    /// The page after this one starts at `cursor`, as returned by the store
+    pub fn next(&self, cursor: impl Into<String>) -> Self {
+        Self {
+            cursor: Some(cursor.into()),
+            limit: self.limit,
+        }
+    }
+
+    pub fn write_to(&self, params: &mut UrlSearchParams<'_>, config: &PaginationConfig) {
+        match &self.cursor {
+            Some(cursor) => params.set(config.cursor_key, cursor.clone()),
+            None => params.delete(config.cursor_key, None),
+        };
+        params.set(config.limit_key, self.limit.to_string());
+    }
+}
+
+/// # Sparse fieldsets: fields per type, in original order
+///
+/// The untyped `fields=title,body` is the type `""`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Fieldsets(pub Vec<(String, Vec<String>)>);
+
+impl Fieldsets {
+    /// # From `key[type]=field,field` pairs
+    pub fn from_query<S: BuildHasher + Clone>(
+        data: &UrlEncodedData<'_, S>,
+        key: &str,
+    ) -> Result<Self, Error> {
+        let mut fieldsets: Vec<(String, Vec<String>)> = Vec::new();
+        for (k, v) in ordered_pairs(data, KeyOrder::Original, ValueOrder::Original) {
+            let kind = match k.strip_prefix(key) {
+                Some("") => "",
+                Some(rest) => match rest.strip_prefix('[').and_then(|r| r.strip_suffix(']')) {
+                    Some(kind) => kind,
+                    None => continue,
+                },
+                None => continue,
+            };
+            if fieldsets.iter().any(|(t, _)| t == kind) {
+                return Err(Error::Duplicated(k.to_string()));
+            }
+            let fields: Vec<String> = if v.is_empty() {
+                Vec::new()
+            } else {
+                v.split(',').map(ToString::to_string).collect()
+            };
+            if fields.iter().any(|field| field.is_empty()) {
+                return Err(Error::Invalid {
+                    key: k.to_string(),
+                    value: v.to_string(),
+                });
+            }
+            fieldsets.push((kind.to_string(), fields));
+        }
+        Ok(Fieldsets(fieldsets))
+    }
+
+    /// Fields of `kind`, `None` if all fields are requested
+    pub fn get(&self, kind: &str) -> Option<&[String]> {
+        self.0
+            .iter()
+            .find(|(t, _)| t == kind)
+            .map(|(_, fields)| fields.as_slice())
+    }
+
+    /// # Only `allowed` fields may be requested for `kind`, errors name `key[kind]` (`key` if `kind` is empty)
+    pub fn check_fields(&self, key: &str, kind: &str, allowed: &[&str]) -> Result<(), Error> {
+        let unknown = self
+            .get(kind)
+            .unwrap_or_default()
+            .iter()
+            .find(|field| !allowed.contains(&field.as_str()));
+        match unknown {
+            None => Ok(()),
+            Some(field) => Err(Error::UnknownField {
+                key: if kind.is_empty() {
+                    key.to_string()
+                } else {
+                    [key, "[", kind, "]"].concat()
+                },
+                field: field.clone(),
+            }),
+        }
+    }
+
+    pub fn write_to(&self, params: &mut UrlSearchParams<'_>, key: &str) {
+        for (kind, fields) in self.0.iter() {
+            let name = if kind.is_empty() {
+                key.to_string()
+            } else {
+                [key, "[", kind, "]"].concat()
+            };
+            params.set(name, fields.join(","));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pagination_bounds_and_defaults() {
+        let config = PaginationConfig::default();
+        let page = |s: &str| Page::from_query(&UrlEncodedData::parse_str(s), &config);
+        assert_eq!(
+            page(""),
+            Ok(Page {
+                number: 1,
+                size: 20
+            })
+        );
+        assert_eq!(
+            page("page=3&per_page=1000"),
+            Ok(Page {
+                number: 3,
+                size: 100
+            })
+        );
+        assert_eq!(
+            page("page=+4+"),
+            Ok(Page {
+                number: 4,
+                size: 20
+            })
+        );
+        for (input, key, value) in [
+            ("page=0", "page", "0"),
+            ("page=abc", "page", "abc"),
+            ("per_page=0", "per_page", "0"),
+            ("page=99999999999999999999", "page", "99999999999999999999"),
+        ] {
+            assert_eq!(
+                page(input),
+                Err(Error::Invalid {
+                    key: key.into(),
+                    value: value.into()
+                })
+            );
+        }
+        assert_eq!(page("page=1&page=2"), Err(Error::Duplicated("page".into())));
+        assert_eq!(page("page=1").unwrap().prev(), None);
+        // public fields: a page 0 starts at 0
+        assert_eq!(
+            Page {
+                number: 0,
+                size: 20
+            }
+            .offset(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_sort_check_fields() {
+        let q = UrlEncodedData::parse_str("order=name,-id");
+        let sort = Sort::from_query(&q, "order").unwrap().unwrap();
+        assert_eq!(sort.check_fields("order", &["id", "name"]), Ok(()));
+        assert_eq!(
+            sort.check_fields("order", &["name"]),
+            Err(Error::UnknownField {
+                key: "order".into(),
+                field: "id".into()
+            })
+        );
+    }
+
+    #[test]
+    fn test_cursor_round_trip() {
+        let config = PaginationConfig {
+            cursor_key: "after",
+            ..PaginationConfig::default()
+        };
+        let q = UrlEncodedData::parse_str("after=&limit=5&q=x");
+        let first = Cursor::from_query(&q, &config).unwrap();
+        assert_eq!(first.cursor, None);
+        let mut params = UrlSearchParams::from(&q);
+        first.next("b2Zmc2V0PTU=").write_to(&mut params, &config);
+        assert_eq!(params.to_string(), "after=b2Zmc2V0PTU%3D&limit=5&q=x");
+        let q = params.into_url_encoded_data();
+        assert_eq!(
+            Cursor::from_query(&q, &config).unwrap().cursor.unwrap(),
+            "b2Zmc2V0PTU="
+        );
+    }
+
+    #[test]
+    fn test_fieldsets() {
+        let q = UrlEncodedData::parse_str(
+            "fields[articles]=title,body&fields[people]=&fieldsx=1&fields=id",
+        );
+        let fieldsets = Fieldsets::from_query(&q, "fields").unwrap();
+        assert_eq!(fieldsets.get("articles").unwrap(), ["title", "body"]);
+        assert!(fieldsets.get("people").unwrap().is_empty());
+        assert_eq!(fieldsets.get("").unwrap(), ["id"]);
+        assert_eq!(fieldsets.get("comments"), None);
+        assert_eq!(
+            fieldsets.check_fields("fields", "articles", &["title"]),
+            Err(Error::UnknownField {
+                key: "fields[articles]".into(),
+                field: "body".into()
+            })
+        );
+        assert_eq!(
+            fieldsets.check_fields("fields", "", &[]),
+            Err(Error::UnknownField {
+                key: "fields".into(),
+                field: "id".into()
+            })
+        );
+        let q = UrlEncodedData::parse_str("f[a]=x");
+        assert_eq!(
+            Fieldsets::from_query(&q, "f")
+                .unwrap()
+                .check_fields("f", "a", &[]),
+            Err(Error::UnknownField {
+                key: "f[a]".into(),
+                field: "x".into()
+            })
+        );
+        let mut params = UrlSearchParams::default();
+        fieldsets.write_to(&mut params, "fields");
+        assert_eq!(
+            params.to_string(),
+            "fields%5Barticles%5D=title%2Cbody&fields%5Bpeople%5D=&fields=id"
+        );
+
+        let q = UrlEncodedData::parse_str("fields[a]=x,,y");
+        assert!(Fieldsets::from_query(&q, "fields").is_err());
+    }
+}
@@ -0,0 +1,330 @@
+//! # RFC 3986 percent-encoding normalization
+//!
+//! Applies [RFC 3986 §6.2.2](https://www.rfc-editor.org/rfc/rfc3986#section-6.2.2) to the raw (still encoded) data:
+//! * case normalization: hex digits of `%xx` are uppercased (`%7e` -> `%7E`)
+//! * percent-encoding normalization: unreserved characters are decoded (`%7E` -> `~`, `%41` -> `A`)
+//! * only what is needed is re-encoded: characters not allowed in a query (space, `"`, `<`, non-ASCII, ...)
+//!   and `%` not followed by two hex digits.
+//!
+//! Reserved characters (`&`, `=`, `+`, `/`, ...) are left as they are, encoded or not, since that changes the meaning.
+//! The decoded pairs are the same before and after normalization.
+//!
+//! ```rust
+//! use url_encoded_data::normalize::normalize_query;
+//! let crawled = ["https://abc.com/?q=%7e", "https://abc.com/?q=%7E", "https://abc.com/?q=~"];
+//! for url in crawled.iter() {
+//!     assert_eq!(normalize_query(url).to_string(), "https://abc.com/?q=~");
+//! }
+//! ```
+
+use alloc::borrow::Cow;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::{Display, Formatter};
+use core::hash::BuildHasher;
+
+use crate::canonical::{ordered_pairs, original_raw_pairs, KeyOrder, ValueOrder};
+use crate::codec::{append_pair, hex_value, push_percent_encoded, raw_pairs};
+use crate::{split_url_encoded_string, UrlEncodedData, UrlEncodedDataPairScanner};
+
+/// RFC 3986 `unreserved`
+#[inline]
+fn is_unreserved(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~')
+}
+
+/// RFC 3986 characters allowed literally in a query: `pchar / "/" / "?"` except `pct-encoded`
+#[inline]
+fn is_allowed_in_query(byte: u8) -> bool {
+    is_unreserved(byte)
+        || matches!(
+            byte,
+            b'!' | b'$'
+                | b'&'
+                | b'\''
+                | b'('
+                | b')'
+                | b'*'
+                | b'+'
+                | b','
+                | b';'
+                | b'='
+                | b':'
+                | b'@'
+                | b'/'
+                | b'?'
+        )
+}
+
+/// # Normalize the percent-encoding of a raw string
+///
+/// Borrowed if the string is normalized already.
+/// ```rust
+/// use url_encoded_data::normalize::normalize_percent_encoding;
+/// assert_eq!(normalize_percent_encoding("a%7eb%2fc"), "a~b%2Fc");
+/// assert_eq!(normalize_percent_encoding("%41%42+%26"), "AB+%26");
+/// assert_eq!(normalize_percent_encoding("100% 世"), "100%25%20%E4%B8%96");
+/// ```
+pub fn normalize_percent_encoding(s: &str) -> Cow<'_, str> {
+    let bytes = s.as_bytes();
+    let mut out: Option<String> = None;
+    let mut i = 0;
+    while i < bytes.len() {
+        let byte = bytes[i];
+        let (replacement, consumed): (Option<u8>, usize) = if byte == b'%' {
+            match (
+                bytes.get(i + 1).copied().and_then(hex_value),
+                bytes.get(i + 2).copied().and_then(hex_value),
+            ) {
+                (Some(h), Some(l)) => {
+                    let decoded = h << 4 | l;
+                    let is_canonical = !is_unreserved(decoded)
+                        && !bytes[i + 1].is_ascii_lowercase()
+                        && !bytes[i + 2].is_ascii_lowercase();
+                    if is_canonical {
+                        (None, 3)
+                    } else {
+                        (Some(decoded), 3)
+                    }
+                }
+                // a lone '%' is encoded
+                _ => (Some(b'%'), 1),
+            }
+        } else if is_allowed_in_query(byte) {
+            (None, 1)
+        } else {
+            (Some(byte), 1)
+        };
+
+        match (replacement, out.as_mut()) {
+            (None, None) => {}
+            (None, Some(out)) => out.push_str(&s[i..i + consumed]),
+            (Some(decoded), _) => {
+                let out = out.get_or_insert_with(|| {
+                    let mut out = String::with_capacity(s.len() + 8);
+                    out.push_str(&s[..i]);
+                    out
+                });
+                if is_unreserved(decoded) {
+                    out.push(decoded as char);
+                } else {
+                    push_percent_encoded(out, decoded);
+                }
+            }
+        }
+        i += consumed;
+    }
+    match out {
+        None => Cow::Borrowed(s),
+        Some(out) => Cow::Owned(out),
+    }
+}
+
+/// # A pair whose raw encoding was changed by the normalization
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChangedPair<'a> {
+    /// index of the pair in the data (empty sequences like `&&` are not counted)
+    pub index: usize,
+    /// raw `key=value` before normalization
+    pub before: &'a str,
+    /// raw `key=value` after normalization
+    pub after: String,
+}
+
+/// # Normalized url-encoded data, with the pairs that changed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Normalized<'a> {
+    /// the prefix (eg: `https://abc.com/?`), kept as-is
+    pub prefix: &'a str,
+    /// the normalized data, empty sequences (`&&`) are dropped
+    pub data: String,
+    /// pairs whose raw encoding changed; always empty when the raw encoding is unknown, see
+    /// `UrlEncodedData::normalize_percent_encoding`
+    pub changed: Vec<ChangedPair<'a>>,
+}
+
+impl<'a> Normalized<'a> {
+    /// Did any pair change?
+    pub fn is_changed(&self) -> bool {
+        !self.changed.is_empty()
+    }
+}
+
+impl<'a> Display for Normalized<'a> {
+    /// prefix + normalized data
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}{}", self.prefix, self.data)
+    }
+}
+
+fn normalize_data<'a>(prefix: &'a str, data_str: &'a str) -> Normalized<'a> {
+    let mut data = String::with_capacity(data_str.len());
+    let mut changed = Vec::new();
+    for (index, (k, v)) in raw_pairs(data_str).enumerate() {
+        // `raw_pairs` borrows from `data_str`: widen the key to its whole `key=value` sequence
+        let start = k.as_ptr() as usize - data_str.as_ptr() as usize;
+        let mut end = start + k.len();
+        if data_str[end..].starts_with('=') {
+            end += 1 + v.len();
+        }
+        let sequence = &data_str[start..end];
+        if !data.is_empty() {
+            data.push('&');
+        }
+        let normalized = normalize_percent_encoding(sequence);
+        data.push_str(&normalized);
+        if let Cow::Owned(after) = normalized {
+            changed.push(ChangedPair {
+                index,
+                before: sequence,
+                after,
+            });
+        }
+    }
+    Normalized {
+        prefix,
+        data,
+        changed,
+    }
+}
+
+/// # Normalize the url-encoded data of a string (an url or the data only)
+/// ```rust
+/// use url_encoded_data::normalize::normalize_query;
+/// let n = normalize_query("https://abc.com/?a=%7e&b=ok&c=%e4%b8%96");
+/// assert_eq!(n.to_string(), "https://abc.com/?a=~&b=ok&c=%E4%B8%96");
+/// assert_eq!(n.changed.len(), 2);
+/// assert_eq!((n.changed[0].index, n.changed[0].before, n.changed[0].after.as_str()), (0, "a=%7e", "a=~"));
+/// assert_eq!((n.changed[1].index, n.changed[1].before, n.changed[1].after.as_str()), (2, "c=%e4%b8%96", "c=%E4%B8%96"));
+/// ```
+pub fn normalize_query(s: &str) -> Normalized<'_> {
+    let (prefix, data_str) = split_url_encoded_string(s);
+    normalize_data(prefix, data_str)
+}
+
+impl<'a, S: BuildHasher + Clone> UrlEncodedData<'a, S> {
+    /// # Normalize the percent-encoding of the original data (see `normalize` module)
+    ///
+    /// Works on `original_data_str`, since decoded pairs do not remember how they were encoded.
+    /// If the pairs no longer match it (set, pushed or deleted after parsing, or data not built
+    /// from a raw string), the current pairs are encoded and normalized instead, and `changed` is empty.
+    /// ```rust
+    /// use url_encoded_data::UrlEncodedData;
+    /// let q = UrlEncodedData::parse_str("https://abc.com/?q=%7Erust&page=%31");
+    /// let n = q.normalize_percent_encoding();
+    /// assert_eq!(n.to_string(), "https://abc.com/?q=~rust&page=1");
+    /// assert!(n.is_changed());
+    /// ```
+    pub fn normalize_percent_encoding(&self) -> Normalized<'a> {
+        if original_raw_pairs(self).is_some() {
+            return normalize_data(self.prefix, self.original_data_str);
+        }
+        let mut data = String::new();
+        for (k, v) in ordered_pairs(self, KeyOrder::Original, ValueOrder::Original) {
+            append_pair(&mut data, k, v);
+        }
+        Normalized {
+            prefix: self.prefix,
+            data: normalize_percent_encoding(&data).into_owned(),
+            changed: Vec::new(),
+        }
+    }
+}
+
+impl<'a> UrlEncodedDataPairScanner<'a> {
+    /// # Normalize the percent-encoding of the scanned data (see `normalize` module)
+    /// ```rust
+    /// use url_encoded_data::UrlEncodedDataPairScanner;
+    /// let n = UrlEncodedDataPairScanner::from("a=%3d%3D").normalize_percent_encoding();
+    /// assert_eq!(n.to_string(), "a=%3D%3D");
+    /// ```
+    pub fn normalize_percent_encoding(&self) -> Normalized<'a> {
+        normalize_data(self.prefix, self.original_data_str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec;
+    use alloc::string::ToString;
+
+    #[test]
+    fn test_decoded_pairs_are_unchanged() {
+        let inputs = [
+            "a=%7e%7E~&b=%2b+%20&c=%26%3d",
+            "%41%62%43=%e4%b8%96%E7%95%8C",
+            "lone=%&bad=%zz&short=%4",
+            "raw=世界 <tag>&bar=|^`",
+        ];
+        for input in inputs.iter() {
+            let n = normalize_query(input);
+            let before: Vec<_> = codec::parse(input).collect();
+            let after: Vec<_> = codec::parse(&n.data).collect();
+            assert_eq!(before, after, "input: {:?}", input);
+            // normalizing twice changes nothing
+            assert!(!normalize_query(&n.data).is_changed(), "input: {:?}", input);
+        }
+    }
+
+    #[test]
+    fn test_changed_pairs_report() {
+        let n = normalize_query("?&a=1&&b=%7e&c=2&d=%ZZ&%65&=%7e=");
+        assert_eq!(n.to_string(), "?a=1&b=~&c=2&d=%25ZZ&e&=~=");
+        let changed: Vec<_> = n
+            .changed
+            .iter()
+            .map(|c| (c.index, c.before, c.after.as_str()))
+            .collect();
+        assert_eq!(
+            changed,
+            [
+                (1, "b=%7e", "b=~"),
+                (3, "d=%ZZ", "d=%25ZZ"),
+                (4, "%65", "e"),
+                (5, "=%7e=", "=~=")
+            ]
+        );
+
+        let n = normalize_query("https://abc.com/?a=1&b=2");
+        assert!(!n.is_changed());
+        assert_eq!(n.to_string(), "https://abc.com/?a=1&b=2");
+    }
+
+    #[test]
+    fn test_equivalent_urls_give_the_same_key() {
+        let urls = [
+            "https://abc.com/?q=caf%C3%A9%7e",
+            "https://abc.com/?q=caf%c3%a9~",
+            "https://abc.com/?q=café%7E",
+        ];
+        let keys: Vec<_> = urls
+            .iter()
+            .map(|url| {
+                UrlEncodedData::parse_str(url)
+                    .normalize_percent_encoding()
+                    .to_string()
+            })
+            .collect();
+        assert!(keys.iter().all(|k| k == "https://abc.com/?q=caf%C3%A9~"));
+    }
+
+    #[test]
+    fn test_pairs_changed_after_parsing() {
+        let mut q = UrlEncodedData::parse_str("https://abc.com/?q=%7e&page=%31");
+        q.set_one("page", "2").push("tag", "a b~");
+        let n = q.normalize_percent_encoding();
+        assert_eq!(n.to_string(), "https://abc.com/?q=~&page=2&tag=a+b~");
+        // `q=%7e` did change, but the raw data no longer describes the pairs: nothing is reported
+        assert!(!n.is_changed());
+
+        let mut q = UrlEncodedData::parse_str("?a=1&b=%7e");
+        q.delete("a");
+        assert_eq!(q.normalize_percent_encoding().to_string(), "?b=~");
+
+        // data built from decoded pairs, without a raw string
+        let params = crate::search_params::UrlSearchParams::parse("x=%E4%B8%96&y=%2b");
+        let q = params.into_url_encoded_data();
+        assert_eq!(q.normalize_percent_encoding().data, "x=%E4%B8%96&y=%2B");
+    }
+}
@@ -0,0 +1,464 @@
+//! # OAuth 1.0a signature base string and signing (RFC 5849)
+//!
+//! Builds the [normalized parameters](https://www.rfc-editor.org/rfc/rfc5849#section-3.4.1.3.2)
+//! and the [signature base string](https://www.rfc-editor.org/rfc/rfc5849#section-3.4.1)
+//! from the query and the form body of a request plus the `oauth_*` parameters,
+//! then signs it with HMAC-SHA1 or HMAC-SHA256.
+//! The result is sent as an `Authorization` header or as query parameters.
+//!
+//! Timestamp and nonce are given by the caller.
+//!
+//! ```rust
+//! use url_encoded_data::oauth1::{Request, Signer};
+//! use url_encoded_data::UrlEncodedData;
+//!
+//! // RFC 5849 section 1.2
+//! let query = UrlEncodedData::parse_str("http://photos.example.net/photos?file=vacation.jpg&size=original");
+//! let signer = Signer::new("dpf43f3p2l4k3l03", "kd94hf93k423kf44", 137131202, "chapoH")
+//!     .token("nnch734d00sl2jdk", "pfkkdhi9sl3r4s00")
+//!     .realm("Photos");
+//! let signed = signer
+//!     .sign(&Request::new("GET", "http://photos.example.net/photos").query(&query))
+//!     .unwrap();
+//! assert_eq!(signed.signature(), "MdpQcU8iPSUjWoN/UDMsK2sui9I=");
+//! assert_eq!(
+//!     signed.authorization_header(),
+//!     "OAuth realm=\"Photos\", oauth_consumer_key=\"dpf43f3p2l4k3l03\", oauth_token=\"nnch734d00sl2jdk\", \
+//!      oauth_signature_method=\"HMAC-SHA1\", oauth_timestamp=\"137131202\", oauth_nonce=\"chapoH\", \
+//!      oauth_signature=\"MdpQcU8iPSUjWoN%2FUDMsK2sui9I%3D\""
+//! );
+//! ```
+
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::{Display, Formatter};
+use core::hash::BuildHasher;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+use sha2::Sha256;
+
+use crate::codec::append_rfc3986_encoded;
+use crate::{DefaultHashBuilder, UrlEncodedData};
+
+/// # Errors of signing
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// the url is not absolute (`scheme://authority/path`)
+    InvalidUrl,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::InvalidUrl => write!(f, "url must be absolute: scheme://authority/path"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
+/// # `oauth_signature_method`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SignatureMethod {
+    HmacSha1,
+    HmacSha256,
+}
+
+impl SignatureMethod {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SignatureMethod::HmacSha1 => "HMAC-SHA1",
+            SignatureMethod::HmacSha256 => "HMAC-SHA256",
+        }
+    }
+}
+
+/// RFC 5849 section 3.6 percent-encoding
+fn encode(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    append_rfc3986_encoded(&mut out, s, true);
+    out
+}
+
+/// # Base string URI (RFC 5849 section 3.4.1.2)
+///
+/// Scheme and host are lowercased, the default port is removed, query and fragment are dropped.
+/// ```rust
+/// use url_encoded_data::oauth1::base_string_uri;
+/// assert_eq!(base_string_uri("HTTP://EXAMPLE.COM:80/r%20v/X?id=123").unwrap(), "http://example.com/r%20v/X");
+/// assert_eq!(base_string_uri("https://www.example.net:8080/?q=1").unwrap(), "https://www.example.net:8080/");
+/// assert_eq!(base_string_uri("https://example.com").unwrap(), "https://example.com/");
+/// assert!(base_string_uri("/request").is_err());
+/// ```
+pub fn base_string_uri(url: &str) -> Result<String, Error> {
+    let url = url.split(['?', '#']).next().unwrap_or_default();
+    let (scheme, rest) = url.split_once("://").ok_or(Error::InvalidUrl)?;
+    let (authority, path) = match rest.find('/') {
+        Some(idx) => rest.split_at(idx),
+        None => (rest, "/"),
+    };
+    if scheme.is_empty() || authority.is_empty() {
+        return Err(Error::InvalidUrl);
+    }
+    let scheme = scheme.to_ascii_lowercase();
+    let authority = authority.to_ascii_lowercase();
+    let host = match (scheme.as_str(), authority.rsplit_once(':')) {
+        ("http", Some((host, "80"))) | ("https", Some((host, "443"))) => host,
+        _ => authority.as_str(),
+    };
+    Ok(format!("{}://{}{}", scheme, host, path))
+}
+
+/// # The request to sign
+///
+/// `query` holds the query parameters (the query of `url` itself is ignored),
+/// `body` the parameters of an `application/x-www-form-urlencoded` body.
+#[derive(Clone, Copy, Debug)]
+pub struct Request<'r, S = DefaultHashBuilder> {
+    pub method: &'r str,
+    pub url: &'r str,
+    pub query: Option<&'r UrlEncodedData<'r, S>>,
+    pub body: Option<&'r UrlEncodedData<'r, S>>,
+}
+
+impl<'r> Request<'r> {
+    pub fn new(method: &'r str, url: &'r str) -> Self {
+        Self {
+            method,
+            url,
+            query: None,
+            body: None,
+        }
+    }
+}
+
+impl<'r, S: BuildHasher + Clone> Request<'r, S> {
+    pub fn query(mut self, query: &'r UrlEncodedData<'r, S>) -> Self {
+        self.query = Some(query);
+        self
+    }
+
+    pub fn body(mut self, body: &'r UrlEncodedData<'r, S>) -> Self {
+        self.body = Some(body);
+        self
+    }
+}
+
+/// # Credentials and `oauth_*` parameters of a request
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Signer<'s> {
+    pub consumer_key: &'s str,
+    pub consumer_secret: &'s str,
+    pub token: Option<&'s str>,
+    /// empty if there is no token
+    pub token_secret: &'s str,
+    pub signature_method: SignatureMethod,
+    pub timestamp: u64,
+    pub nonce: &'s str,
+    /// `oauth_version`, optional, `1.0` if given
+    pub version: Option<&'s str>,
+    pub callback: Option<&'s str>,
+    pub verifier: Option<&'s str>,
+    /// `realm` of the `Authorization` header, never signed
+    pub realm: Option<&'s str>,
+}
+
+impl<'s> Signer<'s> {
+    /// HMAC-SHA1, no token
+    pub fn new(
+        consumer_key: &'s str,
+        consumer_secret: &'s str,
+        timestamp: u64,
+        nonce: &'s str,
+    ) -> Self {
+        Self {
+            consumer_key,
+            consumer_secret,
+            token: None,
+            token_secret: "",
+            signature_method: SignatureMethod::HmacSha1,
+            timestamp,
+            nonce,
+            version: None,
+            callback: None,
+            verifier: None,
+            realm: None,
+        }
+    }
+
+    pub fn token(mut self, token: &'s str, token_secret: &'s str) -> Self {
+        self.token = Some(token);
+        self.token_secret = token_secret;
+        self
+    }
+
+    pub fn signature_method(mut self, signature_method: SignatureMethod) -> Self {
+        self.signature_method = signature_method;
+        self
+    }
+
+    pub fn version(mut self, version: &'s str) -> Self {
+        self.version = Some(version);
+        self
+    }
+
+    pub fn callback(mut self, callback: &'s str) -> Self {
+        self.callback = Some(callback);
+        self
+    }
+
+    pub fn verifier(mut self, verifier: &'s str) -> Self {
+        self.verifier = Some(verifier);
+        self
+    }
+
+    pub fn realm(mut self, realm: &'s str) -> Self {
+        self.realm = Some(realm);
+        self
+    }
+
+    /// # `oauth_*` parameters, without `oauth_signature`, in the order they are sent
+    pub fn oauth_params(&self) -> Vec<(&'static str, String)> {
+        let mut params = Vec::with_capacity(8);
+        params.push(("oauth_consumer_key", self.consumer_key.to_string()));
+        if let Some(token) = self.token {
+            params.push(("oauth_token", token.to_string()));
+        }
+        params.push((
+            "oauth_signature_method",
+            self.signature_method.as_str().to_string(),
+        ));
+        params.push(("oauth_timestamp", self.timestamp.to_string()));
+        params.push(("oauth_nonce", self.nonce.to_string()));
+        let optional = [
+            ("oauth_version", self.version),
+            ("oauth_callback", self.callback),
+            ("oauth_verifier", self.verifier),
+        ];
+        for (name, value) in optional.iter() {
+            if let Some(value) = value {
+                params.push((name, value.to_string()));
+            }
+        }
+        params
+    }
+
+    /// # Normalized parameters (RFC 5849 section 3.4.1.3.2)
+    ///
+    /// Query, body and `oauth_*` parameters, encoded, sorted by name then value.
+    /// `oauth_signature` of the query or body is excluded.
+    pub fn normalized_parameters<S: BuildHasher + Clone>(
+        &self,
+        request: &Request<'_, S>,
+    ) -> String {
+        let oauth_params = self.oauth_params();
+        let mut encoded: Vec<(String, String)> = [request.query, request.body]
+            .iter()
+            .flatten()
+            .flat_map(|data| {
+                data.map
+                    .iter()
+                    .flat_map(|(k, values)| values.iter().map(move |v| (k.as_ref(), v.as_ref())))
+            })
+            .filter(|(k, _)| *k != "oauth_signature")
+            .chain(oauth_params.iter().map(|(k, v)| (*k, v.as_str())))
+            .map(|(k, v)| (encode(k), encode(v)))
+            .collect();
+        encoded.sort_unstable();
+
+        let mut out = String::new();
+        for (k, v) in encoded.iter() {
+            if !out.is_empty() {
+                out.push('&');
+            }
+            out.push_str(k);
+            out.push('=');
+            out.push_str(v);
+        }
+        out
+    }
+
+    /// # Signature base string (RFC 5849 section 3.4.1.1)
+    pub fn signature_base_string<S: BuildHasher + Clone>(
+        &self,
+        request: &Request<'_, S>,
+    ) -> Result<String, Error> {
+        Ok(format!(
+            "{}&{}&{}",
+            request.method.to_ascii_uppercase(),
+            encode(&base_string_uri(request.url)?),
+            encode(&self.normalized_parameters(request))
+        ))
+    }
+
+    /// # Sign the request
+    pub fn sign<S: BuildHasher + Clone>(&self, request: &Request<'_, S>) -> Result<Signed, Error> {
+        let base_string = self.signature_base_string(request)?;
+        let key = format!(
+            "{}&{}",
+            encode(self.consumer_secret),
+            encode(self.token_secret)
+        );
+        let digest = match self.signature_method {
+            SignatureMethod::HmacSha1 => {
+                let mut mac = Hmac::<Sha1>::new_from_slice(key.as_bytes())
+                    .expect("HMAC accepts keys of any length");
+                mac.update(base_string.as_bytes());
+                mac.finalize().into_bytes().to_vec()
+            }
+            SignatureMethod::HmacSha256 => {
+                let mut mac = Hmac::<Sha256>::new_from_slice(key.as_bytes())
+                    .expect("HMAC accepts keys of any length");
+                mac.update(base_string.as_bytes());
+                mac.finalize().into_bytes().to_vec()
+            }
+        };
+        let mut params = self.oauth_params();
+        params.push(("oauth_signature", BASE64.encode(digest)));
+        Ok(Signed {
+            realm: self.realm.map(String::from),
+            params,
+        })
+    }
+}
+
+/// # `oauth_*` parameters of a signed request
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Signed {
+    realm: Option<String>,
+    // `oauth_signature` is the last one
+    params: Vec<(&'static str, String)>,
+}
+
+impl Signed {
+    /// # Base64 `oauth_signature`
+    pub fn signature(&self) -> &str {
+        &self.params[self.params.len() - 1].1
+    }
+
+    /// # (name, value) of the `oauth_*` parameters, `oauth_signature` included
+    pub fn params(&self) -> impl Iterator<Item = (&'static str, &str)> {
+        self.params.iter().map(|(k, v)| (*k, v.as_str()))
+    }
+
+    /// # Value of the `Authorization` header (RFC 5849 section 3.5.1)
+    pub fn authorization_header(&self) -> String {
+        let mut out = String::from("OAuth ");
+        if let Some(realm) = self.realm.as_ref() {
+            out.push_str("realm=\"");
+            out.push_str(&encode(realm));
+            out.push_str("\", ");
+        }
+        for (idx, (k, v)) in self.params.iter().enumerate() {
+            if idx > 0 {
+                out.push_str(", ");
+            }
+            out.push_str(k);
+            out.push_str("=\"");
+            append_rfc3986_encoded(&mut out, v, true);
+            out.push('"');
+        }
+        out
+    }
+
+    /// # Query parameters (RFC 5849 section 3.5.3), to append to the query of the request
+    /// ```rust
+    /// use url_encoded_data::oauth1::{Request, Signer};
+    /// let signed = Signer::new("key", "secret", 1, "n").sign(&Request::new("GET", "https://a.com/")).unwrap();
+    /// let query = signed.to_query_string();
+    /// assert!(query.starts_with("oauth_consumer_key=key&oauth_signature_method=HMAC-SHA1&oauth_timestamp=1&oauth_nonce=n&oauth_signature="));
+    /// ```
+    pub fn to_query_string(&self) -> String {
+        let mut out = String::new();
+        for (k, v) in self.params.iter() {
+            if !out.is_empty() {
+                out.push('&');
+            }
+            out.push_str(k);
+            out.push('=');
+            append_rfc3986_encoded(&mut out, v, true);
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// RFC 5849 section 3.4.1.1
+    #[test]
+    fn test_rfc5849_signature_base_string() {
+        let query =
+            UrlEncodedData::parse_str("http://example.com/request?b5=%3D%253D&a3=a&c%40=&a2=r%20b");
+        let body = UrlEncodedData::parse_str("c2&a3=2+q");
+        let request = Request::new("POST", "http://example.com/request")
+            .query(&query)
+            .body(&body);
+        let signer = Signer::new("9djdj82h48djs9d2", "", 137131201, "7d8f3e4a")
+            .token("kkk9d7dh3k39sjv7", "")
+            .realm("Example");
+
+        assert_eq!(
+            signer.normalized_parameters(&request),
+            "a2=r%20b&a3=2%20q&a3=a&b5=%3D%253D&c%40=&c2=&oauth_consumer_key=9djdj82h48djs9d2\
+             &oauth_nonce=7d8f3e4a&oauth_signature_method=HMAC-SHA1&oauth_timestamp=137131201\
+             &oauth_token=kkk9d7dh3k39sjv7"
+        );
+        assert_eq!(
+            signer.signature_base_string(&request).unwrap(),
+            "POST&http%3A%2F%2Fexample.com%2Frequest&a2%3Dr%2520b%26a3%3D2%2520q\
+             %26a3%3Da%26b5%3D%253D%25253D%26c%2540%3D%26c2%3D%26oauth_consumer_\
+             key%3D9djdj82h48djs9d2%26oauth_nonce%3D7d8f3e4a%26oauth_signature_m\
+             ethod%3DHMAC-SHA1%26oauth_timestamp%3D137131201%26oauth_token%3Dkkk\
+             9d7dh3k39sjv7"
+        );
+    }
+
+    /// OAuth Core 1.0 appendix A.5, `oauth_version` is signed
+    #[test]
+    fn test_oauth_core_appendix_a5() {
+        let query = UrlEncodedData::parse_str(
+            "http://photos.example.net/photos?file=vacation.jpg&size=original",
+        );
+        let request = Request::new("GET", "http://photos.example.net/photos").query(&query);
+        let signer = Signer::new(
+            "dpf43f3p2l4k3l03",
+            "kd94hf93k423kf44",
+            1191242096,
+            "kllo9940pd9333jh",
+        )
+        .token("nnch734d00sl2jdk", "pfkkdhi9sl3r4s00")
+        .version("1.0");
+        let signed = signer.sign(&request).unwrap();
+        assert_eq!(signed.signature(), "tR3+Ty81lMeYAr/Fid0kMTYa/WM=");
+        assert!(signed
+            .to_query_string()
+            .ends_with("&oauth_version=1.0&oauth_signature=tR3%2BTy81lMeYAr%2FFid0kMTYa%2FWM%3D"));
+    }
+
+    #[test]
+    fn test_hmac_sha256_and_ignored_signature() {
+        let query = UrlEncodedData::parse_str("https://a.com/?x=1&oauth_signature=stale");
+        let clean = UrlEncodedData::parse_str("https://a.com/?x=1");
+        let signer =
+            Signer::new("key", "secret", 1, "n").signature_method(SignatureMethod::HmacSha256);
+        let signed = signer
+            .sign(&Request::new("GET", "https://a.com/").query(&query))
+            .unwrap();
+        let expected = signer
+            .sign(&Request::new("GET", "https://a.com/").query(&clean))
+            .unwrap();
+        assert_eq!(signed, expected);
+        // base64 of 32 bytes
+        assert_eq!(signed.signature().len(), 44);
+        assert!(signed
+            .authorization_header()
+            .contains("oauth_signature_method=\"HMAC-SHA256\""));
+    }
+}
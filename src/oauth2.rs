@@ -0,0 +1,627 @@
+//! # OAuth 2.0 authorization request, callback and token request helpers
+//!
+//! * `AuthorizationRequest`: builds the authorization url ([RFC 6749 §4.1.1](https://www.rfc-editor.org/rfc/rfc6749#section-4.1.1)),
+//!   with PKCE ([RFC 7636](https://www.rfc-editor.org/rfc/rfc7636)) `S256` challenge
+//! * `parse_callback`: parses the redirect (query or fragment) into a typed result, after checking `state`
+//! * `TokenRequest`: encodes the form body of token requests
+//!
+//! ```rust
+//! use url_encoded_data::oauth2::{parse_callback, AuthorizationRequest, Pkce, ResponseMode, TokenRequest};
+//!
+//! let pkce = Pkce::generate().unwrap();
+//! let state = url_encoded_data::oauth2::generate_state().unwrap();
+//! let url = AuthorizationRequest::new("https://auth.abc.com/authorize", "my-app")
+//!     .redirect_uri("https://app.abc.com/cb")
+//!     .scope("openid")
+//!     .scope("email")
+//!     .state(&state)
+//!     .pkce(&pkce)
+//!     .to_url()
+//!     .unwrap();
+//! assert!(url.starts_with("https://auth.abc.com/authorize?response_type=code&client_id=my-app\
+//!     &redirect_uri=https%3A%2F%2Fapp.abc.com%2Fcb&scope=openid+email&state="));
+//!
+//! // the provider redirects to: https://app.abc.com/cb?code=...&state=...
+//! let callback = format!("https://app.abc.com/cb?code=SplxlOBeZQQYbYS6WxSbIA&state={}", state);
+//! let response = parse_callback(&callback, ResponseMode::Query, Some(&state)).unwrap();
+//! assert_eq!(response.code, "SplxlOBeZQQYbYS6WxSbIA");
+//!
+//! let body = TokenRequest::authorization_code(&response.code)
+//!     .redirect_uri("https://app.abc.com/cb")
+//!     .code_verifier(pkce.verifier())
+//!     .client_id("my-app")
+//!     .to_string();
+//! assert!(body.starts_with("grant_type=authorization_code&code=SplxlOBeZQQYbYS6WxSbIA&redirect_uri="));
+//! ```
+
+use alloc::borrow::Cow;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::{Display, Formatter};
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD as BASE64;
+use base64::Engine;
+use sha2::{Digest, Sha256};
+
+use crate::search_params::UrlSearchParams;
+use crate::{codec, UrlEncodedData};
+
+/// `code_challenge_method` of `Pkce`
+pub const S256: &str = "S256";
+
+/// # Errors of PKCE and state generation, and of authorization urls
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// the system random number generator failed
+    Random(getrandom::Error),
+    /// a code verifier is 43 to 128 characters of `A-Za-z0-9-._~`
+    InvalidCodeVerifier,
+    /// the authorization endpoint has a fragment (`#`), RFC 6749 §3.1 forbids it
+    FragmentInEndpoint,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::Random(e) => write!(f, "random number generator failed: {}", e),
+            Error::InvalidCodeVerifier => write!(
+                f,
+                "code verifier must be 43 to 128 characters of A-Za-z0-9-._~"
+            ),
+            Error::FragmentInEndpoint => {
+                write!(f, "authorization endpoint must not have a fragment")
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
+/// base64url (no padding) of `N` random bytes
+fn random_token<const N: usize>() -> Result<String, Error> {
+    let mut bytes = [0u8; N];
+    getrandom::getrandom(&mut bytes).map_err(Error::Random)?;
+    Ok(BASE64.encode(bytes))
+}
+
+/// # Random `state`: 128 bits, base64url
+pub fn generate_state() -> Result<String, Error> {
+    random_token::<16>()
+}
+
+/// # PKCE code verifier and its `S256` code challenge
+#[derive(Clone, PartialEq, Eq)]
+pub struct Pkce {
+    verifier: String,
+    challenge: String,
+}
+
+impl Pkce {
+    /// # Random verifier: 32 random bytes, base64url (43 characters)
+    pub fn generate() -> Result<Self, Error> {
+        Self::from_verifier(random_token::<32>()?)
+    }
+
+    /// # From an existing verifier
+    /// ```rust
+    /// use url_encoded_data::oauth2::{Error, Pkce};
+    /// // RFC 7636 appendix B
+    /// let pkce = Pkce::from_verifier("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk").unwrap();
+    /// assert_eq!(pkce.challenge(), "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM");
+    /// assert_eq!(Pkce::from_verifier("too short").unwrap_err(), Error::InvalidCodeVerifier);
+    /// ```
+    pub fn from_verifier(verifier: impl Into<String>) -> Result<Self, Error> {
+        let verifier = verifier.into();
+        let is_valid = (43..=128).contains(&verifier.len())
+            && verifier
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_' | b'~'));
+        if !is_valid {
+            return Err(Error::InvalidCodeVerifier);
+        }
+        let challenge = BASE64.encode(Sha256::digest(verifier.as_bytes()));
+        Ok(Self {
+            verifier,
+            challenge,
+        })
+    }
+
+    /// `code_verifier`, sent with the token request
+    pub fn verifier(&self) -> &str {
+        &self.verifier
+    }
+
+    /// `code_challenge`, sent with the authorization request
+    pub fn challenge(&self) -> &str {
+        &self.challenge
+    }
+}
+
+impl core::fmt::Debug for Pkce {
+    /// the verifier is a secret, it is not printed
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Pkce")
+            .field("challenge", &self.challenge)
+            .finish()
+    }
+}
+
+/// # Authorization request (authorization code grant)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AuthorizationRequest<'r> {
+    /// authorization endpoint, may have a query already
+    pub endpoint: &'r str,
+    pub response_type: &'r str,
+    pub client_id: &'r str,
+    pub redirect_uri: Option<&'r str>,
+    /// joined by spaces in `scope`
+    pub scopes: Vec<&'r str>,
+    pub state: Option<&'r str>,
+    pub code_challenge: Option<&'r str>,
+    /// other parameters (`prompt`, `nonce`, `response_mode`, ...), in order
+    pub extra: Vec<(&'r str, &'r str)>,
+}
+
+impl<'r> AuthorizationRequest<'r> {
+    /// `response_type=code`
+    pub fn new(endpoint: &'r str, client_id: &'r str) -> Self {
+        Self {
+            endpoint,
+            response_type: "code",
+            client_id,
+            redirect_uri: None,
+            scopes: Vec::new(),
+            state: None,
+            code_challenge: None,
+            extra: Vec::new(),
+        }
+    }
+
+    pub fn redirect_uri(mut self, redirect_uri: &'r str) -> Self {
+        self.redirect_uri = Some(redirect_uri);
+        self
+    }
+
+    /// Add a scope
+    pub fn scope(mut self, scope: &'r str) -> Self {
+        self.scopes.push(scope);
+        self
+    }
+
+    pub fn state(mut self, state: &'r str) -> Self {
+        self.state = Some(state);
+        self
+    }
+
+    /// `code_challenge` of `pkce`, with `code_challenge_method=S256`
+    pub fn pkce(mut self, pkce: &'r Pkce) -> Self {
+        self.code_challenge = Some(pkce.challenge());
+        self
+    }
+
+    /// Add another parameter
+    pub fn param(mut self, key: &'r str, value: &'r str) -> Self {
+        self.extra.push((key, value));
+        self
+    }
+
+    /// # The authorization url
+    ///
+    /// Parameters of the endpoint query are kept, those set by the request replace them.
+    /// An endpoint with a fragment is rejected.
+    /// ```rust
+    /// use url_encoded_data::oauth2::{AuthorizationRequest, Error};
+    /// let url = AuthorizationRequest::new("https://abc.com/auth?tenant=x&client_id=old", "app")
+    ///     .param("prompt", "consent")
+    ///     .to_url();
+    /// assert_eq!(url.unwrap(), "https://abc.com/auth?tenant=x&client_id=app&response_type=code&prompt=consent");
+    /// let url = AuthorizationRequest::new("https://abc.com/auth#top", "app").to_url();
+    /// assert_eq!(url, Err(Error::FragmentInEndpoint));
+    /// ```
+    pub fn to_url(&self) -> Result<String, Error> {
+        if self.endpoint.contains('#') {
+            return Err(Error::FragmentInEndpoint);
+        }
+        let (endpoint, query) = self.endpoint.split_once('?').unwrap_or((self.endpoint, ""));
+        let scope = self.scopes.join(" ");
+
+        let mut params = UrlSearchParams::parse(query);
+        params
+            .set("response_type", self.response_type)
+            .set("client_id", self.client_id);
+        if let Some(redirect_uri) = self.redirect_uri {
+            params.set("redirect_uri", redirect_uri);
+        }
+        if !scope.is_empty() {
+            params.set("scope", scope);
+        }
+        if let Some(state) = self.state {
+            params.set("state", state);
+        }
+        if let Some(code_challenge) = self.code_challenge {
+            params
+                .set("code_challenge", code_challenge)
+                .set("code_challenge_method", S256);
+        }
+        for (k, v) in self.extra.iter() {
+            params.append(*k, *v);
+        }
+        Ok(format!("{}?{}", endpoint, params))
+    }
+}
+
+/// # Where the authorization server puts the response parameters
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResponseMode {
+    /// `?code=...&state=...`
+    Query,
+    /// `#code=...&state=...`
+    Fragment,
+}
+
+/// # Successful authorization response
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AuthorizationResponse {
+    pub code: String,
+    pub state: Option<String>,
+    /// other parameters (`iss`, `session_state`, ...), in order
+    pub extra: Vec<(String, String)>,
+}
+
+/// # Errors of `parse_callback`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CallbackError {
+    /// `state` is missing while one is expected
+    MissingState,
+    /// `state` is not the expected one: the callback was not initiated by us
+    StateMismatch,
+    /// a response parameter occurs more than once (RFC 6749 §3.1)
+    Duplicated(&'static str),
+    /// neither `code` nor `error`
+    MissingCode,
+    /// the authorization server answered with an error (RFC 6749 §4.1.2.1)
+    Authorization {
+        error: String,
+        error_description: Option<String>,
+        error_uri: Option<String>,
+    },
+}
+
+impl Display for CallbackError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            CallbackError::MissingState => write!(f, "missing state"),
+            CallbackError::StateMismatch => write!(f, "state mismatch"),
+            CallbackError::Duplicated(key) => write!(f, "duplicated: {}", key),
+            CallbackError::MissingCode => write!(f, "missing code"),
+            CallbackError::Authorization {
+                error,
+                error_description,
+                ..
+            } => match error_description {
+                None => write!(f, "authorization error: {}", error),
+                Some(description) => write!(f, "authorization error: {}: {}", error, description),
+            },
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for CallbackError {}
+
+/// comparison whose duration does not depend on where the strings differ
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0u8, |acc, (x, y)| acc | (x ^ y))
+            == 0
+}
+
+/// # Parse the redirect of the authorization server
+///
+/// `expected_state` is the `state` of the request, `None` if it had none: the callback must then
+/// carry no `state` either. `state` is checked before anything else, so that forged error responses are rejected too.
+/// ```rust
+/// use url_encoded_data::oauth2::{parse_callback, CallbackError, ResponseMode};
+/// let error = parse_callback(
+///     "https://app.abc.com/cb#error=access_denied&error_description=User+denied&state=xyz",
+///     ResponseMode::Fragment,
+///     Some("xyz"),
+/// );
+/// assert_eq!(error, Err(CallbackError::Authorization {
+///     error: "access_denied".into(),
+///     error_description: Some("User denied".into()),
+///     error_uri: None,
+/// }));
+/// let forged = parse_callback("https://app.abc.com/cb?code=abc&state=other", ResponseMode::Query, Some("xyz"));
+/// assert_eq!(forged, Err(CallbackError::StateMismatch));
+/// ```
+pub fn parse_callback(
+    url: &str,
+    mode: ResponseMode,
+    expected_state: Option<&str>,
+) -> Result<AuthorizationResponse, CallbackError> {
+    let (before_fragment, fragment) = url.split_once('#').unwrap_or((url, ""));
+    let data = match mode {
+        ResponseMode::Query => before_fragment.split_once('?').map_or("", |(_, q)| q),
+        ResponseMode::Fragment => fragment,
+    };
+    let pairs: Vec<(String, String)> = codec::parse(data)
+        .map(|(k, v)| (k.into_owned(), v.into_owned()))
+        .collect();
+
+    let find_one = |key: &'static str| -> Result<Option<&str>, CallbackError> {
+        let mut found = pairs.iter().filter(|(k, _)| k == key);
+        let value = found.next().map(|(_, v)| v.as_str());
+        match found.next() {
+            None => Ok(value),
+            Some(_) => Err(CallbackError::Duplicated(key)),
+        }
+    };
+
+    let state = find_one("state")?;
+    match (state, expected_state) {
+        (None, None) => {}
+        (None, Some(_)) => return Err(CallbackError::MissingState),
+        (Some(state), Some(expected)) if constant_time_eq(state, expected) => {}
+        (Some(_), _) => return Err(CallbackError::StateMismatch),
+    }
+
+    if let Some(error) = find_one("error")? {
+        return Err(CallbackError::Authorization {
+            error: error.to_string(),
+            error_description: find_one("error_description")?.map(String::from),
+            error_uri: find_one("error_uri")?.map(String::from),
+        });
+    }
+    let code = find_one("code")?.ok_or(CallbackError::MissingCode)?;
+    Ok(AuthorizationResponse {
+        code: code.to_string(),
+        state: state.map(String::from),
+        extra: pairs
+            .iter()
+            .filter(|(k, _)| k != "code" && k != "state")
+            .cloned()
+            .collect(),
+    })
+}
+
+/// # Form body of a token request (`application/x-www-form-urlencoded`)
+#[derive(Clone, PartialEq, Eq)]
+pub struct TokenRequest<'t> {
+    pairs: Vec<(&'t str, &'t str)>,
+}
+
+impl<'t> TokenRequest<'t> {
+    fn grant(grant_type: &'t str) -> Self {
+        Self {
+            pairs: alloc::vec![("grant_type", grant_type)],
+        }
+    }
+
+    /// `grant_type=authorization_code`
+    pub fn authorization_code(code: &'t str) -> Self {
+        Self::grant("authorization_code").param("code", code)
+    }
+
+    /// `grant_type=refresh_token`
+    pub fn refresh_token(refresh_token: &'t str) -> Self {
+        Self::grant("refresh_token").param("refresh_token", refresh_token)
+    }
+
+    /// `grant_type=client_credentials`
+    /// ```rust
+    /// use url_encoded_data::oauth2::TokenRequest;
+    /// let body = TokenRequest::client_credentials().scope("read write").to_string();
+    /// assert_eq!(body, "grant_type=client_credentials&scope=read+write");
+    /// ```
+    pub fn client_credentials() -> Self {
+        Self::grant("client_credentials")
+    }
+
+    pub fn redirect_uri(self, redirect_uri: &'t str) -> Self {
+        self.param("redirect_uri", redirect_uri)
+    }
+
+    /// `code_verifier` of PKCE, see `Pkce::verifier`
+    pub fn code_verifier(self, code_verifier: &'t str) -> Self {
+        self.param("code_verifier", code_verifier)
+    }
+
+    pub fn scope(self, scope: &'t str) -> Self {
+        self.param("scope", scope)
+    }
+
+    /// client authentication in the body (`client_secret_post`), prefer HTTP Basic when supported
+    pub fn client_id(self, client_id: &'t str) -> Self {
+        self.param("client_id", client_id)
+    }
+
+    pub fn client_secret(self, client_secret: &'t str) -> Self {
+        self.param("client_secret", client_secret)
+    }
+
+    /// Add another parameter
+    pub fn param(mut self, key: &'t str, value: &'t str) -> Self {
+        self.pairs.push((key, value));
+        self
+    }
+
+    /// # Into `UrlEncodedData`, without prefix
+    pub fn into_url_encoded_data(self) -> UrlEncodedData<'t> {
+        let pairs = self
+            .pairs
+            .into_iter()
+            .map(|(k, v)| (Cow::Borrowed(k), Cow::Borrowed(v)));
+        UrlEncodedData::from_pairs_with_hasher("", "", pairs, Default::default())
+    }
+}
+
+/// keys of `TokenRequest` whose values are secrets
+const SECRET_PARAMS: [&str; 4] = ["code", "code_verifier", "refresh_token", "client_secret"];
+
+impl<'t> core::fmt::Debug for TokenRequest<'t> {
+    /// the code, verifier, refresh token and client secret are not printed
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        let pairs = self.pairs.iter().map(|&(k, v)| {
+            if SECRET_PARAMS.contains(&k) {
+                (k, "***")
+            } else {
+                (k, v)
+            }
+        });
+        f.debug_struct("TokenRequest")
+            .field("pairs", &pairs.collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl<'t> Display for TokenRequest<'t> {
+    /// The form body, pairs in order
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        let mut s = String::new();
+        for (k, v) in self.pairs.iter() {
+            codec::append_pair(&mut s, k, v);
+        }
+        f.write_str(&s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generated_values() {
+        let a = Pkce::generate().unwrap();
+        let b = Pkce::generate().unwrap();
+        assert_eq!(a.verifier().len(), 43);
+        assert_ne!(a.verifier(), b.verifier());
+        assert_eq!(Pkce::from_verifier(a.verifier()).unwrap(), a);
+        assert!(!format!("{:?}", a).contains(a.verifier()));
+        assert_eq!(generate_state().unwrap().len(), 22);
+    }
+
+    #[test]
+    fn test_authorization_url_with_pkce() {
+        let pkce = Pkce::from_verifier("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk").unwrap();
+        let url = AuthorizationRequest::new("https://abc.com/authorize", "s6BhdRkqt3")
+            .redirect_uri("https://client.example.com/cb")
+            .state("xyz")
+            .pkce(&pkce)
+            .to_url()
+            .unwrap();
+        assert_eq!(
+            url,
+            "https://abc.com/authorize?response_type=code&client_id=s6BhdRkqt3\
+             &redirect_uri=https%3A%2F%2Fclient.example.com%2Fcb&state=xyz\
+             &code_challenge=E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM&code_challenge_method=S256"
+        );
+        for endpoint in [
+            "https://abc.com/authorize#frag",
+            "https://abc.com/authorize?a=1#",
+        ] {
+            assert_eq!(
+                AuthorizationRequest::new(endpoint, "s6BhdRkqt3").to_url(),
+                Err(Error::FragmentInEndpoint)
+            );
+        }
+    }
+
+    #[test]
+    fn test_callback() {
+        let url = "https://client.example.com/cb?code=SplxlOBeZQQYbYS6WxSbIA&state=xyz&iss=https%3A%2F%2Fabc.com";
+        let response = parse_callback(url, ResponseMode::Query, Some("xyz")).unwrap();
+        assert_eq!(response.state.as_deref(), Some("xyz"));
+        assert_eq!(
+            response.extra,
+            [("iss".to_string(), "https://abc.com".to_string())]
+        );
+
+        // the fragment is not the query
+        let url = "https://client.example.com/cb#code=abc&state=xyz";
+        assert_eq!(
+            parse_callback(url, ResponseMode::Query, Some("xyz")),
+            Err(CallbackError::MissingState)
+        );
+        assert!(parse_callback(url, ResponseMode::Fragment, Some("xyz")).is_ok());
+
+        assert!(parse_callback("https://a.com/cb?code=abc", ResponseMode::Query, None).is_ok());
+
+        let cases = [
+            (
+                "https://a.com/cb?code=abc",
+                Some("xyz"),
+                CallbackError::MissingState,
+            ),
+            (
+                "https://a.com/cb?state=xyz",
+                Some("xyz"),
+                CallbackError::MissingCode,
+            ),
+            (
+                "https://a.com/cb?code=abc&state=xyz&code=def",
+                Some("xyz"),
+                CallbackError::Duplicated("code"),
+            ),
+            (
+                "https://a.com/cb?state=xyz&state=xyz&code=abc",
+                Some("xyz"),
+                CallbackError::Duplicated("state"),
+            ),
+            (
+                "https://a.com/cb?state=xy&code=abc",
+                Some("xyz"),
+                CallbackError::StateMismatch,
+            ),
+            // an empty state is a state, and does not disable the check
+            (
+                "https://a.com/cb?state=&code=abc",
+                Some("xyz"),
+                CallbackError::StateMismatch,
+            ),
+            // a state that was not expected
+            (
+                "https://a.com/cb?state=xyz&code=abc",
+                None,
+                CallbackError::StateMismatch,
+            ),
+        ];
+        for (url, expected_state, error) in cases.iter() {
+            assert_eq!(
+                &parse_callback(url, ResponseMode::Query, *expected_state).unwrap_err(),
+                error,
+                "{}",
+                url
+            );
+        }
+    }
+
+    #[test]
+    fn test_token_request() {
+        let request = TokenRequest::refresh_token("tGzv3JOkF0XG5Qx2TlKWIA")
+            .client_id("s6BhdRkqt3")
+            .client_secret("7Fjfp0ZBr1KtDRbnfVdmIw");
+        assert_eq!(
+            request.to_string(),
+            "grant_type=refresh_token&refresh_token=tGzv3JOkF0XG5Qx2TlKWIA\
+             &client_id=s6BhdRkqt3&client_secret=7Fjfp0ZBr1KtDRbnfVdmIw"
+        );
+        assert_eq!(
+            format!("{:?}", request),
+            "TokenRequest { pairs: [(\"grant_type\", \"refresh_token\"), (\"refresh_token\", \"***\"), \
+             (\"client_id\", \"s6BhdRkqt3\"), (\"client_secret\", \"***\")] }"
+        );
+        let debug = format!(
+            "{:?}",
+            TokenRequest::authorization_code("SplxlOBeZQQYbYS6WxSbIA")
+                .code_verifier("dBjftJeZ4CVP")
+        );
+        assert!(!debug.contains("SplxlOBeZQQYbYS6WxSbIA") && !debug.contains("dBjftJeZ4CVP"));
+        let q = request.into_url_encoded_data();
+        assert_eq!(q.get_first("grant_type"), Some("refresh_token"));
+    }
+}
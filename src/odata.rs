@@ -0,0 +1,1318 @@
+//! # OData system query options
+//!
+//! `QueryOptions::from_query` reads `$filter`, `$select`, `$expand`, `$orderby`, `$top`, `$skip`,
+//! `$count` and `$search`; `$filter` and `$orderby` are parsed into `Expr` trees, `$expand` into
+//! items with their nested options (`Orders($select=Id;$top=5)`).
+//!
+//! OData urls are not form-encoded: a `+` is a plus (as in `2012-12-03T07:16:23+01:00`), spaces
+//! come as `%20`. The options are therefore read from the raw query, `original_data_str`, and
+//! decoded with `+` kept; values set after parsing (`set_one`, `push`) are taken as they are.
+//! Option names are case-insensitive (`$Filter`, `%24filter`); other `$` options (`$format`,
+//! `$skiptoken`, ...) and custom parameters are ignored.
+//!
+//! Expressions follow the precedence of OData URL Conventions §5.1.1.16, loosest first: `or`, `and`,
+//! `eq` `ne`, `gt` `ge` `lt` `le`, `add` `sub`, `mul` `div` `divby` `mod`, unary `not` and `-`,
+//! then `has` and `in`; binary operators are left-associative (`not Active eq true` is
+//! `(not Active) eq true`, `Price gt 5 eq true` is `(Price gt 5) eq true`). Strings are in single
+//! quotes, `''` being a quote; dates, times, date-time offsets and GUIDs are typed literals.
+//! `Display` writes an expression back, with the parentheses it needs.
+//!
+//! Each `Error` names its option; syntax errors carry the byte position in the decoded value.
+//! Expressions and `$expand` options nested deeper than `MAX_DEPTH` are rejected (`TooDeep`); each
+//! operator of a chain (`a add b add c`) counts as one level.
+//!
+//! ```rust
+//! use url_encoded_data::listing::Direction;
+//! use url_encoded_data::odata::{BinaryOp, Expr, Literal, QueryOptions};
+//! use url_encoded_data::UrlEncodedData;
+//!
+//! let q = UrlEncodedData::parse_str(
+//!     "/Products?$filter=Price%20gt%205%20and%20(contains(Name,'Bike')%20or%20Date%20lt%202012-12-03T07:16:23+01:00)\
+//!      &$orderby=Price%20desc,Name&$top=10&$expand=Orders($select=Id;$top=2)&$count=true",
+//! );
+//! let options = QueryOptions::from_query(&q).unwrap();
+//!
+//! let filter = options.filter.unwrap();
+//! assert_eq!(
+//!     filter.to_string(),
+//!     "Price gt 5 and (contains(Name,'Bike') or Date lt 2012-12-03T07:16:23+01:00)"
+//! );
+//! if let Expr::Binary(left, BinaryOp::And, _) = &filter {
+//!     assert_eq!(**left, Expr::Binary(
+//!         Box::new(Expr::Member(vec!["Price".into()])),
+//!         BinaryOp::Gt,
+//!         Box::new(Expr::Literal(Literal::Integer(5))),
+//!     ));
+//! }
+//! let orderby = options.orderby.unwrap();
+//! assert_eq!(orderby[0].direction, Direction::Descending);
+//! assert_eq!(orderby[1].expr.to_string(), "Name");
+//! assert_eq!(options.top, Some(10));
+//! assert_eq!(options.count, Some(true));
+//! let expand = options.expand.unwrap();
+//! assert_eq!(expand[0].path, "Orders");
+//! assert_eq!(expand[0].options.top, Some(2));
+//!
+//! let err = QueryOptions::from_query(&UrlEncodedData::parse_str("$top=10&$filter=Price%20gt")).unwrap_err();
+//! assert_eq!(err.option, "$filter");
+//! assert_eq!(err.to_string(), "$filter: unexpected end at position 8");
+//! ```
+
+use alloc::borrow::Cow;
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::{Display, Formatter, Write};
+use core::hash::BuildHasher;
+
+use crate::canonical::{ordered_pairs, KeyOrder, ValueOrder};
+use crate::codec::{decode, percent_decode_bytes, raw_pairs};
+use crate::listing::Direction;
+use crate::UrlEncodedData;
+
+/// # Maximum nesting of expressions (parentheses, `not`, `-`, calls, chained operators) and of `$expand` options
+pub const MAX_DEPTH: usize = 64;
+
+/// # Kind of an error
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// the option is present more than once
+    Duplicated,
+    /// not a value of the option: `$top=-1`, `$count=yes`, ...
+    Invalid(String),
+    UnexpectedEnd {
+        position: usize,
+    },
+    UnexpectedToken {
+        position: usize,
+        token: String,
+    },
+    /// a string literal without its closing quote
+    UnterminatedString {
+        position: usize,
+    },
+    /// nested deeper than `MAX_DEPTH`
+    TooDeep {
+        position: usize,
+    },
+    /// an error in the nested options of an `$expand` item
+    Expand {
+        path: String,
+        error: Box<Error>,
+    },
+}
+
+/// # Error of an option
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Error {
+    /// as it is in the query: `$filter`, `$Top`, ...
+    pub option: String,
+    pub kind: ErrorKind,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}: ", self.option)?;
+        match &self.kind {
+            ErrorKind::Duplicated => f.write_str("present more than once"),
+            ErrorKind::Invalid(value) => write!(f, "invalid value {:?}", value),
+            ErrorKind::UnexpectedEnd { position } => {
+                write!(f, "unexpected end at position {}", position)
+            }
+            ErrorKind::UnexpectedToken { position, token } => {
+                write!(f, "unexpected {:?} at position {}", token, position)
+            }
+            ErrorKind::UnterminatedString { position } => {
+                write!(f, "unterminated string at position {}", position)
+            }
+            ErrorKind::TooDeep { position } => write!(
+                f,
+                "nested deeper than {} at position {}",
+                MAX_DEPTH, position
+            ),
+            ErrorKind::Expand { path, error } => write!(f, "in {}: {}", path, error),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
+/// # Literals
+#[derive(Clone, Debug, PartialEq)]
+pub enum Literal {
+    Null,
+    Boolean(bool),
+    Integer(i64),
+    Decimal(f64),
+    String(String),
+    /// `2012-12-03`
+    Date(String),
+    /// `2012-12-03T07:16:23Z`, `2012-12-03T07:16:23.5+01:00`
+    DateTimeOffset(String),
+    /// `07:16:23`
+    TimeOfDay(String),
+    /// `01234567-89ab-cdef-0123-456789abcdef`
+    Guid(String),
+}
+
+impl Display for Literal {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Literal::Null => f.write_str("null"),
+            Literal::Boolean(b) => write!(f, "{}", b),
+            Literal::Integer(n) => write!(f, "{}", n),
+            Literal::Decimal(n) => {
+                let s = n.to_string();
+                f.write_str(&s)?;
+                // `10.0` is written `10`, which reads back as an integer
+                if s.bytes().all(|b| b.is_ascii_digit() || b == b'-') {
+                    f.write_str(".0")?;
+                }
+                Ok(())
+            }
+            Literal::String(s) => {
+                f.write_char('\'')?;
+                f.write_str(&s.replace('\'', "''"))?;
+                f.write_char('\'')
+            }
+            Literal::Date(s)
+            | Literal::DateTimeOffset(s)
+            | Literal::TimeOfDay(s)
+            | Literal::Guid(s) => f.write_str(s),
+        }
+    }
+}
+
+/// # Unary operators
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum UnaryOp {
+    Not,
+    /// `-`
+    Neg,
+}
+
+/// # Binary operators, by increasing precedence
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BinaryOp {
+    Or,
+    And,
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    DivBy,
+    Mod,
+    Has,
+}
+
+impl BinaryOp {
+    pub fn keyword(self) -> &'static str {
+        match self {
+            BinaryOp::Or => "or",
+            BinaryOp::And => "and",
+            BinaryOp::Eq => "eq",
+            BinaryOp::Ne => "ne",
+            BinaryOp::Gt => "gt",
+            BinaryOp::Ge => "ge",
+            BinaryOp::Lt => "lt",
+            BinaryOp::Le => "le",
+            BinaryOp::Add => "add",
+            BinaryOp::Sub => "sub",
+            BinaryOp::Mul => "mul",
+            BinaryOp::Div => "div",
+            BinaryOp::DivBy => "divby",
+            BinaryOp::Mod => "mod",
+            BinaryOp::Has => "has",
+        }
+    }
+
+    fn precedence(self) -> u8 {
+        match self {
+            BinaryOp::Or => 1,
+            BinaryOp::And => 2,
+            BinaryOp::Eq | BinaryOp::Ne => 3,
+            BinaryOp::Gt | BinaryOp::Ge | BinaryOp::Lt | BinaryOp::Le => 4,
+            BinaryOp::Add | BinaryOp::Sub => 5,
+            BinaryOp::Mul | BinaryOp::Div | BinaryOp::DivBy | BinaryOp::Mod => 6,
+            // 7: unary operators
+            BinaryOp::Has => 8,
+        }
+    }
+}
+
+impl Display for BinaryOp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.write_str(self.keyword())
+    }
+}
+
+/// # Lambda operators
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LambdaKind {
+    Any,
+    All,
+}
+
+/// # Expression tree of `$filter` and `$orderby`
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expr {
+    Literal(Literal),
+    /// property path: `Address/City`
+    Member(Vec<String>),
+    Unary(UnaryOp, Box<Expr>),
+    Binary(Box<Expr>, BinaryOp, Box<Expr>),
+    /// `Name in ('a','b')`
+    In(Box<Expr>, Vec<Expr>),
+    /// `contains(Name,'Bike')`
+    Call(String, Vec<Expr>),
+    /// `Tags/any(t:t eq 'x')`; `Tags/any()` has no variable and predicate
+    Lambda {
+        path: Vec<String>,
+        kind: LambdaKind,
+        predicate: Option<(String, Box<Expr>)>,
+    },
+}
+
+impl Expr {
+    fn precedence(&self) -> u8 {
+        match self {
+            Expr::Binary(_, op, _) => op.precedence(),
+            Expr::Unary(..) => 7,
+            Expr::In(..) => 8,
+            _ => 9,
+        }
+    }
+
+    /// Write in parentheses if it binds looser than `min`
+    fn fmt_at(&self, f: &mut Formatter<'_>, min: u8) -> core::fmt::Result {
+        if self.precedence() < min {
+            write!(f, "({})", self)
+        } else {
+            write!(f, "{}", self)
+        }
+    }
+}
+
+impl Drop for Expr {
+    /// Without recursion, however deep the tree
+    fn drop(&mut self) {
+        fn take_children(expr: &mut Expr, children: &mut Vec<Expr>) {
+            let mut take = |child: &mut Box<Expr>| {
+                children.push(core::mem::replace(
+                    &mut **child,
+                    Expr::Literal(Literal::Null),
+                ))
+            };
+            match expr {
+                Expr::Unary(_, operand) => take(operand),
+                Expr::Binary(left, _, right) => {
+                    take(left);
+                    take(right);
+                }
+                Expr::In(left, items) => {
+                    take(left);
+                    children.append(items);
+                }
+                Expr::Call(_, args) => children.append(args),
+                Expr::Lambda {
+                    predicate: Some((_, predicate)),
+                    ..
+                } => take(predicate),
+                _ => {}
+            }
+        }
+        let mut children = Vec::new();
+        take_children(self, &mut children);
+        while let Some(mut child) = children.pop() {
+            take_children(&mut child, &mut children);
+        }
+    }
+}
+
+fn write_list(f: &mut Formatter<'_>, items: &[Expr]) -> core::fmt::Result {
+    for (idx, item) in items.iter().enumerate() {
+        if idx > 0 {
+            f.write_char(',')?;
+        }
+        write!(f, "{}", item)?;
+    }
+    Ok(())
+}
+
+impl Display for Expr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Expr::Literal(literal) => literal.fmt(f),
+            Expr::Member(path) => f.write_str(&path.join("/")),
+            Expr::Unary(UnaryOp::Not, operand) => {
+                f.write_str("not ")?;
+                operand.fmt_at(f, 7)
+            }
+            Expr::Unary(UnaryOp::Neg, operand) => {
+                f.write_char('-')?;
+                match **operand {
+                    // `-5` reads back as a literal
+                    Expr::Literal(Literal::Integer(_) | Literal::Decimal(_)) => {
+                        write!(f, "({})", operand)
+                    }
+                    _ => operand.fmt_at(f, 7),
+                }
+            }
+            Expr::Binary(left, op, right) => {
+                // the left spine of a chain (`a add b add c`) is walked, not recursed into
+                let precedence = op.precedence();
+                let mut chain = alloc::vec![(op, right)];
+                let mut leftmost = left;
+                while let Expr::Binary(left, op, right) = &**leftmost {
+                    if op.precedence() != precedence {
+                        break;
+                    }
+                    chain.push((op, right));
+                    leftmost = left;
+                }
+                leftmost.fmt_at(f, precedence)?;
+                for (op, right) in chain.iter().rev() {
+                    write!(f, " {} ", op)?;
+                    right.fmt_at(f, precedence + 1)?;
+                }
+                Ok(())
+            }
+            Expr::In(left, items) => {
+                left.fmt_at(f, 8)?;
+                f.write_str(" in (")?;
+                write_list(f, items)?;
+                f.write_char(')')
+            }
+            Expr::Call(name, args) => {
+                write!(f, "{}(", name)?;
+                write_list(f, args)?;
+                f.write_char(')')
+            }
+            Expr::Lambda {
+                path,
+                kind,
+                predicate,
+            } => {
+                let kind = match kind {
+                    LambdaKind::Any => "any",
+                    LambdaKind::All => "all",
+                };
+                write!(f, "{}/{}(", path.join("/"), kind)?;
+                if let Some((variable, predicate)) = predicate {
+                    write!(f, "{}:{}", variable, predicate)?;
+                }
+                f.write_char(')')
+            }
+        }
+    }
+}
+
+/// # An `$orderby` item
+#[derive(Clone, Debug, PartialEq)]
+pub struct OrderBy {
+    pub expr: Expr,
+    pub direction: Direction,
+}
+
+/// # An `$expand` item and its nested options
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Expand {
+    pub path: String,
+    pub options: QueryOptions,
+}
+
+/// # System query options
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct QueryOptions {
+    pub filter: Option<Expr>,
+    /// paths, or `*`
+    pub select: Option<Vec<String>>,
+    pub expand: Option<Vec<Expand>>,
+    pub orderby: Option<Vec<OrderBy>>,
+    pub top: Option<u64>,
+    pub skip: Option<u64>,
+    pub count: Option<bool>,
+    pub search: Option<String>,
+}
+
+/// Percent-decode, `+` kept
+fn decode_keeping_plus(s: &str) -> Cow<'_, str> {
+    match percent_decode_bytes(s.as_bytes(), false) {
+        None => Cow::Borrowed(s),
+        Some(bytes) => match String::from_utf8(bytes) {
+            Ok(s) => Cow::Owned(s),
+            Err(e) => Cow::Owned(String::from_utf8_lossy(e.as_bytes()).into_owned()),
+        },
+    }
+}
+
+/// The `$` pairs of `data`: values still as parsed are decoded from the raw query with `+` kept,
+/// the values of a key set, pushed or deleted after parsing are the current ones
+fn option_pairs<'d, S: BuildHasher + Clone>(
+    data: &'d UrlEncodedData<'_, S>,
+) -> Vec<(&'d str, Cow<'d, str>)> {
+    let raw: Vec<_> = raw_pairs(data.original_data_str)
+        .map(|(k, v)| (decode(k), v))
+        .filter(|(k, _)| k.starts_with('$'))
+        .collect();
+    let current: Vec<_> = ordered_pairs(data, KeyOrder::Original, ValueOrder::Original)
+        .into_iter()
+        .filter(|(k, _)| k.starts_with('$'))
+        .collect();
+    let mut pairs = Vec::with_capacity(current.len());
+    let mut rest = current.as_slice();
+    while let Some(&(key, _)) = rest.first() {
+        let count = rest.iter().take_while(|(k, _)| *k == key).count();
+        let (values, tail) = rest.split_at(count);
+        rest = tail;
+        let parsed: Vec<&str> = raw
+            .iter()
+            .filter(|(k, _)| k == key)
+            .map(|(_, v)| *v)
+            .collect();
+        let as_parsed = parsed.len() == count
+            && parsed
+                .iter()
+                .zip(values)
+                .all(|(raw, (_, v))| decode(raw) == *v);
+        if as_parsed {
+            pairs.extend(parsed.into_iter().map(|v| (key, decode_keeping_plus(v))));
+        } else {
+            pairs.extend(values.iter().map(|&(k, v)| (k, Cow::Borrowed(v))));
+        }
+    }
+    pairs
+}
+
+fn put<T>(slot: &mut Option<T>, value: Result<T, ErrorKind>) -> Result<(), ErrorKind> {
+    if slot.is_some() {
+        return Err(ErrorKind::Duplicated);
+    }
+    *slot = Some(value?);
+    Ok(())
+}
+
+impl QueryOptions {
+    /// # Options of the raw query of `data`, and of the values set after parsing
+    pub fn from_query<S: BuildHasher + Clone>(data: &UrlEncodedData<'_, S>) -> Result<Self, Error> {
+        let mut options = Self::default();
+        for (key, value) in option_pairs(data) {
+            options.set(key, &value, 0)?;
+        }
+        Ok(options)
+    }
+
+    /// `depth`: of `$expand` nesting
+    fn set(&mut self, option: &str, value: &str, depth: usize) -> Result<(), Error> {
+        let result = match option.to_ascii_lowercase().as_str() {
+            "$filter" => put(&mut self.filter, parse_filter(value)),
+            "$select" => put(&mut self.select, parse_select(value)),
+            "$expand" => put(&mut self.expand, parse_expand(value, depth)),
+            "$orderby" => put(&mut self.orderby, parse_orderby(value)),
+            "$top" => put(&mut self.top, parse_count(value)),
+            "$skip" => put(&mut self.skip, parse_count(value)),
+            "$count" => put(
+                &mut self.count,
+                match value {
+                    "true" => Ok(true),
+                    "false" => Ok(false),
+                    _ => Err(ErrorKind::Invalid(value.to_string())),
+                },
+            ),
+            "$search" if value.trim().is_empty() => Err(ErrorKind::Invalid(value.to_string())),
+            "$search" => put(&mut self.search, Ok(value.to_string())),
+            _ => Ok(()),
+        };
+        result.map_err(|kind| Error {
+            option: option.to_string(),
+            kind,
+        })
+    }
+}
+
+fn parse_count(value: &str) -> Result<u64, ErrorKind> {
+    let invalid = || ErrorKind::Invalid(value.to_string());
+    if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(invalid());
+    }
+    value.parse().map_err(|_| invalid())
+}
+
+/// `Name`, `NS.Type`, `$it`
+fn is_identifier(s: &str) -> bool {
+    let s = s.strip_prefix('$').unwrap_or(s);
+    let mut chars = s.chars();
+    matches!(chars.next(), Some(c) if c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_' || c == '.')
+}
+
+fn is_path(s: &str, star: bool) -> bool {
+    s.split('/')
+        .all(|segment| is_identifier(segment) || (star && segment == "*"))
+}
+
+fn parse_select(value: &str) -> Result<Vec<String>, ErrorKind> {
+    value
+        .split(',')
+        .map(|item| {
+            let item = item.trim();
+            if is_path(item, true) {
+                Ok(item.to_string())
+            } else {
+                Err(ErrorKind::Invalid(item.to_string()))
+            }
+        })
+        .collect()
+}
+
+/// Pieces of `value` separated by `separator` out of parentheses and strings, with their offsets
+fn split_top_level(value: &str, separator: char) -> Result<Vec<(usize, &str)>, ErrorKind> {
+    let mut pieces = Vec::new();
+    let mut depth = 0usize;
+    let mut quote_start = None;
+    let mut start = 0;
+    for (idx, c) in value.char_indices() {
+        match c {
+            // `''` closes and reopens: same state
+            '\'' if quote_start.is_some() => quote_start = None,
+            '\'' => quote_start = Some(idx),
+            _ if quote_start.is_some() => {}
+            '(' => depth += 1,
+            ')' if depth == 0 => {
+                return Err(ErrorKind::UnexpectedToken {
+                    position: idx,
+                    token: ")".to_string(),
+                })
+            }
+            ')' => depth -= 1,
+            c if c == separator && depth == 0 => {
+                pieces.push((start, &value[start..idx]));
+                start = idx + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    if let Some(position) = quote_start {
+        return Err(ErrorKind::UnterminatedString { position });
+    }
+    if depth > 0 {
+        return Err(ErrorKind::UnexpectedEnd {
+            position: value.len(),
+        });
+    }
+    pieces.push((start, &value[start..]));
+    Ok(pieces)
+}
+
+fn parse_expand(value: &str, depth: usize) -> Result<Vec<Expand>, ErrorKind> {
+    let mut items = Vec::new();
+    for (offset, item) in split_top_level(value, ',')? {
+        if depth == MAX_DEPTH && item.contains('(') {
+            return Err(ErrorKind::TooDeep { position: offset });
+        }
+        let (path, nested) = match item.find('(') {
+            Some(open) if item.trim_end().ends_with(')') => (
+                &item[..open],
+                &item.trim_end()[open + 1..item.trim_end().len() - 1],
+            ),
+            Some(open) => {
+                return Err(ErrorKind::UnexpectedToken {
+                    position: offset + open,
+                    token: item[open..].to_string(),
+                })
+            }
+            None => (item, ""),
+        };
+        let path = path.trim();
+        if !is_path(path, true) {
+            return Err(ErrorKind::Invalid(path.to_string()));
+        }
+        let mut options = QueryOptions::default();
+        for (_, option) in split_top_level(nested, ';')? {
+            if option.trim().is_empty() {
+                continue;
+            }
+            let (name, value) = option.split_once('=').unwrap_or((option, ""));
+            options
+                .set(name.trim(), value, depth + 1)
+                .map_err(|error| ErrorKind::Expand {
+                    path: path.to_string(),
+                    error: Box::new(error),
+                })?;
+        }
+        items.push(Expand {
+            path: path.to_string(),
+            options,
+        });
+    }
+    Ok(items)
+}
+
+fn parse_filter(value: &str) -> Result<Expr, ErrorKind> {
+    let mut parser = Parser::new(value);
+    let expr = parser.expr()?;
+    parser.end()?;
+    Ok(expr)
+}
+
+fn parse_orderby(value: &str) -> Result<Vec<OrderBy>, ErrorKind> {
+    let mut parser = Parser::new(value);
+    let mut items = Vec::new();
+    loop {
+        let expr = parser.expr()?;
+        let direction = if parser.keyword("desc") {
+            Direction::Descending
+        } else {
+            parser.keyword("asc");
+            Direction::Ascending
+        };
+        items.push(OrderBy { expr, direction });
+        if !parser.eat(',') {
+            break;
+        }
+    }
+    parser.end()?;
+    Ok(items)
+}
+
+fn is_date(s: &str) -> bool {
+    let b = s.as_bytes();
+    b.len() == 10
+        && b.iter().enumerate().all(|(idx, &c)| match idx {
+            4 | 7 => c == b'-',
+            _ => c.is_ascii_digit(),
+        })
+}
+
+/// `hh:mm`, `hh:mm:ss`, `hh:mm:ss.fffffff`
+fn is_time(s: &str) -> bool {
+    let (hms, fraction) = s.split_once('.').unwrap_or((s, "0"));
+    let b = hms.as_bytes();
+    (b.len() == 5 || b.len() == 8)
+        && b.iter().enumerate().all(|(idx, &c)| match idx {
+            2 | 5 => c == b':',
+            _ => c.is_ascii_digit(),
+        })
+        && !fraction.is_empty()
+        && fraction.bytes().all(|c| c.is_ascii_digit())
+}
+
+fn is_date_time_offset(s: &str) -> bool {
+    let (date, time) = match s.split_once('T') {
+        Some(split) => split,
+        None => return false,
+    };
+    let time = match time.strip_suffix(['Z', 'z']) {
+        Some(time) => time,
+        None if time.len() > 6 && time.is_char_boundary(time.len() - 6) => {
+            let (time, zone) = time.split_at(time.len() - 6);
+            if !zone.starts_with(['+', '-']) || !is_time(&zone[1..]) {
+                return false;
+            }
+            time
+        }
+        None => return false,
+    };
+    is_date(date) && is_time(time)
+}
+
+fn is_guid(s: &str) -> bool {
+    let b = s.as_bytes();
+    b.len() == 36
+        && b.iter().enumerate().all(|(idx, &c)| match idx {
+            8 | 13 | 18 | 23 => c == b'-',
+            _ => c.is_ascii_hexdigit(),
+        })
+}
+
+fn literal(word: &str) -> Option<Literal> {
+    Some(match word {
+        "null" => Literal::Null,
+        "true" => Literal::Boolean(true),
+        "false" => Literal::Boolean(false),
+        _ if is_guid(word) => Literal::Guid(word.to_string()),
+        _ if !word
+            .trim_start_matches('-')
+            .starts_with(|c: char| c.is_ascii_digit()) =>
+        {
+            return None
+        }
+        _ if is_date(word) => Literal::Date(word.to_string()),
+        _ if is_date_time_offset(word) => Literal::DateTimeOffset(word.to_string()),
+        _ if is_time(word) => Literal::TimeOfDay(word.to_string()),
+        _ => match word.parse::<i64>() {
+            Ok(n) => Literal::Integer(n),
+            Err(_) => Literal::Decimal(word.parse::<f64>().ok().filter(|n| n.is_finite())?),
+        },
+    })
+}
+
+/// Ends a word: whitespace, parentheses, commas and quotes
+fn ends_word(c: char) -> bool {
+    c.is_whitespace() || matches!(c, '(' | ')' | ',' | '\'')
+}
+
+struct Parser<'s> {
+    input: &'s str,
+    pos: usize,
+    depth: usize,
+}
+
+impl<'s> Parser<'s> {
+    fn new(input: &'s str) -> Self {
+        Self {
+            input,
+            pos: 0,
+            depth: 0,
+        }
+    }
+
+    /// Run `parse` one level deeper, up to `MAX_DEPTH`
+    fn nested<T>(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<T, ErrorKind>,
+    ) -> Result<T, ErrorKind> {
+        if self.depth == MAX_DEPTH {
+            self.skip_whitespace();
+            return Err(ErrorKind::TooDeep { position: self.pos });
+        }
+        self.depth += 1;
+        let parsed = parse(self);
+        self.depth -= 1;
+        parsed
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.input[self.pos..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.bump();
+        }
+    }
+
+    fn eat(&mut self, expected: char) -> bool {
+        self.skip_whitespace();
+        if self.peek() == Some(expected) {
+            self.bump();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn word_end(&self) -> usize {
+        self.input[self.pos..]
+            .find(ends_word)
+            .map_or(self.input.len(), |idx| self.pos + idx)
+    }
+
+    /// Consume the next word if it is `keyword`
+    fn keyword(&mut self, keyword: &str) -> bool {
+        self.skip_whitespace();
+        let end = self.word_end();
+        if &self.input[self.pos..end] == keyword {
+            self.pos = end;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn unexpected(&mut self) -> ErrorKind {
+        self.skip_whitespace();
+        let position = self.pos;
+        let end = self.word_end();
+        match self.peek() {
+            None => ErrorKind::UnexpectedEnd { position },
+            Some(c) if end == position => ErrorKind::UnexpectedToken {
+                position,
+                token: c.to_string(),
+            },
+            Some(_) => ErrorKind::UnexpectedToken {
+                position,
+                token: self.input[position..end].to_string(),
+            },
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), ErrorKind> {
+        if self.eat(expected) {
+            Ok(())
+        } else {
+            Err(self.unexpected())
+        }
+    }
+
+    fn end(&mut self) -> Result<(), ErrorKind> {
+        self.skip_whitespace();
+        match self.peek() {
+            None => Ok(()),
+            Some(_) => Err(self.unexpected()),
+        }
+    }
+
+    fn binary(
+        &mut self,
+        operand: fn(&mut Self) -> Result<Expr, ErrorKind>,
+        ops: &[BinaryOp],
+    ) -> Result<Expr, ErrorKind> {
+        let depth = self.depth;
+        let mut left = operand(self)?;
+        let parsed = loop {
+            self.skip_whitespace();
+            let position = self.pos;
+            let op = match ops.iter().copied().find(|op| self.keyword(op.keyword())) {
+                None => break Ok(left),
+                Some(op) => op,
+            };
+            // each operator of a chain nests the tree one level deeper
+            if self.depth == MAX_DEPTH {
+                break Err(ErrorKind::TooDeep { position });
+            }
+            self.depth += 1;
+            match operand(self) {
+                Ok(right) => left = Expr::Binary(Box::new(left), op, Box::new(right)),
+                Err(e) => break Err(e),
+            }
+        };
+        self.depth = depth;
+        parsed
+    }
+
+    fn expr(&mut self) -> Result<Expr, ErrorKind> {
+        self.nested(|p| {
+            p.binary(
+                |p| p.binary(Self::equality, &[BinaryOp::And]),
+                &[BinaryOp::Or],
+            )
+        })
+    }
+
+    fn equality(&mut self) -> Result<Expr, ErrorKind> {
+        self.binary(
+            |p| {
+                p.binary(
+                    Self::additive,
+                    &[BinaryOp::Gt, BinaryOp::Ge, BinaryOp::Lt, BinaryOp::Le],
+                )
+            },
+            &[BinaryOp::Eq, BinaryOp::Ne],
+        )
+    }
+
+    fn additive(&mut self) -> Result<Expr, ErrorKind> {
+        self.binary(
+            |p| {
+                p.binary(
+                    Self::unary,
+                    &[BinaryOp::Mul, BinaryOp::Div, BinaryOp::DivBy, BinaryOp::Mod],
+                )
+            },
+            &[BinaryOp::Add, BinaryOp::Sub],
+        )
+    }
+
+    fn unary(&mut self) -> Result<Expr, ErrorKind> {
+        if self.keyword("not") {
+            let operand = self.nested(Self::unary)?;
+            return Ok(Expr::Unary(UnaryOp::Not, Box::new(operand)));
+        }
+        self.skip_whitespace();
+        let mut ahead = self.input[self.pos..].chars();
+        if ahead.next() == Some('-') && !ahead.next().is_some_and(|c| c.is_ascii_digit()) {
+            self.bump();
+            let operand = self.nested(Self::unary)?;
+            return Ok(Expr::Unary(UnaryOp::Neg, Box::new(operand)));
+        }
+        self.has_in()
+    }
+
+    /// `has` and `in`, which bind tighter than unary operators
+    fn has_in(&mut self) -> Result<Expr, ErrorKind> {
+        let depth = self.depth;
+        let mut left = self.primary()?;
+        let parsed = loop {
+            self.skip_whitespace();
+            let position = self.pos;
+            let has = self.keyword(BinaryOp::Has.keyword());
+            if !has && !self.keyword("in") {
+                break Ok(left);
+            }
+            // as in `binary`, each operator of a chain nests the tree one level deeper
+            if self.depth == MAX_DEPTH {
+                break Err(ErrorKind::TooDeep { position });
+            }
+            self.depth += 1;
+            let next = if has {
+                self.primary()
+                    .map(|right| Expr::Binary(Box::new(left), BinaryOp::Has, Box::new(right)))
+            } else {
+                self.expect('(')
+                    .and_then(|()| self.list(')'))
+                    .map(|list| Expr::In(Box::new(left), list))
+            };
+            match next {
+                Ok(next) => left = next,
+                Err(e) => break Err(e),
+            }
+        };
+        self.depth = depth;
+        parsed
+    }
+
+    /// Expressions separated by commas, up to `close`; the opening parenthesis is consumed
+    fn list(&mut self, close: char) -> Result<Vec<Expr>, ErrorKind> {
+        let mut items = Vec::new();
+        if self.eat(close) {
+            return Ok(items);
+        }
+        loop {
+            items.push(self.expr()?);
+            if !self.eat(',') {
+                break;
+            }
+        }
+        self.expect(close)?;
+        Ok(items)
+    }
+
+    fn string(&mut self) -> Result<String, ErrorKind> {
+        let position = self.pos;
+        self.bump();
+        let mut s = String::new();
+        loop {
+            match self.bump() {
+                None => return Err(ErrorKind::UnterminatedString { position }),
+                Some('\'') if self.peek() == Some('\'') => {
+                    self.bump();
+                    s.push('\'');
+                }
+                Some('\'') => return Ok(s),
+                Some(c) => s.push(c),
+            }
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expr, ErrorKind> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('(') => {
+                self.bump();
+                let expr = self.expr()?;
+                self.expect(')')?;
+                return Ok(expr);
+            }
+            Some('\'') => return Ok(Expr::Literal(Literal::String(self.string()?))),
+            _ => {}
+        }
+        let start = self.pos;
+        let end = self.word_end();
+        let word = &self.input[start..end];
+        let unexpected_word = || ErrorKind::UnexpectedToken {
+            position: start,
+            token: word.to_string(),
+        };
+        if word.is_empty() {
+            return Err(self.unexpected());
+        }
+        if let Some(literal) = literal(word) {
+            self.pos = end;
+            return Ok(Expr::Literal(literal));
+        }
+        if !is_path(word, false) {
+            return Err(unexpected_word());
+        }
+        self.pos = end;
+        let mut path: Vec<String> = word.split('/').map(String::from).collect();
+        if self.peek() != Some('(') {
+            return Ok(Expr::Member(path));
+        }
+        self.bump();
+        let kind = match path.last().map(String::as_str) {
+            Some("any") if path.len() > 1 => LambdaKind::Any,
+            Some("all") if path.len() > 1 => LambdaKind::All,
+            _ if path.len() == 1 => return Ok(Expr::Call(word.to_string(), self.list(')')?)),
+            _ => return Err(unexpected_word()),
+        };
+        path.pop();
+        let predicate = if self.eat(')') {
+            None
+        } else {
+            self.skip_whitespace();
+            let variable_start = self.pos;
+            let word = &self.input[variable_start..self.word_end()];
+            let variable = &word[..word.find(':').unwrap_or(word.len())];
+            if !is_identifier(variable) {
+                return Err(self.unexpected());
+            }
+            self.pos = variable_start + variable.len();
+            self.expect(':')?;
+            let predicate = self.expr()?;
+            self.expect(')')?;
+            Some((variable.to_string(), Box::new(predicate)))
+        };
+        Ok(Expr::Lambda {
+            path,
+            kind,
+            predicate,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(s: &str) -> Expr {
+        parse_filter(s).unwrap()
+    }
+
+    #[test]
+    fn test_precedence() {
+        for (input, output) in [
+            ("a or b and not c eq 1", "a or b and not c eq 1"),
+            ("(a or b) and c", "(a or b) and c"),
+            ("((a))", "a"),
+            ("a add b mul c sub d", "a add b mul c sub d"),
+            ("(a add b) mul -c", "(a add b) mul -c"),
+            ("a sub (b sub c)", "a sub (b sub c)"),
+            ("not (a and b)", "not (a and b)"),
+            ("- (5) eq -5", "-(5) eq -5"),
+            ("Price  div  2.0 ge 1.5e3", "Price div 2.0 ge 1500.0"),
+            (
+                "Name in ('a', 'b''c') and Tags/any(t : t eq 'x')",
+                "Name in ('a','b''c') and Tags/any(t:t eq 'x')",
+            ),
+            (
+                "Items/all(i:i/Qty gt 0) or Items/any()",
+                "Items/all(i:i/Qty gt 0) or Items/any()",
+            ),
+            ("year(Date) eq 2012", "year(Date) eq 2012"),
+            ("a lt b ge c eq (d ne e)", "a lt b ge c eq (d ne e)"),
+            ("a eq (b gt c)", "a eq b gt c"),
+            ("not (a eq true)", "not (a eq true)"),
+            ("-(a in (1)) has b", "-a in (1) has b"),
+            ("(not a) has b", "(not a) has b"),
+        ] {
+            let expr = filter(input);
+            assert_eq!(expr.to_string(), output);
+            assert_eq!(filter(output), expr, "{}", output);
+        }
+        assert_eq!(
+            filter("a eq 1 or b eq 2 and c eq 3"),
+            Expr::Binary(
+                Box::new(filter("a eq 1")),
+                BinaryOp::Or,
+                Box::new(filter("b eq 2 and c eq 3"))
+            )
+        );
+        let member = |name: &str| Box::new(Expr::Member(vec![name.to_string()]));
+        let boolean = Box::new(Expr::Literal(Literal::Boolean(true)));
+        assert_eq!(
+            filter("Price gt 5 eq true"),
+            Expr::Binary(
+                Box::new(filter("Price gt 5")),
+                BinaryOp::Eq,
+                boolean.clone()
+            )
+        );
+        assert_eq!(
+            filter("not Active eq true"),
+            Expr::Binary(
+                Box::new(Expr::Unary(UnaryOp::Not, member("Active"))),
+                BinaryOp::Eq,
+                boolean
+            )
+        );
+        assert_eq!(
+            filter("a add b has c"),
+            Expr::Binary(
+                member("a"),
+                BinaryOp::Add,
+                Box::new(Expr::Binary(member("b"), BinaryOp::Has, member("c")))
+            )
+        );
+        assert_eq!(
+            filter("not a in (1)"),
+            Expr::Unary(UnaryOp::Not, Box::new(filter("a in (1)")))
+        );
+    }
+
+    #[test]
+    fn test_literals() {
+        for (input, literal) in [
+            ("null", Literal::Null),
+            ("-12", Literal::Integer(-12)),
+            ("0.5", Literal::Decimal(0.5)),
+            ("'it''s'", Literal::String("it's".to_string())),
+            ("2012-12-03", Literal::Date("2012-12-03".to_string())),
+            (
+                "07:16:23.125",
+                Literal::TimeOfDay("07:16:23.125".to_string()),
+            ),
+            (
+                "2012-12-03T07:16:23+01:00",
+                Literal::DateTimeOffset("2012-12-03T07:16:23+01:00".to_string()),
+            ),
+            (
+                "2012-12-03T07:16Z",
+                Literal::DateTimeOffset("2012-12-03T07:16Z".to_string()),
+            ),
+            (
+                "a1b2c3d4-0000-1111-2222-abcdefabcdef",
+                Literal::Guid("a1b2c3d4-0000-1111-2222-abcdefabcdef".to_string()),
+            ),
+        ] {
+            assert_eq!(filter(input), Expr::Literal(literal), "{}", input);
+        }
+        assert!(parse_filter("2012-13").is_err());
+        assert!(parse_filter("1e999").is_err());
+        // enum literals are not supported
+        assert!(parse_filter("Style has NS.Color'Y'").is_err());
+        // a multibyte character where the time zone would start
+        let q = UrlEncodedData::parse_str("$filter=1Ta%C3%A912345");
+        assert!(QueryOptions::from_query(&q).is_err());
+    }
+
+    #[test]
+    fn test_options_and_errors() {
+        let options = |s: &str| QueryOptions::from_query(&UrlEncodedData::parse_str(s));
+        let ok = options(
+            "$Select=Id,Address/City&%24skip=20&$search=blue%20OR%20green&$format=json&filter=x&\
+             $expand=Orders($filter=Note%20eq%20'a;b)';$expand=Items($select=*)),Customer",
+        )
+        .unwrap();
+        assert_eq!(ok.select.unwrap(), ["Id", "Address/City"]);
+        assert_eq!(ok.skip, Some(20));
+        assert_eq!(ok.search.unwrap(), "blue OR green");
+        let expand = ok.expand.unwrap();
+        assert_eq!(expand.len(), 2);
+        assert_eq!(
+            expand[0].options.filter.as_ref().unwrap().to_string(),
+            "Note eq 'a;b)'"
+        );
+        let items = &expand[0].options.expand.as_ref().unwrap()[0];
+        assert_eq!(
+            (
+                items.path.as_str(),
+                items.options.select.as_ref().unwrap()[0].as_str()
+            ),
+            ("Items", "*")
+        );
+        assert_eq!(
+            expand[1],
+            Expand {
+                path: "Customer".to_string(),
+                options: QueryOptions::default()
+            }
+        );
+
+        // values set after parsing, `+` kept in the ones still as parsed
+        let mut q =
+            UrlEncodedData::parse_str("$filter=d%20lt%202012-12-03T07:16:23+01:00&$top=1&$skip=2");
+        q.set_one("$top", "5").delete("$skip");
+        let set = QueryOptions::from_query(&q).unwrap();
+        assert_eq!((set.top, set.skip), (Some(5), None));
+        assert_eq!(
+            set.filter.unwrap().to_string(),
+            "d lt 2012-12-03T07:16:23+01:00"
+        );
+        let params = crate::search_params::UrlSearchParams::parse("$top=3&$select=a");
+        let built = QueryOptions::from_query(&params.into_url_encoded_data()).unwrap();
+        assert_eq!(built.top, Some(3));
+        assert_eq!(built.select.unwrap(), ["a"]);
+
+        let error = |s: &str| options(s).unwrap_err().to_string();
+        assert_eq!(error("$top=1&$TOP=2"), "$TOP: present more than once");
+        assert_eq!(error("$skip=-1"), "$skip: invalid value \"-1\"");
+        assert_eq!(error("$count=1"), "$count: invalid value \"1\"");
+        assert_eq!(error("$select=a,,b"), "$select: invalid value \"\"");
+        assert_eq!(
+            error("$orderby=a%20desc%20b"),
+            "$orderby: unexpected \"b\" at position 7"
+        );
+        assert_eq!(
+            error("$filter=Name%20eq%20'x"),
+            "$filter: unterminated string at position 8"
+        );
+        assert_eq!(
+            error("$expand=Orders($top=x)"),
+            "$expand: in Orders: $top: invalid value \"x\""
+        );
+        assert_eq!(
+            error("$expand=Orders($top=1"),
+            "$expand: unexpected end at position 13"
+        );
+        let nested = |depth: usize| ["(".repeat(depth), "a".into(), ")".repeat(depth)].concat();
+        assert!(parse_filter(&nested(MAX_DEPTH - 1)).is_ok());
+        assert_eq!(
+            parse_filter(&nested(25_000)),
+            Err(ErrorKind::TooDeep { position: 64 })
+        );
+        let chain = |length: usize| ["a", &" add a".repeat(length)].concat();
+        assert!(parse_filter(&chain(MAX_DEPTH - 1)).is_ok());
+        assert_eq!(
+            parse_filter(&chain(10_000)),
+            Err(ErrorKind::TooDeep { position: 380 })
+        );
+        assert!(matches!(
+            parse_filter(&["a", &" eq a or a".repeat(10_000)].concat()),
+            Err(ErrorKind::TooDeep { .. })
+        ));
+        // the items of an `in` list sit one level below the operator
+        for (operator, longest) in [(" has b", MAX_DEPTH - 1), (" in (1)", MAX_DEPTH - 2)] {
+            assert!(parse_filter(&["a", &operator.repeat(longest)].concat()).is_ok());
+            assert!(parse_filter(&["a", &operator.repeat(longest + 1)].concat()).is_err());
+            assert!(matches!(
+                parse_filter(&["a", &operator.repeat(200_000)].concat()),
+                Err(ErrorKind::TooDeep { .. })
+            ));
+        }
+        // trees built by hand: `Display` and `Drop` do not recurse along the chain
+        let a = || Box::new(Expr::Member(vec!["a".to_string()]));
+        let mut long = Expr::Member(vec!["a".to_string()]);
+        for _ in 0..200_000 {
+            long = Expr::Binary(Box::new(long), BinaryOp::Add, a());
+        }
+        assert_eq!(long.to_string(), chain(200_000));
+        drop(long);
+        assert!(matches!(
+            parse_filter(&"not ".repeat(25_000)),
+            Err(ErrorKind::TooDeep { .. })
+        ));
+        assert!(matches!(
+            parse_filter(&"-".repeat(25_000)),
+            Err(ErrorKind::TooDeep { .. })
+        ));
+        let expand = [
+            "$expand=",
+            &"a($expand=".repeat(25_000),
+            "b",
+            &")".repeat(25_000),
+        ]
+        .concat();
+        assert!(error(&expand).starts_with("$expand: in a: $expand: in a: "));
+        assert!(error(&expand).ends_with("$expand: nested deeper than 64 at position 0"));
+        // a form-decoded `+` would have been a space
+        let offset = options("$filter=d%20lt%202012-12-03T07:16:23+01:00").unwrap();
+        assert_eq!(
+            offset.filter.unwrap().to_string(),
+            "d lt 2012-12-03T07:16:23+01:00"
+        );
+    }
+}
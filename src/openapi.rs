@@ -0,0 +1,562 @@
+//! # OpenAPI 3 query parameter styles
+//!
+//! A `Parameter` (name, `Style`, explode) encodes a `Value` (primitive, array or object) to pairs
+//! and decodes it back from `UrlEncodedData`, following the style examples table of the OpenAPI specification
+//! (parameter `color`, string `"blue"`, array `["blue", "black", "brown"]`, object `{"R": 100, "G": 200, "B": 150}`):
+//!
+//! | style            | explode | empty    | string       | array                                  | object                                   |
+//! |------------------|---------|----------|--------------|----------------------------------------|------------------------------------------|
+//! | `form`           | false   | `color=` | `color=blue` | `color=blue,black,brown`               | `color=R,100,G,200,B,150`                |
+//! | `form`           | true    | `color=` | `color=blue` | `color=blue&color=black&color=brown`   | `R=100&G=200&B=150`                      |
+//! | `spaceDelimited` | false   | n/a      | n/a          | `color=blue%20black%20brown`           | `color=R%20100%20G%20200%20B%20150`      |
+//! | `pipeDelimited`  | false   | n/a      | n/a          | `color=blue\|black\|brown`             | `color=R\|100\|G\|200\|B\|150`           |
+//! | `deepObject`     | true    | n/a      | n/a          | n/a                                    | `color[R]=100&color[G]=200&color[B]=150` |
+//!
+//! `to_query_string` writes exactly these strings: delimiters literally, names and values RFC 3986 encoded
+//! (so a `,` inside a value is `%2C`). Arrays with `spaceDelimited` or `pipeDelimited` and explode are
+//! written as with `form`. Other n/a combinations are `Error::Unsupported`.
+//!
+//! Decoding reads decoded pairs, where an encoded delimiter inside a value can no longer be told apart:
+//! keep delimiters out of values, as the specification recommends.
+//!
+//! ```rust
+//! use url_encoded_data::openapi::{Kind, Parameter, Style, Value};
+//! use url_encoded_data::UrlEncodedData;
+//!
+//! let color = Parameter::new("color", Style::DeepObject);
+//! let rgb = Value::object([("R", "100"), ("G", "200"), ("B", "150")]);
+//! assert_eq!(color.to_query_string(&rgb).unwrap(), "color[R]=100&color[G]=200&color[B]=150");
+//!
+//! let q = UrlEncodedData::parse_str("https://abc.com/?color%5BR%5D=100&color%5BG%5D=200&color%5BB%5D=150&page=2");
+//! assert_eq!(color.decode(&q, Kind::Object).unwrap(), Some(rgb));
+//!
+//! let tags = Parameter::new("tags", Style::PipeDelimited);
+//! let q = UrlEncodedData::parse_str("tags=a|b|c");
+//! assert_eq!(tags.decode(&q, Kind::Array).unwrap(), Some(Value::array(["a", "b", "c"])));
+//! ```
+
+use alloc::borrow::Cow;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::{Display, Formatter};
+use core::hash::BuildHasher;
+
+use crate::canonical::{ordered_pairs, KeyOrder, ValueOrder};
+use crate::codec::append_rfc3986_encoded;
+use crate::{Pair, UrlEncodedData};
+
+/// # Serialization style of a query parameter
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Style {
+    Form,
+    SpaceDelimited,
+    PipeDelimited,
+    DeepObject,
+}
+
+impl Style {
+    /// delimiter of values, decoded
+    fn delimiter(&self) -> char {
+        match self {
+            Style::SpaceDelimited => ' ',
+            Style::PipeDelimited => '|',
+            _ => ',',
+        }
+    }
+
+    /// delimiter of values, as written
+    fn raw_delimiter(&self) -> &'static str {
+        match self {
+            Style::SpaceDelimited => "%20",
+            Style::PipeDelimited => "|",
+            _ => ",",
+        }
+    }
+}
+
+impl Display for Style {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.write_str(match self {
+            Style::Form => "form",
+            Style::SpaceDelimited => "spaceDelimited",
+            Style::PipeDelimited => "pipeDelimited",
+            Style::DeepObject => "deepObject",
+        })
+    }
+}
+
+/// # Shape of a value, expected by `decode`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Kind {
+    Primitive,
+    Array,
+    Object,
+}
+
+/// # A parameter value: query strings are untyped, so are primitives
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Value {
+    Primitive(String),
+    Array(Vec<String>),
+    /// properties in order
+    Object(Vec<(String, String)>),
+}
+
+impl Value {
+    pub fn kind(&self) -> Kind {
+        match self {
+            Value::Primitive(_) => Kind::Primitive,
+            Value::Array(_) => Kind::Array,
+            Value::Object(_) => Kind::Object,
+        }
+    }
+
+    pub fn array<I: IntoIterator<Item = T>, T: Into<String>>(items: I) -> Self {
+        Value::Array(items.into_iter().map(Into::into).collect())
+    }
+
+    pub fn object<I: IntoIterator<Item = (K, V)>, K: Into<String>, V: Into<String>>(
+        properties: I,
+    ) -> Self {
+        Value::Object(
+            properties
+                .into_iter()
+                .map(|(k, v)| (k.into(), v.into()))
+                .collect(),
+        )
+    }
+}
+
+/// # Errors of encoding and decoding
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// a combination marked n/a in the specification
+    Unsupported {
+        style: Style,
+        explode: bool,
+        kind: Kind,
+    },
+    /// a non-exploded parameter present more than once
+    Duplicated(String),
+    /// an object with an odd count of items
+    Malformed(String),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::Unsupported {
+                style,
+                explode,
+                kind,
+            } => write!(
+                f,
+                "{:?} with style {} and explode {} is not defined",
+                kind, style, explode
+            ),
+            Error::Duplicated(name) => write!(f, "parameter {:?} is present more than once", name),
+            Error::Malformed(name) => {
+                write!(f, "parameter {:?} is not a list of key-value items", name)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
+/// Pairs of an encoded value, values joined with a delimiter are written raw
+enum Encoded<'p> {
+    /// (name, value)
+    Pairs(Vec<(Cow<'p, str>, &'p str)>),
+    /// (name, items joined with the delimiter)
+    Delimited(&'p str, Vec<&'p str>),
+}
+
+/// # A query parameter: name, style and explode
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Parameter<'n> {
+    pub name: &'n str,
+    pub style: Style,
+    pub explode: bool,
+}
+
+impl<'n> Parameter<'n> {
+    /// With the default explode of the style: `true` for `form` and `deepObject`, `false` otherwise
+    pub fn new(name: &'n str, style: Style) -> Self {
+        Self {
+            name,
+            style,
+            explode: matches!(style, Style::Form | Style::DeepObject),
+        }
+    }
+
+    pub fn explode(mut self, explode: bool) -> Self {
+        self.explode = explode;
+        self
+    }
+
+    fn unsupported(&self, kind: Kind) -> Error {
+        Error::Unsupported {
+            style: self.style,
+            explode: self.explode,
+            kind,
+        }
+    }
+
+    fn encoded<'p>(&'p self, value: &'p Value) -> Result<Encoded<'p>, Error> {
+        let name = self.name;
+        let flat_object = |properties: &'p [(String, String)]| -> Vec<&'p str> {
+            properties
+                .iter()
+                .flat_map(|(k, v)| [k.as_str(), v.as_str()])
+                .collect()
+        };
+        match (self.style, self.explode, value) {
+            (Style::Form, _, Value::Primitive(v)) => Ok(Encoded::Pairs(alloc::vec![(
+                Cow::Borrowed(name),
+                v.as_str()
+            )])),
+            (Style::Form, _, Value::Array(items)) if items.is_empty() => {
+                Ok(Encoded::Pairs(alloc::vec![(Cow::Borrowed(name), "")]))
+            }
+            (Style::Form, _, Value::Object(properties)) if properties.is_empty() => {
+                Ok(Encoded::Pairs(alloc::vec![(Cow::Borrowed(name), "")]))
+            }
+            (
+                Style::Form | Style::SpaceDelimited | Style::PipeDelimited,
+                true,
+                Value::Array(items),
+            ) => Ok(Encoded::Pairs(
+                items
+                    .iter()
+                    .map(|v| (Cow::Borrowed(name), v.as_str()))
+                    .collect(),
+            )),
+            (Style::Form, true, Value::Object(properties)) => Ok(Encoded::Pairs(
+                properties
+                    .iter()
+                    .map(|(k, v)| (Cow::Borrowed(k.as_str()), v.as_str()))
+                    .collect(),
+            )),
+            (Style::DeepObject, true, Value::Object(properties)) => Ok(Encoded::Pairs(
+                properties
+                    .iter()
+                    .map(|(k, v)| (Cow::Owned([name, "[", k, "]"].concat()), v.as_str()))
+                    .collect(),
+            )),
+            (
+                Style::Form | Style::SpaceDelimited | Style::PipeDelimited,
+                false,
+                Value::Array(items),
+            ) => Ok(Encoded::Delimited(
+                name,
+                items.iter().map(|v| v.as_str()).collect(),
+            )),
+            (
+                Style::Form | Style::SpaceDelimited | Style::PipeDelimited,
+                false,
+                Value::Object(properties),
+            ) => Ok(Encoded::Delimited(name, flat_object(properties))),
+            _ => Err(self.unsupported(value.kind())),
+        }
+    }
+
+    /// # Decoded pairs of `value`, in order
+    /// ```rust
+    /// use url_encoded_data::openapi::{Parameter, Style, Value};
+    /// let color = Parameter::new("color", Style::SpaceDelimited);
+    /// let pairs = color.encode(&Value::array(["blue", "black"])).unwrap();
+    /// assert_eq!(pairs, [("color".into(), "blue black".into())]);
+    /// ```
+    pub fn encode(&self, value: &Value) -> Result<Vec<Pair<'static>>, Error> {
+        let owned = |s: &str| Cow::Owned(s.to_string());
+        Ok(match self.encoded(value)? {
+            Encoded::Pairs(pairs) => pairs
+                .into_iter()
+                .map(|(k, v)| (owned(&k), owned(v)))
+                .collect(),
+            Encoded::Delimited(name, items) => {
+                let delimiter = self.style.delimiter().to_string();
+                alloc::vec![(owned(name), Cow::Owned(items.join(&delimiter)))]
+            }
+        })
+    }
+
+    /// # Query string of `value`, exactly as in the table of the specification
+    /// ```rust
+    /// use url_encoded_data::openapi::{Parameter, Style, Value};
+    /// let color = Parameter::new("color", Style::Form).explode(false);
+    /// assert_eq!(color.to_query_string(&Value::array(["a,b", "c d"])).unwrap(), "color=a%2Cb,c%20d");
+    /// ```
+    pub fn to_query_string(&self, value: &Value) -> Result<String, Error> {
+        let mut out = String::new();
+        match self.encoded(value)? {
+            Encoded::Pairs(pairs) => {
+                for (k, v) in pairs {
+                    if !out.is_empty() {
+                        out.push('&');
+                    }
+                    match self.style {
+                        // brackets are written as in the specification
+                        Style::DeepObject => {
+                            let property = &k[self.name.len() + 1..k.len() - 1];
+                            append_rfc3986_encoded(&mut out, self.name, true);
+                            out.push('[');
+                            append_rfc3986_encoded(&mut out, property, true);
+                            out.push(']');
+                        }
+                        _ => append_rfc3986_encoded(&mut out, &k, true),
+                    }
+                    out.push('=');
+                    append_rfc3986_encoded(&mut out, v, true);
+                }
+            }
+            Encoded::Delimited(name, items) => {
+                append_rfc3986_encoded(&mut out, name, true);
+                out.push('=');
+                for (idx, item) in items.iter().enumerate() {
+                    if idx > 0 {
+                        out.push_str(self.style.raw_delimiter());
+                    }
+                    append_rfc3986_encoded(&mut out, item, true);
+                }
+            }
+        }
+        Ok(out)
+    }
+
+    /// # Decode the parameter from `data`, `None` if it is absent
+    ///
+    /// An exploded `form` object is made of every pair of `data`, in original order.
+    pub fn decode<S: BuildHasher + Clone>(
+        &self,
+        data: &UrlEncodedData<'_, S>,
+        kind: Kind,
+    ) -> Result<Option<Value>, Error> {
+        let name = self.name;
+        let values = || data.map.get(name).map(|values| values.as_slice());
+        let single = || -> Result<Option<&str>, Error> {
+            match values() {
+                None | Some([]) => Ok(None),
+                Some([value]) => Ok(Some(value.as_ref())),
+                Some(_) => Err(Error::Duplicated(name.to_string())),
+            }
+        };
+        let split = |value: &str| -> Vec<String> {
+            if value.is_empty() {
+                Vec::new()
+            } else {
+                value
+                    .split(self.style.delimiter())
+                    .map(ToString::to_string)
+                    .collect()
+            }
+        };
+        let into_object = |items: Vec<String>| -> Result<Value, Error> {
+            if items.len() % 2 == 1 {
+                return Err(Error::Malformed(name.to_string()));
+            }
+            let mut items = items.into_iter();
+            let mut properties = Vec::new();
+            while let (Some(k), Some(v)) = (items.next(), items.next()) {
+                properties.push((k, v));
+            }
+            Ok(Value::Object(properties))
+        };
+
+        match (self.style, self.explode, kind) {
+            (Style::Form, _, Kind::Primitive) => {
+                Ok(single()?.map(|v| Value::Primitive(v.to_string())))
+            }
+            (Style::Form | Style::SpaceDelimited | Style::PipeDelimited, true, Kind::Array) => {
+                Ok(values().filter(|values| !values.is_empty()).map(|values| {
+                    if let [value] = values {
+                        if value.is_empty() {
+                            return Value::Array(Vec::new());
+                        }
+                    }
+                    Value::array(values.iter().map(|v| v.as_ref()))
+                }))
+            }
+            (Style::Form, true, Kind::Object) => {
+                if let Some([value]) = values() {
+                    if value.is_empty() {
+                        return Ok(Some(Value::Object(Vec::new())));
+                    }
+                }
+                let pairs = ordered_pairs(data, KeyOrder::Original, ValueOrder::Original);
+                Ok(if pairs.is_empty() {
+                    None
+                } else {
+                    Some(Value::object(pairs))
+                })
+            }
+            (Style::DeepObject, true, Kind::Object) => {
+                let properties: Vec<(&str, &str)> =
+                    ordered_pairs(data, KeyOrder::Original, ValueOrder::Original)
+                        .into_iter()
+                        .filter_map(|(k, v)| {
+                            let property =
+                                k.strip_prefix(name)?.strip_prefix('[')?.strip_suffix(']')?;
+                            if property.contains(['[', ']']) {
+                                None
+                            } else {
+                                Some((property, v))
+                            }
+                        })
+                        .collect();
+                Ok(if properties.is_empty() {
+                    None
+                } else {
+                    Some(Value::object(properties))
+                })
+            }
+            (Style::Form | Style::SpaceDelimited | Style::PipeDelimited, false, Kind::Array) => {
+                Ok(single()?.map(|v| Value::Array(split(v))))
+            }
+            (Style::Form | Style::SpaceDelimited | Style::PipeDelimited, false, Kind::Object) => {
+                single()?.map(|v| into_object(split(v))).transpose()
+            }
+            _ => Err(self.unsupported(kind)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// the style examples table of the specification, `None` is n/a
+    #[test]
+    fn test_specification_table() {
+        let empty = Value::Primitive(String::new());
+        let string = Value::Primitive("blue".into());
+        let array = Value::array(["blue", "black", "brown"]);
+        let object = Value::object([("R", "100"), ("G", "200"), ("B", "150")]);
+        let table: [(Style, bool, [Option<&str>; 4]); 5] = [
+            (
+                Style::Form,
+                false,
+                [
+                    Some("color="),
+                    Some("color=blue"),
+                    Some("color=blue,black,brown"),
+                    Some("color=R,100,G,200,B,150"),
+                ],
+            ),
+            (
+                Style::Form,
+                true,
+                [
+                    Some("color="),
+                    Some("color=blue"),
+                    Some("color=blue&color=black&color=brown"),
+                    Some("R=100&G=200&B=150"),
+                ],
+            ),
+            (
+                Style::SpaceDelimited,
+                false,
+                [
+                    None,
+                    None,
+                    Some("color=blue%20black%20brown"),
+                    Some("color=R%20100%20G%20200%20B%20150"),
+                ],
+            ),
+            (
+                Style::PipeDelimited,
+                false,
+                [
+                    None,
+                    None,
+                    Some("color=blue|black|brown"),
+                    Some("color=R|100|G|200|B|150"),
+                ],
+            ),
+            (
+                Style::DeepObject,
+                true,
+                [
+                    None,
+                    None,
+                    None,
+                    Some("color[R]=100&color[G]=200&color[B]=150"),
+                ],
+            ),
+        ];
+        for (style, explode, row) in table {
+            let parameter = Parameter::new("color", style).explode(explode);
+            for (value, expected) in [&empty, &string, &array, &object].iter().zip(row) {
+                let kind = value.kind();
+                let encoded = parameter.to_query_string(value);
+                match expected {
+                    // n/a
+                    None => {
+                        assert!(encoded.is_err(), "{} {} {:?}", style, explode, value);
+                        assert_eq!(
+                            encoded.unwrap_err(),
+                            Error::Unsupported {
+                                style,
+                                explode,
+                                kind
+                            }
+                        );
+                    }
+                    Some(expected) => {
+                        assert_eq!(encoded.unwrap(), expected, "{} {}", style, explode);
+                        let q = UrlEncodedData::parse_str(expected);
+                        assert_eq!(
+                            parameter.decode(&q, kind).unwrap().as_ref(),
+                            Some(*value),
+                            "{} {} {}",
+                            style,
+                            explode,
+                            expected
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_decode_edge_cases() {
+        let form = Parameter::new("color", Style::Form).explode(false);
+        let q = UrlEncodedData::parse_str("color=a&color=b");
+        assert_eq!(
+            form.decode(&q, Kind::Array),
+            Err(Error::Duplicated("color".into()))
+        );
+        let q = UrlEncodedData::parse_str("color=R,100,G");
+        assert_eq!(
+            form.decode(&q, Kind::Object),
+            Err(Error::Malformed("color".into()))
+        );
+        assert_eq!(
+            form.decode(&q, Kind::Array).unwrap(),
+            Some(Value::array(["R", "100", "G"]))
+        );
+        let q = UrlEncodedData::parse_str("size=1");
+        assert_eq!(form.decode(&q, Kind::Array), Ok(None));
+        assert_eq!(
+            Parameter::new("color", Style::DeepObject).decode(&q, Kind::Object),
+            Ok(None)
+        );
+        // nested deep objects are not defined by the specification
+        let q = UrlEncodedData::parse_str("color[R]=1&color[a][b]=2&colors[G]=3");
+        assert_eq!(
+            Parameter::new("color", Style::DeepObject).decode(&q, Kind::Object),
+            Ok(Some(Value::object([("R", "1")])))
+        );
+        assert_eq!(
+            Parameter::new("color", Style::DeepObject).decode(&q, Kind::Array),
+            Err(Error::Unsupported {
+                style: Style::DeepObject,
+                explode: true,
+                kind: Kind::Array
+            })
+        );
+    }
+}
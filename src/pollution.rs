@@ -0,0 +1,302 @@
+//! # HTTP parameter pollution: what to do with duplicated keys
+//!
+//! `UrlEncodedData::parse_str` keeps every value of a duplicated key, and backends disagree on which one
+//! to use (first, last, all joined, ...), which attackers exploit to slip a value past a filter.
+//! A `PollutionPolicy` resolves duplicates at parse time with a `DuplicatePolicy`, by default and per key,
+//! and `Resolved::duplicates` reports every duplicated key.
+//!
+//! ```rust
+//! use url_encoded_data::pollution::{DuplicatePolicy, Error, PollutionPolicy};
+//! use url_encoded_data::UrlEncodedData;
+//!
+//! let policy = PollutionPolicy::new(DuplicatePolicy::KeepFirst)
+//!     .key("tag", DuplicatePolicy::KeepAll)
+//!     .key("role", DuplicatePolicy::Reject);
+//! let resolved = UrlEncodedData::parse_str_with_policy("id=1&tag=a&id=2&tag=b", &policy).unwrap();
+//! assert_eq!(resolved.data.get("id").unwrap(), ["1"]);
+//! assert_eq!(resolved.data.get("tag").unwrap(), ["a", "b"]);
+//! let duplicated: Vec<_> = resolved.duplicates.iter().map(|d| (d.key.as_ref(), d.indexes.as_slice())).collect();
+//! assert_eq!(duplicated, [("id", &[0, 2][..]), ("tag", &[1, 3][..])]);
+//!
+//! let rejected = UrlEncodedData::parse_str_with_policy("role=user&role=admin", &policy);
+//! assert_eq!(rejected.unwrap_err(), Error::Duplicated { key: "role".into(), count: 2 });
+//! ```
+
+use alloc::borrow::Cow;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::{Display, Formatter};
+use core::hash::BuildHasher;
+
+use crate::{codec, split_url_encoded_string, DefaultHashBuilder, Pair, UrlEncodedData};
+
+/// # Errors of resolution
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// a key with the `Reject` policy is present more than once
+    Duplicated { key: String, count: usize },
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::Duplicated { key, count } => {
+                write!(f, "parameter {:?} is present {} times", key, count)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
+/// # Resolution of a duplicated key
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum DuplicatePolicy {
+    /// fail the whole parse
+    Reject,
+    KeepFirst,
+    KeepLast,
+    /// the behavior of `UrlEncodedData::parse_str`
+    KeepAll,
+    /// a single value: all values joined with the delimiter
+    Join(Cow<'static, str>),
+}
+
+/// # Duplicate policies: a default one, overridden per (decoded) key
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PollutionPolicy {
+    pub default: DuplicatePolicy,
+    pub keys: Vec<(Cow<'static, str>, DuplicatePolicy)>,
+}
+
+impl Default for PollutionPolicy {
+    /// Keep all values of every key
+    fn default() -> Self {
+        Self::new(DuplicatePolicy::KeepAll)
+    }
+}
+
+impl PollutionPolicy {
+    pub fn new(default: DuplicatePolicy) -> Self {
+        Self {
+            default,
+            keys: Vec::new(),
+        }
+    }
+
+    /// Policy of `key`, replaces a previous one
+    pub fn key(mut self, key: impl Into<Cow<'static, str>>, policy: DuplicatePolicy) -> Self {
+        let key = key.into();
+        self.keys.retain(|(k, _)| *k != key);
+        self.keys.push((key, policy));
+        self
+    }
+
+    /// The policy applied to `key`
+    pub fn policy_of(&self, key: &str) -> &DuplicatePolicy {
+        self.keys
+            .iter()
+            .find(|(k, _)| k == key)
+            .map_or(&self.default, |(_, policy)| policy)
+    }
+}
+
+/// # A key present more than once in the input
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Duplicate<'a> {
+    pub key: Cow<'a, str>,
+    /// indexes of its pairs in the input
+    pub indexes: Vec<usize>,
+    /// the policy which resolved it
+    pub policy: DuplicatePolicy,
+}
+
+/// # Result of `resolve`: the data with duplicates resolved, and the report of duplicated keys
+#[derive(Clone, Debug)]
+pub struct Resolved<'a, S = DefaultHashBuilder> {
+    pub data: UrlEncodedData<'a, S>,
+    /// in order of first occurrence
+    pub duplicates: Vec<Duplicate<'a>>,
+}
+
+impl<'a, S> Resolved<'a, S> {
+    /// Was any key duplicated?
+    pub fn is_polluted(&self) -> bool {
+        !self.duplicates.is_empty()
+    }
+}
+
+/// # Build `UrlEncodedData` from decoded pairs, resolving duplicated keys with `policy`
+pub fn resolve<'a, S: BuildHasher + Clone>(
+    prefix: &'a str,
+    original_data_str: &'a str,
+    pairs: impl IntoIterator<Item = Pair<'a>>,
+    policy: &PollutionPolicy,
+    hash_builder: S,
+) -> Result<Resolved<'a, S>, Error> {
+    let pairs: Vec<Pair<'a>> = pairs.into_iter().collect();
+    let mut data = UrlEncodedData::from_pairs_with_hasher(
+        prefix,
+        original_data_str,
+        pairs.iter().cloned(),
+        hash_builder,
+    );
+
+    let mut duplicates = Vec::new();
+    for key in data.original_keys_in_order.iter() {
+        let values = match data.map.get_mut(key.as_ref()) {
+            Some(values) if values.len() > 1 => values,
+            _ => continue,
+        };
+        let policy = policy.policy_of(key);
+        match policy {
+            DuplicatePolicy::Reject => {
+                return Err(Error::Duplicated {
+                    key: key.clone().into_owned(),
+                    count: values.len(),
+                })
+            }
+            DuplicatePolicy::KeepFirst => values.truncate(1),
+            DuplicatePolicy::KeepLast => {
+                values.drain(..values.len() - 1);
+            }
+            DuplicatePolicy::KeepAll => {}
+            DuplicatePolicy::Join(delimiter) => {
+                let joined = values.join(delimiter.as_ref());
+                *values = alloc::vec![Cow::Owned(joined)];
+            }
+        }
+        duplicates.push(Duplicate {
+            key: key.clone(),
+            indexes: pairs
+                .iter()
+                .enumerate()
+                .filter(|(_, (k, _))| k == key)
+                .map(|(index, _)| index)
+                .collect(),
+            policy: policy.clone(),
+        });
+    }
+    Ok(Resolved { data, duplicates })
+}
+
+impl<'a> UrlEncodedData<'a> {
+    /// # Parse, resolving duplicated keys with `policy`, see `pollution::resolve`
+    pub fn parse_str_with_policy(
+        s: &'a str,
+        policy: &PollutionPolicy,
+    ) -> Result<Resolved<'a>, Error> {
+        Self::parse_str_with_policy_and_hasher(s, policy, DefaultHashBuilder::default())
+    }
+}
+
+impl<'a, S: BuildHasher + Clone> UrlEncodedData<'a, S> {
+    /// # Parse with a custom `BuildHasher`, resolving duplicated keys with `policy`
+    pub fn parse_str_with_policy_and_hasher(
+        s: &'a str,
+        policy: &PollutionPolicy,
+        hash_builder: S,
+    ) -> Result<Resolved<'a, S>, Error> {
+        let (prefix, data_str) = split_url_encoded_string(s);
+        let mut resolved = resolve(
+            prefix,
+            data_str,
+            codec::parse(data_str),
+            policy,
+            hash_builder,
+        )?;
+        // nothing dropped or joined: the raw data still holds the pairs, in their sequence
+        resolved.data.raw_is_current = resolved
+            .duplicates
+            .iter()
+            .all(|duplicate| duplicate.policy == DuplicatePolicy::KeepAll);
+        Ok(resolved)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_policies() {
+        let input = "https://abc.com/?a=1&b=x&a=2&c=&a=3&c=z";
+        let resolve_with = |policy: DuplicatePolicy| {
+            let resolved =
+                UrlEncodedData::parse_str_with_policy(input, &PollutionPolicy::new(policy))
+                    .unwrap();
+            assert_eq!(resolved.duplicates.len(), 2);
+            resolved.data.to_string_of_original_order()
+        };
+        assert_eq!(
+            resolve_with(DuplicatePolicy::KeepAll),
+            "https://abc.com/?a=1&a=2&a=3&b=x&c=&c=z"
+        );
+        assert_eq!(
+            resolve_with(DuplicatePolicy::KeepFirst),
+            "https://abc.com/?a=1&b=x&c="
+        );
+        assert_eq!(
+            resolve_with(DuplicatePolicy::KeepLast),
+            "https://abc.com/?a=3&b=x&c=z"
+        );
+        assert_eq!(
+            resolve_with(DuplicatePolicy::Join(",".into())),
+            "https://abc.com/?a=1%2C2%2C3&b=x&c=%2Cz"
+        );
+    }
+
+    #[test]
+    fn test_report() {
+        let policy = PollutionPolicy::new(DuplicatePolicy::KeepLast)
+            .key("a", DuplicatePolicy::Reject)
+            .key("a", DuplicatePolicy::Join("|".into()));
+        let resolved =
+            UrlEncodedData::parse_str_with_policy("b=1&a=x&b=2&a=y&c=3", &policy).unwrap();
+        assert!(resolved.is_polluted());
+        assert_eq!(
+            resolved.duplicates,
+            [
+                Duplicate {
+                    key: "b".into(),
+                    indexes: alloc::vec![0, 2],
+                    policy: DuplicatePolicy::KeepLast,
+                },
+                Duplicate {
+                    key: "a".into(),
+                    indexes: alloc::vec![1, 3],
+                    policy: DuplicatePolicy::Join("|".into()),
+                },
+            ]
+        );
+        assert_eq!(resolved.data.get_first("a").unwrap(), "x|y");
+
+        let clean = UrlEncodedData::parse_str_with_policy("a=1&b=2", &policy).unwrap();
+        assert!(!clean.is_polluted());
+    }
+
+    #[test]
+    fn test_sequence_is_kept_when_nothing_is_dropped() {
+        use crate::canonical::SequenceEq;
+
+        let input = "/a?tag=x&page=2&tag=y";
+        let parsed = UrlEncodedData::parse_str(input);
+        let keep_all = PollutionPolicy::new(DuplicatePolicy::KeepAll);
+        let resolved = UrlEncodedData::parse_str_with_policy(input, &keep_all).unwrap();
+        assert!(resolved.is_polluted());
+        assert_eq!(SequenceEq(&resolved.data), SequenceEq(&parsed));
+
+        let clean = UrlEncodedData::parse_str_with_policy("/a?b=1&a=2", &keep_all).unwrap();
+        assert_eq!(clean.data.original_data_str, "b=1&a=2");
+        assert!(clean.data.raw_is_current);
+
+        let keep_last = PollutionPolicy::new(DuplicatePolicy::KeepLast);
+        let resolved = UrlEncodedData::parse_str_with_policy(input, &keep_last).unwrap();
+        assert!(!resolved.data.raw_is_current);
+        assert_eq!(
+            resolved.data.to_string_of_original_order(),
+            "/a?tag=y&page=2"
+        );
+    }
+}
@@ -0,0 +1,517 @@
+//! # Open-redirect-safe handling of redirect parameters
+//!
+//! Values of `next`, `return_to`, `redirect_uri`, `continue`, ... come from the client.
+//! `RedirectValidator::validate` resolves such a value against the url of the current request (the prefix
+//! of `UrlEncodedData`), and accepts it only if its origin is the current one or an allowed one, and its path
+//! is under an allowed prefix. It returns a sanitized absolute url (dot segments removed, unsafe bytes encoded).
+//! Dot segments are resolved as a server would after decoding `%2F`, `%2e` and their other cases.
+//!
+//! Rejected, also when hidden behind more levels of percent-encoding: scheme-relative `//evil.com`,
+//! backslashes (browsers read `/\evil.com` as `//evil.com`), control characters (`java\tscript:`),
+//! schemes other than http(s) (`javascript:`, `data:`), and userinfo (`https://app.com@evil.com`).
+//!
+//! ```rust
+//! use url_encoded_data::redirect::{Error, RedirectValidator};
+//! use url_encoded_data::UrlEncodedData;
+//!
+//! let validator = RedirectValidator::new()
+//!     .allow_origin("https://accounts.app.com")
+//!     .allow_path_prefix("/app");
+//! let q = UrlEncodedData::parse_str("https://app.com/app/login?next=settings%3Ftab%3D2&continue=%2F%2Fevil.com");
+//! assert_eq!(
+//!     q.redirect_target("next", &validator).unwrap().unwrap(),
+//!     "https://app.com/app/settings?tab=2"
+//! );
+//! assert_eq!(q.redirect_target("continue", &validator), Err(Error::SchemeRelative));
+//! assert_eq!(q.redirect_target("return_to", &validator), Ok(None));
+//! assert_eq!(
+//!     validator.validate(q.prefix(), "https://accounts.app.com/app/../logout"),
+//!     Err(Error::DisallowedPath("/logout".into()))
+//! );
+//! ```
+
+use alloc::borrow::Cow;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::{Display, Formatter};
+use core::hash::BuildHasher;
+
+use crate::codec::{percent_decode_bytes, push_percent_encoded};
+use crate::UrlEncodedData;
+
+/// Extra percent-decoding passes checked for hidden dangerous forms
+const MAX_DECODING_PASSES: usize = 3;
+
+/// # Why a redirect target is rejected
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    Empty,
+    /// the parameter is present more than once
+    Duplicated,
+    /// control character anywhere, or whitespace at the start or the end
+    ControlCharacter,
+    Backslash,
+    /// `//host...`
+    SchemeRelative,
+    /// any scheme but `http` and `https`, lowercased
+    DisallowedScheme(String),
+    /// `user:password@` before the host
+    UserInfo,
+    InvalidHost,
+    /// a normalized origin, eg: `https://evil.com`
+    DisallowedOrigin(String),
+    /// a resolved path
+    DisallowedPath(String),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::Empty => write!(f, "empty redirect target"),
+            Error::Duplicated => write!(f, "redirect parameter present more than once"),
+            Error::ControlCharacter => write!(f, "control character or surrounding whitespace"),
+            Error::Backslash => write!(f, "backslash in redirect target"),
+            Error::SchemeRelative => write!(f, "scheme-relative redirect target"),
+            Error::DisallowedScheme(scheme) => write!(f, "scheme {:?} is not allowed", scheme),
+            Error::UserInfo => write!(f, "userinfo in redirect target"),
+            Error::InvalidHost => write!(f, "invalid host in redirect target"),
+            Error::DisallowedOrigin(origin) => write!(f, "origin {} is not allowed", origin),
+            Error::DisallowedPath(path) => write!(f, "path {} is not allowed", path),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
+/// `(lowercased scheme, rest after ':')` if `s` starts with a scheme
+fn split_scheme(s: &str) -> Option<(String, &str)> {
+    let colon = s.find(':')?;
+    let scheme = &s[..colon];
+    let mut chars = scheme.chars();
+    let valid = chars.next()?.is_ascii_alphabetic()
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+    if valid {
+        Some((scheme.to_ascii_lowercase(), &s[colon + 1..]))
+    } else {
+        None
+    }
+}
+
+/// `(authority, path + query + fragment)` of what follows `//`
+fn split_authority(s: &str) -> (&str, &str) {
+    let end = s.find(['/', '?', '#']).unwrap_or(s.len());
+    s.split_at(end)
+}
+
+/// `scheme://host[:port]`, lowercased, without default port
+fn normalize_origin(scheme: &str, authority: &str) -> Result<String, Error> {
+    if authority.contains('@') {
+        return Err(Error::UserInfo);
+    }
+    let authority = authority.to_ascii_lowercase();
+    let port_start = authority
+        .rfind(':')
+        .filter(|idx| !authority[*idx..].contains(']'));
+    let (host, port) = match port_start {
+        Some(idx) => (&authority[..idx], &authority[idx + 1..]),
+        None => (authority.as_str(), ""),
+    };
+    let valid_host = !host.is_empty()
+        && host
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'[' | b']' | b':'));
+    if !valid_host || !port.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(Error::InvalidHost);
+    }
+    let default_port = match scheme {
+        "https" => "443",
+        _ => "80",
+    };
+    if port.is_empty() || port == default_port {
+        Ok([scheme, "://", host].concat())
+    } else {
+        Ok([scheme, "://", host, ":", port].concat())
+    }
+}
+
+/// `.` or its encoded forms: browsers resolve `%2e` segments like dot segments
+fn is_single_dot(segment: &str) -> bool {
+    segment == "." || segment.eq_ignore_ascii_case("%2e")
+}
+
+/// `..`, `.%2e`, `%2e.` or `%2e%2e`, any case
+fn is_double_dot(segment: &str) -> bool {
+    let segment = segment.to_ascii_lowercase();
+    matches!(segment.as_str(), ".." | ".%2e" | "%2e." | "%2e%2e")
+}
+
+/// `%2F` as `/`, any case: servers and proxies that decode it before routing split the path there
+///
+/// `%5C` needs no such care, a backslash in the decoded target is rejected.
+fn decode_encoded_slashes(path: &str) -> Cow<'_, str> {
+    let bytes = path.as_bytes();
+    let mut out = String::new();
+    let (mut start, mut idx) = (0, 0);
+    while idx + 3 <= bytes.len() {
+        if bytes[idx] == b'%'
+            && bytes[idx + 1] == b'2'
+            && bytes[idx + 2].eq_ignore_ascii_case(&b'f')
+        {
+            out.push_str(&path[start..idx]);
+            out.push('/');
+            idx += 3;
+            start = idx;
+        } else {
+            idx += 1;
+        }
+    }
+    if start == 0 {
+        return Cow::Borrowed(path);
+    }
+    out.push_str(&path[start..]);
+    Cow::Owned(out)
+}
+
+/// RFC 3986 §5.2.4 on a path
+fn remove_dot_segments(path: &str) -> String {
+    let path: Cow<str> = if path.starts_with('/') {
+        Cow::Borrowed(path)
+    } else {
+        Cow::Owned(["/", path].concat())
+    };
+    let mut output: Vec<&str> = Vec::new();
+    let segments: Vec<&str> = path.split('/').collect();
+    for (idx, segment) in segments.iter().enumerate() {
+        let is_last = idx + 1 == segments.len();
+        let double = is_double_dot(segment);
+        if double || is_single_dot(segment) {
+            if double && output.len() > 1 {
+                output.pop();
+            }
+            if is_last {
+                output.push("");
+            }
+        } else {
+            output.push(segment);
+        }
+    }
+    output.join("/")
+}
+
+/// Split `path?query#fragment` into the path and the rest
+fn split_path(s: &str) -> (&str, &str) {
+    let end = s.find(['?', '#']).unwrap_or(s.len());
+    s.split_at(end)
+}
+
+/// Dangerous forms, checked on the value and on its further decoded forms
+fn check_form(s: &str) -> Result<(), Error> {
+    if s.bytes().any(|b| b.is_ascii_control()) {
+        return Err(Error::ControlCharacter);
+    }
+    if s.starts_with(|c: char| c.is_whitespace()) || s.ends_with(|c: char| c.is_whitespace()) {
+        return Err(Error::ControlCharacter);
+    }
+    if s.contains('\\') {
+        return Err(Error::Backslash);
+    }
+    if s.starts_with("//") {
+        return Err(Error::SchemeRelative);
+    }
+    if let Some((scheme, rest)) = split_scheme(s) {
+        if scheme != "http" && scheme != "https" {
+            return Err(Error::DisallowedScheme(scheme));
+        }
+        if !rest.starts_with("//") {
+            return Err(Error::InvalidHost);
+        }
+    }
+    Ok(())
+}
+
+/// # Validator of redirect targets: allowed origins and path prefixes
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RedirectValidator {
+    origins: Vec<String>,
+    path_prefixes: Vec<String>,
+}
+
+impl RedirectValidator {
+    /// Only the current origin, any path
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// # Also allow this origin, eg: `https://accounts.app.com`
+    ///
+    /// Invalid origins are ignored.
+    pub fn allow_origin(mut self, origin: &str) -> Self {
+        let normalized = split_scheme(origin).and_then(|(scheme, rest)| {
+            let rest = rest.strip_prefix("//")?;
+            let (authority, path) = split_authority(rest);
+            if !path.is_empty() && path != "/" {
+                return None;
+            }
+            normalize_origin(&scheme, authority).ok()
+        });
+        self.origins.extend(normalized);
+        self
+    }
+
+    /// # Only allow paths under this prefix (the first allowed prefix restricts, the next ones widen)
+    ///
+    /// `/app` allows `/app` and `/app/...`, not `/apple`.
+    pub fn allow_path_prefix(mut self, prefix: &str) -> Self {
+        let prefix = if prefix.starts_with('/') {
+            prefix.to_string()
+        } else {
+            ["/", prefix].concat()
+        };
+        self.path_prefixes.push(prefix);
+        self
+    }
+
+    fn is_path_allowed(&self, path: &str) -> bool {
+        self.path_prefixes.is_empty()
+            || self.path_prefixes.iter().any(|prefix| {
+                path.strip_prefix(prefix.as_str()).is_some_and(|rest| {
+                    rest.is_empty() || prefix.ends_with('/') || rest.starts_with('/')
+                })
+            })
+    }
+
+    /// # Resolve `target` against `base` (the current url, or a prefix of it), and validate it
+    ///
+    /// `target` is decoded once, as returned by `get_first`. If `base` is not an absolute http(s) url,
+    /// only targets with an allowed origin, or relative ones resolved to a path, are accepted.
+    /// ```rust
+    /// use url_encoded_data::redirect::{Error, RedirectValidator};
+    /// let validator = RedirectValidator::new().allow_origin("https://cdn.app.com:443/");
+    /// let base = "https://app.com:443/a/b?";
+    /// assert_eq!(validator.validate(base, "../c?x#y").unwrap(), "https://app.com/c?x#y");
+    /// assert_eq!(validator.validate(base, "HTTPS://CDN.APP.COM/f").unwrap(), "https://cdn.app.com/f");
+    /// assert_eq!(validator.validate(base, "/a b\"<>").unwrap(), "https://app.com/a%20b%22%3C%3E");
+    /// assert_eq!(validator.validate("/a/b?", "c").unwrap(), "/a/c");
+    /// assert_eq!(validator.validate(base, "/%2F%2Fevil.com"), Err(Error::SchemeRelative));
+    /// assert_eq!(validator.validate(base, "/%5Cevil.com"), Err(Error::Backslash));
+    /// assert_eq!(validator.validate(base, "JavaScript:alert(1)"), Err(Error::DisallowedScheme("javascript".into())));
+    /// assert_eq!(validator.validate(base, "https://app.com@evil.com"), Err(Error::UserInfo));
+    /// assert_eq!(validator.validate(base, "http://app.com/"), Err(Error::DisallowedOrigin("http://app.com".into())));
+    /// ```
+    pub fn validate(&self, base: &str, target: &str) -> Result<String, Error> {
+        if target.is_empty() {
+            return Err(Error::Empty);
+        }
+        check_form(target)?;
+        let mut decoded: Cow<str> = Cow::Borrowed(target);
+        for _ in 0..MAX_DECODING_PASSES {
+            let next = match percent_decode_bytes(decoded.as_bytes(), false) {
+                Some(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+                None => break,
+            };
+            check_form(&next)?;
+            // a path whose decoded form starts with `//`
+            if next.starts_with('/') && split_path(&next).0.starts_with("//") {
+                return Err(Error::SchemeRelative);
+            }
+            decoded = Cow::Owned(next);
+        }
+
+        // the current origin and path
+        let base = &base[..base.find(['?', '#']).unwrap_or(base.len())];
+        let (base_origin, base_path) = match split_scheme(base) {
+            Some((scheme, rest)) if scheme == "http" || scheme == "https" => {
+                match rest.strip_prefix("//") {
+                    Some(rest) => {
+                        let (authority, path) = split_authority(rest);
+                        (normalize_origin(&scheme, authority).ok(), path)
+                    }
+                    None => (None, ""),
+                }
+            }
+            _ => (None, base),
+        };
+
+        let (origin, path_and_rest): (Option<String>, String) = match split_scheme(target) {
+            Some((scheme, rest)) => {
+                // `check_form` accepted only `http(s)://`
+                let (authority, path_and_rest) = split_authority(&rest[2..]);
+                let origin = normalize_origin(&scheme, authority)?;
+                let allowed = base_origin.as_deref() == Some(origin.as_str())
+                    || self.origins.contains(&origin);
+                if !allowed {
+                    return Err(Error::DisallowedOrigin(origin));
+                }
+                let path_and_rest = if path_and_rest.starts_with('/') {
+                    path_and_rest.to_string()
+                } else {
+                    ["/", path_and_rest].concat()
+                };
+                (Some(origin), path_and_rest)
+            }
+            None if target.starts_with('/') => (base_origin, target.to_string()),
+            None => {
+                // relative to the directory of the current path
+                let (base_path, _) = split_path(base_path);
+                let directory = &base_path[..base_path.rfind('/').map_or(0, |idx| idx + 1)];
+                let resolved = if target.starts_with(['?', '#']) {
+                    [base_path, target].concat()
+                } else {
+                    [directory, target].concat()
+                };
+                (base_origin, resolved)
+            }
+        };
+
+        let (path, rest) = split_path(&path_and_rest);
+        let path = remove_dot_segments(&decode_encoded_slashes(path));
+        if path.starts_with("//") {
+            return Err(Error::SchemeRelative);
+        }
+        if !self.is_path_allowed(&path) {
+            return Err(Error::DisallowedPath(path));
+        }
+
+        let mut out = origin.unwrap_or_default();
+        for &byte in path.as_bytes().iter().chain(rest.as_bytes()) {
+            let unsafe_byte = byte <= b' '
+                || byte >= 0x7F
+                || matches!(byte, b'"' | b'<' | b'>' | b'`' | b'{' | b'}' | b'|' | b'^');
+            if unsafe_byte {
+                push_percent_encoded(&mut out, byte);
+            } else {
+                out.push(byte as char);
+            }
+        }
+        Ok(out)
+    }
+}
+
+impl<'a, S: BuildHasher + Clone> UrlEncodedData<'a, S> {
+    /// # The validated redirect target in `key`, see `redirect::RedirectValidator::validate`
+    ///
+    /// `Ok(None)` if `key` is absent, `Err(Error::Duplicated)` if it is present more than once.
+    pub fn redirect_target(
+        &self,
+        key: &str,
+        validator: &RedirectValidator,
+    ) -> Result<Option<String>, Error> {
+        match self.map.get(key).map(|values| values.as_slice()) {
+            None | Some([]) => Ok(None),
+            Some([value]) => validator.validate(self.prefix, value).map(Some),
+            Some(_) => Err(Error::Duplicated),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_remove_dot_segments() {
+        assert_eq!(remove_dot_segments("/a/b/c/./../../g"), "/a/g");
+        assert_eq!(remove_dot_segments("/../../x"), "/x");
+        assert_eq!(remove_dot_segments("/a/.."), "/");
+        assert_eq!(remove_dot_segments("/a/b/."), "/a/b/");
+        assert_eq!(remove_dot_segments("a/../b"), "/b");
+        assert_eq!(remove_dot_segments(""), "/");
+        assert_eq!(remove_dot_segments("/a/%2e%2e/b/%2E/c/.%2E/d"), "/b/d");
+        assert_eq!(remove_dot_segments("/a/%2E."), "/");
+    }
+
+    #[test]
+    fn test_attacks_are_rejected() {
+        let validator = RedirectValidator::new();
+        let base = "https://app.com/login?";
+        for (target, error) in [
+            ("//evil.com", Error::SchemeRelative),
+            ("///evil.com", Error::SchemeRelative),
+            ("/.//evil.com", Error::SchemeRelative),
+            ("%2F%2Fevil.com", Error::SchemeRelative),
+            ("%252F%252Fevil.com", Error::SchemeRelative),
+            ("/\\evil.com", Error::Backslash),
+            ("%5C%5Cevil.com", Error::Backslash),
+            ("%255Cevil.com", Error::Backslash),
+            ("java\tscript:alert(1)", Error::ControlCharacter),
+            (" //evil.com", Error::ControlCharacter),
+            ("/a%0D%0ALocation:x", Error::ControlCharacter),
+            (
+                "javascript%3Aalert(1)",
+                Error::DisallowedScheme("javascript".into()),
+            ),
+            ("data:text/html,x", Error::DisallowedScheme("data".into())),
+            ("https:evil.com", Error::InvalidHost),
+            ("https://evil.com%2F@app.com", Error::UserInfo),
+            (
+                "https://evil.com",
+                Error::DisallowedOrigin("https://evil.com".into()),
+            ),
+            (
+                "https://app.com.evil.com/",
+                Error::DisallowedOrigin("https://app.com.evil.com".into()),
+            ),
+            (
+                "https://app.com:8443/",
+                Error::DisallowedOrigin("https://app.com:8443".into()),
+            ),
+            ("https://app.com%2eevil.com/", Error::InvalidHost),
+            ("", Error::Empty),
+        ] {
+            assert_eq!(validator.validate(base, target), Err(error), "{}", target);
+        }
+    }
+
+    #[test]
+    fn test_path_prefixes() {
+        let validator = RedirectValidator::new()
+            .allow_path_prefix("app")
+            .allow_path_prefix("/docs/");
+        let base = "https://app.com/app/x?";
+        for (target, expected) in [
+            ("/app", Ok("https://app.com/app")),
+            ("y?z=1", Ok("https://app.com/app/y?z=1")),
+            ("?page=2", Ok("https://app.com/app/x?page=2")),
+            ("/docs/", Ok("https://app.com/docs/")),
+            ("/apple", Err(Error::DisallowedPath("/apple".into()))),
+            ("/docs", Err(Error::DisallowedPath("/docs".into()))),
+            ("../admin", Err(Error::DisallowedPath("/admin".into()))),
+            (
+                "/app/%2e%2e/admin",
+                Err(Error::DisallowedPath("/admin".into())),
+            ),
+            (
+                "/app/.%2E/admin",
+                Err(Error::DisallowedPath("/admin".into())),
+            ),
+            ("/app/%2e/y", Ok("https://app.com/app/y")),
+            (
+                "/app/%2e%2e%2fadmin",
+                Err(Error::DisallowedPath("/admin".into())),
+            ),
+            (
+                "/app/..%2Fadmin",
+                Err(Error::DisallowedPath("/admin".into())),
+            ),
+            ("/app/..%5cadmin", Err(Error::Backslash)),
+            ("/app/a%2Fb", Ok("https://app.com/app/a/b")),
+        ] {
+            assert_eq!(
+                validator.validate(base, target),
+                expected.map(String::from),
+                "{}",
+                target
+            );
+        }
+    }
+
+    #[test]
+    fn test_duplicated_parameter() {
+        let q = UrlEncodedData::parse_str("https://app.com/?next=%2Fa&next=%2F%2Fevil.com");
+        assert_eq!(
+            q.redirect_target("next", &RedirectValidator::new()),
+            Err(Error::Duplicated)
+        );
+    }
+}
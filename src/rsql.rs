@@ -0,0 +1,551 @@
+//! # RSQL / FIQL filter expressions: `filter=name==foo;age=gt=30,status=in=(a,b)`
+//!
+//! `parse` reads an expression into a `Node` tree: `;` (and) binds tighter than `,` (or),
+//! parentheses group. A comparison is a selector, an operator (`==`, `!=`, FIQL `=xx=`, or the RSQL
+//! aliases `<` `<=` `>` `>=`, read as `=lt=` `=le=` `=gt=` `=ge=`) and an argument or a list of
+//! them in parentheses. Arguments with reserved characters (`"'();,=!~<>`, whitespace) are quoted
+//! with `"` or `'`; `\` escapes inside quotes. Whitespace between tokens is ignored.
+//!
+//! Errors carry the byte position in the expression; parentheses nest at most `MAX_DEPTH` deep.
+//! `Display` serializes a tree back, with the parentheses and quotes it needs; `Visitor` folds it
+//! into something else, a storage query for instance.
+//!
+//! ```rust
+//! use url_encoded_data::rsql::{Node, Visitor, Comparison};
+//! use url_encoded_data::UrlEncodedData;
+//!
+//! let q = UrlEncodedData::parse_str("/users?filter=name==%22John%20Doe%22;age=gt=30,status=in=(a,b)");
+//! let filter = Node::from_query(&q, "filter").unwrap().unwrap();
+//! assert_eq!(filter.to_string(), "name==\"John Doe\";age=gt=30,status=in=(a,b)");
+//!
+//! struct Sql;
+//! impl Visitor for Sql {
+//!     type Output = String;
+//!     fn and(&mut self, children: Vec<String>) -> String {
+//!         format!("({})", children.join(" AND "))
+//!     }
+//!     fn or(&mut self, children: Vec<String>) -> String {
+//!         format!("({})", children.join(" OR "))
+//!     }
+//!     fn comparison(&mut self, c: &Comparison) -> String {
+//!         let op = match c.operator.as_str() {
+//!             "==" => "=",
+//!             "=gt=" => ">",
+//!             "=in=" => "IN",
+//!             _ => "?",
+//!         };
+//!         format!("{} {} ({})", c.selector, op, c.arguments.join(", "))
+//!     }
+//! }
+//! assert_eq!(
+//!     filter.accept(&mut Sql),
+//!     "((name = (John Doe) AND age > (30)) OR status IN (a, b))"
+//! );
+//!
+//! let err = url_encoded_data::rsql::parse("name==foo;(age=gt=30").unwrap_err();
+//! assert_eq!(err.position, 20);
+//! ```
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::{Display, Formatter, Write};
+use core::hash::BuildHasher;
+
+use crate::UrlEncodedData;
+
+/// # Operators of RSQL and FIQL, as `parse` normalizes them
+pub const OPERATORS: [&str; 8] = ["==", "!=", "=lt=", "=le=", "=gt=", "=ge=", "=in=", "=out="];
+
+/// # Maximum nesting of parentheses, deeper expressions are rejected with `ErrorKind::TooDeep`
+pub const MAX_DEPTH: usize = 64;
+
+/// # Kind of a parsing error
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// the expression ends too early, or is empty
+    UnexpectedEnd,
+    UnexpectedChar(char),
+    /// a quote without its closing quote
+    UnterminatedQuote,
+    /// a comparison without a selector
+    EmptySelector,
+    /// a comparison or a list item without an argument
+    EmptyArgument,
+    /// a malformed operator, or one out of the allowed ones
+    InvalidOperator(String),
+    /// the parameter is present more than once
+    Duplicated,
+    /// parentheses nested deeper than `MAX_DEPTH`
+    TooDeep,
+}
+
+/// # Parsing error at a byte position of the expression
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Error {
+    pub position: usize,
+    pub kind: ErrorKind,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match &self.kind {
+            ErrorKind::UnexpectedEnd => f.write_str("unexpected end of expression")?,
+            ErrorKind::UnexpectedChar(c) => write!(f, "unexpected character {:?}", c)?,
+            ErrorKind::UnterminatedQuote => f.write_str("unterminated quoted argument")?,
+            ErrorKind::EmptySelector => f.write_str("missing selector")?,
+            ErrorKind::EmptyArgument => f.write_str("missing argument")?,
+            ErrorKind::InvalidOperator(op) => write!(f, "invalid operator {:?}", op)?,
+            ErrorKind::Duplicated => return f.write_str("filter parameter present more than once"),
+            ErrorKind::TooDeep => write!(f, "parentheses nested deeper than {}", MAX_DEPTH)?,
+        }
+        write!(f, " at position {}", self.position)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
+/// # `selector operator arguments`
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Comparison {
+    pub selector: String,
+    /// `==`, `!=` or `=xx=`
+    pub operator: String,
+    /// one, or the items of a list
+    pub arguments: Vec<String>,
+}
+
+/// # Node of an expression tree
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Node {
+    /// `;`
+    And(Vec<Node>),
+    /// `,`
+    Or(Vec<Node>),
+    Comparison(Comparison),
+}
+
+/// # Fold of a tree, children first
+pub trait Visitor {
+    type Output;
+    fn and(&mut self, children: Vec<Self::Output>) -> Self::Output;
+    fn or(&mut self, children: Vec<Self::Output>) -> Self::Output;
+    fn comparison(&mut self, comparison: &Comparison) -> Self::Output;
+}
+
+/// # Parse an expression, any `=xx=` operator accepted
+pub fn parse(input: &str) -> Result<Node, Error> {
+    Parser::new(input, None).parse()
+}
+
+/// # Parse an expression, only `operators` (as normalized, see `OPERATORS`) accepted
+/// ```rust
+/// use url_encoded_data::rsql::{parse_with_operators, ErrorKind, OPERATORS};
+/// let err = parse_with_operators("a==1;b=like=x", &OPERATORS).unwrap_err();
+/// assert_eq!(err.position, 6);
+/// assert_eq!(err.kind, ErrorKind::InvalidOperator("=like=".into()));
+/// ```
+pub fn parse_with_operators(input: &str, operators: &[&str]) -> Result<Node, Error> {
+    Parser::new(input, Some(operators)).parse()
+}
+
+impl Node {
+    /// # The expression of the parameter `key`, `None` if absent
+    pub fn from_query<S: BuildHasher + Clone>(
+        data: &UrlEncodedData<'_, S>,
+        key: &str,
+    ) -> Result<Option<Self>, Error> {
+        match data.map.get(key).map(|values| values.as_slice()) {
+            None | Some([]) => Ok(None),
+            Some([value]) => parse(value).map(Some),
+            Some(_) => Err(Error {
+                position: 0,
+                kind: ErrorKind::Duplicated,
+            }),
+        }
+    }
+
+    pub fn accept<V: Visitor + ?Sized>(&self, visitor: &mut V) -> V::Output {
+        match self {
+            Node::And(children) => {
+                let children = children.iter().map(|c| c.accept(visitor)).collect();
+                visitor.and(children)
+            }
+            Node::Or(children) => {
+                let children = children.iter().map(|c| c.accept(visitor)).collect();
+                visitor.or(children)
+            }
+            Node::Comparison(comparison) => visitor.comparison(comparison),
+        }
+    }
+}
+
+fn is_reserved(c: char) -> bool {
+    matches!(
+        c,
+        '"' | '\'' | '(' | ')' | ';' | ',' | '=' | '!' | '~' | '<' | '>'
+    ) || c.is_whitespace()
+}
+
+fn write_argument(f: &mut Formatter<'_>, argument: &str) -> core::fmt::Result {
+    if !argument.is_empty() && !argument.chars().any(is_reserved) {
+        return f.write_str(argument);
+    }
+    f.write_char('"')?;
+    for c in argument.chars() {
+        if c == '"' || c == '\\' {
+            f.write_char('\\')?;
+        }
+        f.write_char(c)?;
+    }
+    f.write_char('"')
+}
+
+impl Display for Comparison {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}{}", self.selector, self.operator)?;
+        if let [argument] = self.arguments.as_slice() {
+            return write_argument(f, argument);
+        }
+        f.write_char('(')?;
+        for (idx, argument) in self.arguments.iter().enumerate() {
+            if idx > 0 {
+                f.write_char(',')?;
+            }
+            write_argument(f, argument)?;
+        }
+        f.write_char(')')
+    }
+}
+
+impl Display for Node {
+    /// Parentheses only around an `or` inside an `and`
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        let (children, separator) = match self {
+            Node::Comparison(comparison) => return comparison.fmt(f),
+            Node::And(children) => (children, ';'),
+            Node::Or(children) => (children, ','),
+        };
+        for (idx, child) in children.iter().enumerate() {
+            if idx > 0 {
+                f.write_char(separator)?;
+            }
+            match (self, child) {
+                (Node::And(_), Node::Or(_)) => write!(f, "({})", child)?,
+                _ => child.fmt(f)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+struct Parser<'i, 'o> {
+    input: &'i str,
+    pos: usize,
+    depth: usize,
+    operators: Option<&'o [&'o str]>,
+}
+
+impl<'i, 'o> Parser<'i, 'o> {
+    fn new(input: &'i str, operators: Option<&'o [&'o str]>) -> Self {
+        Self {
+            input,
+            pos: 0,
+            depth: 0,
+            operators,
+        }
+    }
+
+    fn parse(mut self) -> Result<Node, Error> {
+        let node = self.or()?;
+        self.skip_whitespace();
+        match self.peek() {
+            None => Ok(node),
+            Some(_) => Err(self.unexpected()),
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.input[self.pos..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn eat(&mut self, expected: char) -> bool {
+        self.skip_whitespace();
+        if self.peek() == Some(expected) {
+            self.pos += expected.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.bump();
+        }
+    }
+
+    fn error_at(&self, position: usize, kind: ErrorKind) -> Error {
+        Error { position, kind }
+    }
+
+    fn unexpected(&self) -> Error {
+        let kind = match self.peek() {
+            None => ErrorKind::UnexpectedEnd,
+            Some(c) => ErrorKind::UnexpectedChar(c),
+        };
+        self.error_at(self.pos, kind)
+    }
+
+    /// `and (',' and)*`
+    fn or(&mut self) -> Result<Node, Error> {
+        let mut children = alloc::vec![self.and()?];
+        while self.eat(',') {
+            children.push(self.and()?);
+        }
+        Ok(match children.len() {
+            1 => children.pop().unwrap(),
+            _ => Node::Or(children),
+        })
+    }
+
+    /// `constraint (';' constraint)*`
+    fn and(&mut self) -> Result<Node, Error> {
+        let mut children = alloc::vec![self.constraint()?];
+        while self.eat(';') {
+            children.push(self.constraint()?);
+        }
+        Ok(match children.len() {
+            1 => children.pop().unwrap(),
+            _ => Node::And(children),
+        })
+    }
+
+    /// `'(' or ')' | comparison`
+    fn constraint(&mut self) -> Result<Node, Error> {
+        self.skip_whitespace();
+        let position = self.pos;
+        if self.eat('(') {
+            if self.depth == MAX_DEPTH {
+                return Err(self.error_at(position, ErrorKind::TooDeep));
+            }
+            self.depth += 1;
+            let node = self.or()?;
+            self.depth -= 1;
+            if !self.eat(')') {
+                return Err(self.unexpected());
+            }
+            return Ok(node);
+        }
+        self.comparison().map(Node::Comparison)
+    }
+
+    fn unreserved(&mut self) -> &'i str {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| !is_reserved(c)) {
+            self.bump();
+        }
+        &self.input[start..self.pos]
+    }
+
+    fn comparison(&mut self) -> Result<Comparison, Error> {
+        self.skip_whitespace();
+        let selector = self.unreserved();
+        if selector.is_empty() {
+            return Err(match self.peek() {
+                Some('=' | '!' | '<' | '>') => self.error_at(self.pos, ErrorKind::EmptySelector),
+                _ => self.unexpected(),
+            });
+        }
+        self.skip_whitespace();
+        let operator = self.operator()?;
+        let arguments = if self.eat('(') {
+            let mut arguments = alloc::vec![self.argument()?];
+            while self.eat(',') {
+                arguments.push(self.argument()?);
+            }
+            if !self.eat(')') {
+                return Err(self.unexpected());
+            }
+            arguments
+        } else {
+            alloc::vec![self.argument()?]
+        };
+        Ok(Comparison {
+            selector: selector.to_string(),
+            operator,
+            arguments,
+        })
+    }
+
+    fn operator(&mut self) -> Result<String, Error> {
+        let start = self.pos;
+        let operator = match self.bump() {
+            Some('=') if self.peek() == Some('=') => {
+                self.bump();
+                "==".to_string()
+            }
+            Some('=') => {
+                while self.peek().is_some_and(|c| c.is_ascii_alphabetic()) {
+                    self.bump();
+                }
+                if self.pos == start + 1 || self.peek() != Some('=') {
+                    let invalid = self.input[start..self.pos].to_string();
+                    return Err(self.error_at(start, ErrorKind::InvalidOperator(invalid)));
+                }
+                self.bump();
+                self.input[start..self.pos].to_string()
+            }
+            Some('!') if self.peek() == Some('=') => {
+                self.bump();
+                "!=".to_string()
+            }
+            Some(c @ ('<' | '>')) => {
+                let or_equal = self.peek() == Some('=');
+                if or_equal {
+                    self.bump();
+                }
+                match (c, or_equal) {
+                    ('<', false) => "=lt=",
+                    ('<', true) => "=le=",
+                    (_, false) => "=gt=",
+                    (_, true) => "=ge=",
+                }
+                .to_string()
+            }
+            _ => {
+                self.pos = start;
+                return Err(self.unexpected());
+            }
+        };
+        match self.operators {
+            Some(allowed) if !allowed.contains(&operator.as_str()) => {
+                Err(self.error_at(start, ErrorKind::InvalidOperator(operator)))
+            }
+            _ => Ok(operator),
+        }
+    }
+
+    fn argument(&mut self) -> Result<String, Error> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(quote @ ('"' | '\'')) => {
+                let start = self.pos;
+                self.bump();
+                let mut argument = String::new();
+                loop {
+                    match self.bump() {
+                        None => return Err(self.error_at(start, ErrorKind::UnterminatedQuote)),
+                        Some('\\') => match self.bump() {
+                            None => return Err(self.error_at(start, ErrorKind::UnterminatedQuote)),
+                            Some(c) => argument.push(c),
+                        },
+                        Some(c) if c == quote => return Ok(argument),
+                        Some(c) => argument.push(c),
+                    }
+                }
+            }
+            _ => {
+                let argument = self.unreserved();
+                if argument.is_empty() {
+                    return Err(match self.peek() {
+                        Some(')' | ',' | ';') => self.error_at(self.pos, ErrorKind::EmptyArgument),
+                        _ => self.unexpected(),
+                    });
+                }
+                Ok(argument.to_string())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cmp(selector: &str, operator: &str, arguments: &[&str]) -> Node {
+        Node::Comparison(Comparison {
+            selector: selector.to_string(),
+            operator: operator.to_string(),
+            arguments: arguments.iter().map(|a| a.to_string()).collect(),
+        })
+    }
+
+    #[test]
+    fn test_precedence_and_grouping() {
+        assert_eq!(
+            parse("a==1,b==2;c==3").unwrap(),
+            Node::Or(alloc::vec![
+                cmp("a", "==", &["1"]),
+                Node::And(alloc::vec![cmp("b", "==", &["2"]), cmp("c", "==", &["3"])]),
+            ])
+        );
+        let grouped = parse(" ( a==1 , b!=2 ) ; c<=3 ").unwrap();
+        assert_eq!(
+            grouped,
+            Node::And(alloc::vec![
+                Node::Or(alloc::vec![cmp("a", "==", &["1"]), cmp("b", "!=", &["2"])]),
+                cmp("c", "=le=", &["3"]),
+            ])
+        );
+        assert_eq!(grouped.to_string(), "(a==1,b!=2);c=le=3");
+        assert_eq!(parse("((a>1))").unwrap(), cmp("a", "=gt=", &["1"]));
+    }
+
+    #[test]
+    fn test_quoting_round_trip() {
+        let node =
+            parse(r#"name=in=("a b",'it\'s', "x\"\\y", "", plain);d=ge=2024-01-01T00:00:00Z"#)
+                .unwrap();
+        assert_eq!(
+            node,
+            Node::And(alloc::vec![
+                cmp("name", "=in=", &["a b", "it's", "x\"\\y", "", "plain"]),
+                cmp("d", "=ge=", &["2024-01-01T00:00:00Z"]),
+            ])
+        );
+        let s = node.to_string();
+        assert_eq!(
+            s,
+            r#"name=in=("a b","it's","x\"\\y","",plain);d=ge=2024-01-01T00:00:00Z"#
+        );
+        assert_eq!(parse(&s).unwrap(), node);
+    }
+
+    #[test]
+    fn test_error_positions() {
+        let err = |s: &str| {
+            let e = parse(s).unwrap_err();
+            (e.position, e.kind)
+        };
+        assert_eq!(err(""), (0, ErrorKind::UnexpectedEnd));
+        assert_eq!(err("a==1;"), (5, ErrorKind::UnexpectedEnd));
+        assert_eq!(err("==1"), (0, ErrorKind::EmptySelector));
+        assert_eq!(err("a==1)"), (4, ErrorKind::UnexpectedChar(')')));
+        assert_eq!(err("a b==1"), (2, ErrorKind::UnexpectedChar('b')));
+        assert_eq!(err("a=in=(1,)"), (8, ErrorKind::EmptyArgument));
+        assert_eq!(err("a==\"1"), (3, ErrorKind::UnterminatedQuote));
+        assert_eq!(
+            err("a=g1=1"),
+            (1, ErrorKind::InvalidOperator("=g".to_string()))
+        );
+        assert_eq!(err("a~1"), (1, ErrorKind::UnexpectedChar('~')));
+        assert_eq!(err("a==1;(b==2"), (10, ErrorKind::UnexpectedEnd));
+        let nested = |depth: usize| ["(".repeat(depth), "a==1".into(), ")".repeat(depth)].concat();
+        assert!(parse(&nested(MAX_DEPTH)).is_ok());
+        assert_eq!(
+            err(&["b==2;", &nested(30_000)].concat()),
+            (5 + MAX_DEPTH, ErrorKind::TooDeep)
+        );
+        assert_eq!(
+            parse("a==").unwrap_err().to_string(),
+            "unexpected end of expression at position 3"
+        );
+    }
+}
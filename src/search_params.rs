@@ -0,0 +1,260 @@
+//! # WHATWG `URLSearchParams` compatible API
+//!
+//! `UrlEncodedData` groups values by key, while [`URLSearchParams`](https://url.spec.whatwg.org/#interface-urlsearchparams)
+//! is an ordered list of (name, value) pairs. `UrlSearchParams` keeps that list, so that
+//! `append`, `delete`, `has`, `get`, `get_all`, `set`, `sort` and `size` behave exactly like in a browser.
+//!
+//! ```rust
+//! use url_encoded_data::search_params::UrlSearchParams;
+//! let mut params = UrlSearchParams::parse("?b=1&a=2&b=3");
+//! params.set("b", "4"); // replaces the first "b" in place, removes the others
+//! params.append("c", "x y");
+//! assert_eq!(params.to_string(), "b=4&a=2&c=x+y");
+//!
+//! params.sort();
+//! assert_eq!(params.to_string(), "a=2&b=4&c=x+y");
+//! assert_eq!(params.size(), 3);
+//! ```
+
+use alloc::borrow::Cow;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::{Display, Formatter};
+use core::hash::BuildHasher;
+
+use crate::canonical::original_raw_pairs;
+use crate::{codec, Pair, UrlEncodedData};
+
+/// # An ordered list of (name, value) pairs with `URLSearchParams` semantics
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct UrlSearchParams<'a> {
+    // prefix of the UrlEncodedData it was built from, never part of the serialization
+    prefix: &'a str,
+    list: Vec<Pair<'a>>,
+}
+
+impl<'a> UrlSearchParams<'a> {
+    /// # Parse like `new URLSearchParams(init)`: a single leading `?` is ignored
+    ///
+    /// Unlike `UrlEncodedData::parse_str`, the input is not searched for an url prefix.
+    /// ```rust
+    /// use url_encoded_data::search_params::UrlSearchParams;
+    /// let params = UrlSearchParams::parse("?a=b?c&&d");
+    /// assert_eq!(params.get("a"), Some("b?c"));
+    /// assert!(params.has("d", None));
+    /// ```
+    pub fn parse(init: &'a str) -> Self {
+        let data = init.strip_prefix('?').unwrap_or(init);
+        Self {
+            prefix: "",
+            list: codec::parse(data).collect(),
+        }
+    }
+
+    /// # Number of pairs, like `URLSearchParams.size`
+    pub fn size(&self) -> usize {
+        self.list.len()
+    }
+
+    /// # Append a pair at the end
+    /// ```rust
+    /// use url_encoded_data::search_params::UrlSearchParams;
+    /// let mut params = UrlSearchParams::parse("a=1");
+    /// params.append("a", "2").append("b", String::from("3"));
+    /// assert_eq!(params.get_all("a"), vec!["1", "2"]);
+    /// assert_eq!(params.to_string(), "a=1&a=2&b=3");
+    /// ```
+    pub fn append(
+        &mut self,
+        name: impl Into<Cow<'a, str>>,
+        value: impl Into<Cow<'a, str>>,
+    ) -> &mut Self {
+        self.list.push((name.into(), value.into()));
+        self
+    }
+
+    /// # Delete all pairs of `name`, or only those whose value is `value` if given
+    /// ```rust
+    /// use url_encoded_data::search_params::UrlSearchParams;
+    /// let mut params = UrlSearchParams::parse("a=1&b=2&a=3&a=1");
+    /// params.delete("a", Some("1"));
+    /// assert_eq!(params.to_string(), "b=2&a=3");
+    /// params.delete("a", None);
+    /// assert_eq!(params.to_string(), "b=2");
+    /// ```
+    pub fn delete(&mut self, name: &str, value: Option<&str>) -> &mut Self {
+        self.list
+            .retain(|(k, v)| !(k == name && (value.is_none() || value == Some(v.as_ref()))));
+        self
+    }
+
+    /// # Is there a pair of `name` (and of `value` if given)?
+    /// ```rust
+    /// use url_encoded_data::search_params::UrlSearchParams;
+    /// let params = UrlSearchParams::parse("a=1&b=2");
+    /// assert!(params.has("a", None));
+    /// assert!(params.has("a", Some("1")));
+    /// assert!(!params.has("a", Some("2")));
+    /// assert!(!params.has("c", None));
+    /// ```
+    pub fn has(&self, name: &str, value: Option<&str>) -> bool {
+        self.list
+            .iter()
+            .any(|(k, v)| k == name && (value.is_none() || value == Some(v.as_ref())))
+    }
+
+    /// # Value of the first pair of `name`
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.list
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_ref())
+    }
+
+    /// # Values of all pairs of `name`, in order
+    pub fn get_all(&self, name: &str) -> Vec<&str> {
+        self.list
+            .iter()
+            .filter(|(k, _)| k == name)
+            .map(|(_, v)| v.as_ref())
+            .collect()
+    }
+
+    /// # Set the value of the first pair of `name` in place and remove the other pairs of `name`
+    ///
+    /// Appends the pair if `name` does not exist.
+    /// ```rust
+    /// use url_encoded_data::search_params::UrlSearchParams;
+    /// let mut params = UrlSearchParams::parse("a=1&b=2&a=3");
+    /// params.set("a", "4");
+    /// assert_eq!(params.to_string(), "a=4&b=2");
+    /// params.set("c", "5");
+    /// assert_eq!(params.to_string(), "a=4&b=2&c=5");
+    /// ```
+    pub fn set(
+        &mut self,
+        name: impl Into<Cow<'a, str>>,
+        value: impl Into<Cow<'a, str>>,
+    ) -> &mut Self {
+        let name = name.into();
+        let value = value.into();
+        match self.list.iter().position(|(k, _)| *k == name) {
+            None => self.list.push((name, value)),
+            Some(first) => {
+                self.list[first].1 = value;
+                let mut idx = 0;
+                self.list.retain(|(k, _)| {
+                    let keep = idx <= first || *k != name;
+                    idx += 1;
+                    keep
+                });
+            }
+        }
+        self
+    }
+
+    /// # Stable sort by name, comparing UTF-16 code units like browsers do
+    /// ```rust
+    /// use url_encoded_data::search_params::UrlSearchParams;
+    /// let mut params = UrlSearchParams::parse("z=b&a=b&z=a&a=a");
+    /// params.sort();
+    /// assert_eq!(params.to_string(), "a=b&a=a&z=b&z=a");
+    /// ```
+    pub fn sort(&mut self) -> &mut Self {
+        self.list
+            .sort_by(|(a, _), (b, _)| a.encode_utf16().cmp(b.encode_utf16()));
+        self
+    }
+
+    /// # Iterator of (name, value) pairs, in order
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.list.iter().map(|(k, v)| (k.as_ref(), v.as_ref()))
+    }
+
+    /// # Into `UrlEncodedData`, keeping the prefix it was built from (if any)
+    /// ```rust
+    /// use url_encoded_data::search_params::UrlSearchParams;
+    /// use url_encoded_data::UrlEncodedData;
+    /// let q = UrlEncodedData::parse_str("https://abc.com/?a=1&b=2");
+    /// let mut params = UrlSearchParams::from(&q);
+    /// params.set("a", "3");
+    /// let q = params.into_url_encoded_data();
+    /// assert_eq!(q.to_string_of_original_order(), "https://abc.com/?a=3&b=2");
+    /// ```
+    pub fn into_url_encoded_data(self) -> UrlEncodedData<'a> {
+        UrlEncodedData::from_pairs_with_hasher(self.prefix, "", self.list, Default::default())
+    }
+}
+
+impl<'a, S: BuildHasher + Clone> From<&'a UrlEncodedData<'a, S>> for UrlSearchParams<'a> {
+    /// Pairs are taken in their original sequence. Once pairs were set, pushed or deleted after
+    /// parsing, the sequence is lost: see `UrlEncodedData::as_pairs_of_original_order`
+    fn from(data: &'a UrlEncodedData<'a, S>) -> Self {
+        let list = match original_raw_pairs(data) {
+            Some(raw) => raw
+                .into_iter()
+                .map(|(k, v)| (codec::decode(k), codec::decode(v)))
+                .collect(),
+            None => data
+                .as_pairs_of_original_order()
+                .into_iter()
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect(),
+        };
+        Self {
+            prefix: data.prefix(),
+            list,
+        }
+    }
+}
+
+impl<'a> Display for UrlSearchParams<'a> {
+    /// Serialization of `URLSearchParams.toString()`, without prefix
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        let mut s = String::new();
+        for (k, v) in self.list.iter() {
+            codec::append_pair(&mut s, k, v);
+        }
+        f.write_str(&s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    #[test]
+    fn test_interleaved_order_is_kept() {
+        let mut params = UrlSearchParams::parse("a=1&b=2&a=3");
+        assert_eq!(params.to_string(), "a=1&b=2&a=3");
+        params.append("b", "4");
+        assert_eq!(params.get_all("b"), ["2", "4"]);
+        params.delete("b", Some("2"));
+        assert_eq!(params.to_string(), "a=1&a=3&b=4");
+        params.set("b", "5").set("a", "6");
+        assert_eq!(params.to_string(), "a=6&b=5");
+        assert_eq!(params.iter().collect::<Vec<_>>(), [("a", "6"), ("b", "5")]);
+    }
+
+    #[test]
+    fn test_sort_compares_utf16_code_units() {
+        // U+1F308 is a surrogate pair in UTF-16, which sorts before U+FB03
+        let mut params = UrlSearchParams::parse("\u{FB03}&\u{1F308}");
+        params.sort();
+        let names: Vec<_> = params.iter().map(|(k, _)| k).collect();
+        assert_eq!(names, ["\u{1F308}", "\u{FB03}"]);
+    }
+
+    #[test]
+    fn test_url_encoded_data_round_trip() {
+        let q = UrlEncodedData::parse_str("https://abc.com/?c=1&a=2&c=3");
+        let mut params = UrlSearchParams::from(&q);
+        assert_eq!(params.to_string(), "c=1&a=2&c=3");
+        params.sort();
+        let q = params.into_url_encoded_data();
+        assert_eq!(q.prefix(), "https://abc.com/?");
+        assert_eq!(q.get("c").unwrap(), ["1", "3"]);
+        assert_eq!(q.keys_of_original_order(), ["a", "c"]);
+    }
+}
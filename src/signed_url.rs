@@ -0,0 +1,369 @@
+//! # HMAC-signed, expiring urls
+//!
+//! `UrlEncodedData::sign_url` appends `expires`, `kid` (key id) and `sig` to the data.
+//! `sig` is the HMAC-SHA256 (base64url, no padding) of a canonical encoding of
+//! the prefix and of all the other pairs **in sequence order**, `expires` and `kid` included:
+//! reordering the pairs, changing the prefix or any key or value invalidates the signature.
+//! Keys are rotated by key id, see `Keyring`.
+//!
+//! `UrlEncodedData::parse_signed` verifies in constant time and rejects urls that are tampered,
+//! expired, signed by an unknown key, or where `expires`, `kid` or `sig` are missing or repeated.
+//!
+//! ```rust
+//! use url_encoded_data::signed_url::{Error, Keyring};
+//! use url_encoded_data::UrlEncodedData;
+//!
+//! let keyring = Keyring::new("2024-06", b"new secret").with_retired("2024-01", b"old secret");
+//! let url = UrlEncodedData::parse_str("https://cdn.abc.com/file.zip?user=42")
+//!     .sign_url(&keyring, 1_700_000_600)
+//!     .unwrap();
+//! assert!(url.starts_with("https://cdn.abc.com/file.zip?user=42&expires=1700000600&kid=2024-06&sig="));
+//!
+//! let q = UrlEncodedData::parse_signed(&url, &keyring, 1_700_000_000).unwrap();
+//! assert_eq!(q.get_first("user"), Some("42"));
+//! assert!(!q.exists("sig"));
+//!
+//! let tampered = url.replace("user=42", "user=43");
+//! assert_eq!(UrlEncodedData::parse_signed(&tampered, &keyring, 1_700_000_000), Err(Error::BadSignature));
+//! assert_eq!(
+//!     UrlEncodedData::parse_signed(&url, &keyring, 1_700_000_600),
+//!     Err(Error::Expired { expires: 1_700_000_600, now: 1_700_000_600 })
+//! );
+//! ```
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::{Display, Formatter};
+use core::hash::BuildHasher;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD as BASE64;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::canonical::{ordered_pairs, original_raw_pairs, KeyOrder, ValueOrder};
+use crate::codec::{self, append_rfc3986_encoded};
+use crate::{split_url_encoded_string, Pair, UrlEncodedData};
+
+/// key of the expiry, unix timestamp in seconds
+pub const EXPIRES: &str = "expires";
+/// key of the key id
+pub const KID: &str = "kid";
+/// key of the signature
+pub const SIG: &str = "sig";
+
+/// version of the canonical encoding, part of the signed message
+const CANONICAL_VERSION: &str = "v1";
+
+/// # Errors of signing and verification
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// the data to sign already has `expires`, `kid` or `sig`
+    ReservedKey(&'static str),
+    /// `expires`, `kid` or `sig` is missing
+    Missing(&'static str),
+    /// `expires`, `kid` or `sig` occurs more than once
+    Duplicated(&'static str),
+    /// `expires` is not a unix timestamp
+    InvalidExpires,
+    /// `kid` is not in the keyring
+    UnknownKeyId(String),
+    /// the signature does not match: the url was tampered with
+    BadSignature,
+    /// the signature matches, but `now >= expires`
+    Expired { expires: u64, now: u64 },
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::ReservedKey(key) => write!(f, "key is reserved for signing: {}", key),
+            Error::Missing(key) => write!(f, "missing: {}", key),
+            Error::Duplicated(key) => write!(f, "duplicated: {}", key),
+            Error::InvalidExpires => write!(f, "{} must be a unix timestamp", EXPIRES),
+            Error::UnknownKeyId(kid) => write!(f, "unknown key id: {}", kid),
+            Error::BadSignature => write!(f, "bad signature"),
+            Error::Expired { expires, now } => write!(f, "expired at {}, now: {}", expires, now),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
+/// # Signing keys by key id
+///
+/// The active key signs, all keys verify: retired keys keep the urls they signed valid
+/// until they expire.
+#[derive(Clone)]
+pub struct Keyring<'k> {
+    // the active key is the first one
+    keys: Vec<(&'k str, &'k [u8])>,
+}
+
+impl<'k> Keyring<'k> {
+    /// Keyring whose active key is `secret`
+    pub fn new(kid: &'k str, secret: &'k [u8]) -> Self {
+        Self {
+            keys: alloc::vec![(kid, secret)],
+        }
+    }
+
+    /// Add a key that only verifies
+    pub fn with_retired(mut self, kid: &'k str, secret: &'k [u8]) -> Self {
+        self.keys.push((kid, secret));
+        self
+    }
+
+    /// Id of the active key
+    pub fn active_kid(&self) -> &'k str {
+        self.keys[0].0
+    }
+
+    fn secret(&self, kid: &str) -> Option<&'k [u8]> {
+        self.keys.iter().find(|(k, _)| *k == kid).map(|(_, s)| *s)
+    }
+}
+
+impl<'k> core::fmt::Debug for Keyring<'k> {
+    /// secrets are not printed
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.debug_list()
+            .entries(self.keys.iter().map(|(kid, _)| kid))
+            .finish()
+    }
+}
+
+/// HMAC of the canonical encoding: version, prefix and pairs (without `sig`), RFC 3986 encoded
+fn mac_of<'p>(
+    secret: &[u8],
+    prefix: &str,
+    pairs: impl Iterator<Item = (&'p str, &'p str)>,
+) -> Hmac<Sha256> {
+    let mut message = String::from(CANONICAL_VERSION);
+    message.push('\n');
+    append_rfc3986_encoded(&mut message, prefix, true);
+    message.push('\n');
+    for (idx, (k, v)) in pairs.enumerate() {
+        if idx > 0 {
+            message.push('&');
+        }
+        append_rfc3986_encoded(&mut message, k, true);
+        message.push('=');
+        append_rfc3986_encoded(&mut message, v, true);
+    }
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(message.as_bytes());
+    mac
+}
+
+impl<'a, S: BuildHasher + Clone> UrlEncodedData<'a, S> {
+    /// # Prefix + pairs in their original sequence + `expires`, `kid` and `sig`
+    ///
+    /// `expires` is a unix timestamp in seconds. Once pairs were set, pushed or deleted after
+    /// parsing, their sequence is lost: they are signed and written grouped by key (see
+    /// `as_pairs_of_original_order`), so only the returned url verifies, not the original query
+    /// with the signing parameters appended.
+    /// ```rust
+    /// use url_encoded_data::signed_url::{Error, Keyring};
+    /// use url_encoded_data::UrlEncodedData;
+    /// let keyring = Keyring::new("k1", b"secret");
+    /// let q = UrlEncodedData::parse_str("https://abc.com/?sig=1");
+    /// assert_eq!(q.sign_url(&keyring, 1), Err(Error::ReservedKey("sig")));
+    /// ```
+    pub fn sign_url(&self, keyring: &Keyring<'_>, expires: u64) -> Result<String, Error> {
+        for key in [EXPIRES, KID, SIG].iter() {
+            if self.exists(key) {
+                return Err(Error::ReservedKey(key));
+            }
+        }
+        let expires = expires.to_string();
+        let (kid, secret) = keyring.keys[0];
+        let sequence: Option<Vec<Pair<'a>>> = original_raw_pairs(self).map(|raw| {
+            raw.into_iter()
+                .map(|(k, v)| (codec::decode(k), codec::decode(v)))
+                .collect()
+        });
+        let mut pairs = match &sequence {
+            Some(sequence) => sequence
+                .iter()
+                .map(|(k, v)| (k.as_ref(), v.as_ref()))
+                .collect(),
+            None => ordered_pairs(self, KeyOrder::Original, ValueOrder::Original),
+        };
+        pairs.push((EXPIRES, &expires));
+        pairs.push((KID, kid));
+
+        let sig = BASE64.encode(
+            mac_of(secret, self.prefix, pairs.iter().copied())
+                .finalize()
+                .into_bytes(),
+        );
+
+        let mut data = String::new();
+        for (k, v) in pairs.iter() {
+            codec::append_pair(&mut data, k, v);
+        }
+        codec::append_pair(&mut data, SIG, &sig);
+        Ok(String::from(self.prefix) + &data)
+    }
+}
+
+impl<'a> UrlEncodedData<'a> {
+    /// # Parse a signed url and verify it, `now` is a unix timestamp in seconds
+    ///
+    /// The result has neither `expires`, `kid` nor `sig`.
+    /// ```rust
+    /// use url_encoded_data::signed_url::{Error, Keyring};
+    /// use url_encoded_data::UrlEncodedData;
+    /// let keyring = Keyring::new("k1", b"secret");
+    /// let url = UrlEncodedData::parse_str("https://abc.com/?a=1").sign_url(&keyring, 100).unwrap();
+    /// let duplicated = format!("{}&expires=200", url);
+    /// assert_eq!(UrlEncodedData::parse_signed(&duplicated, &keyring, 50), Err(Error::Duplicated("expires")));
+    /// ```
+    pub fn parse_signed(s: &'a str, keyring: &Keyring<'_>, now: u64) -> Result<Self, Error> {
+        let (prefix, data_str) = split_url_encoded_string(s);
+        let pairs: Vec<Pair<'a>> = codec::parse(data_str).collect();
+
+        let find_one = |key: &'static str| -> Result<&str, Error> {
+            let mut found = pairs.iter().filter(|(k, _)| k == key);
+            let value = found.next().ok_or(Error::Missing(key))?;
+            match found.next() {
+                None => Ok(value.1.as_ref()),
+                Some(_) => Err(Error::Duplicated(key)),
+            }
+        };
+        let expires = find_one(EXPIRES)?;
+        let kid = find_one(KID)?;
+        let sig = find_one(SIG)?;
+
+        let secret = keyring
+            .secret(kid)
+            .ok_or_else(|| Error::UnknownKeyId(kid.to_string()))?;
+        let sig = BASE64.decode(sig).map_err(|_| Error::BadSignature)?;
+        let signed = pairs
+            .iter()
+            .filter(|(k, _)| k != SIG)
+            .map(|(k, v)| (k.as_ref(), v.as_ref()));
+        mac_of(secret, prefix, signed)
+            .verify_slice(&sig)
+            .map_err(|_| Error::BadSignature)?;
+
+        let expires: u64 = expires.parse().map_err(|_| Error::InvalidExpires)?;
+        if now >= expires {
+            return Err(Error::Expired { expires, now });
+        }
+
+        let pairs = pairs
+            .into_iter()
+            .filter(|(k, _)| k != EXPIRES && k != KID && k != SIG);
+        Ok(Self::from_pairs_with_hasher(
+            prefix,
+            "",
+            pairs,
+            Default::default(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_700_000_000;
+
+    fn keyring() -> Keyring<'static> {
+        Keyring::new("k2", b"secret 2").with_retired("k1", b"secret 1")
+    }
+
+    #[test]
+    fn test_key_rotation() {
+        let old = Keyring::new("k1", b"secret 1");
+        let url = UrlEncodedData::parse_str("https://abc.com/f?a=1")
+            .sign_url(&old, NOW + 60)
+            .unwrap();
+        assert!(UrlEncodedData::parse_signed(&url, &keyring(), NOW).is_ok());
+        assert_eq!(
+            UrlEncodedData::parse_signed(&url, &Keyring::new("k2", b"secret 2"), NOW),
+            Err(Error::UnknownKeyId("k1".into()))
+        );
+        // a key id pointing to another key does not verify
+        let forged = url.replace("kid=k1", "kid=k2");
+        assert_eq!(
+            UrlEncodedData::parse_signed(&forged, &keyring(), NOW),
+            Err(Error::BadSignature)
+        );
+    }
+
+    #[test]
+    fn test_reordered_and_tampered() {
+        let url = UrlEncodedData::parse_str("https://abc.com/f?a=1&a=2&b=3")
+            .sign_url(&keyring(), NOW + 60)
+            .unwrap();
+        let q = UrlEncodedData::parse_signed(&url, &keyring(), NOW).unwrap();
+        assert_eq!(
+            q.to_string_of_original_order(),
+            "https://abc.com/f?a=1&a=2&b=3"
+        );
+
+        let tampered = [
+            url.replace("a=1&a=2", "a=2&a=1"),
+            url.replace("a=1&a=2&b=3", "b=3&a=1&a=2"),
+            url.replace("https://abc.com/f", "https://abc.com/g"),
+            url.replace("b=3", "b=3&c=4"),
+            url.replace("&sig=", "&sig=A"),
+            url.replace(
+                &format!("expires={}", NOW + 60),
+                &format!("expires={}", NOW + 6000),
+            ),
+        ];
+        for url in tampered.iter() {
+            assert_eq!(
+                UrlEncodedData::parse_signed(url, &keyring(), NOW),
+                Err(Error::BadSignature),
+                "{}",
+                url
+            );
+        }
+
+        // the encoding may change, the decoded pairs are signed
+        let reencoded = url.replace("a=1", "%61=%31");
+        assert!(UrlEncodedData::parse_signed(&reencoded, &keyring(), NOW).is_ok());
+
+        // interleaved keys keep their sequence: the raw query with the signing parameters verifies
+        let raw = "https://abc.com/f?a=1&b=%7e&a=2";
+        let url = UrlEncodedData::parse_str(raw)
+            .sign_url(&keyring(), NOW + 60)
+            .unwrap();
+        assert!(url.starts_with("https://abc.com/f?a=1&b=%7E&a=2&expires="));
+        let appended = [raw, &url[url.find("&expires=").unwrap()..]].concat();
+        assert!(UrlEncodedData::parse_signed(&appended, &keyring(), NOW).is_ok());
+
+        // once changed after parsing, pairs are grouped by key
+        let mut q = UrlEncodedData::parse_str(raw);
+        q.set_one("c", "3");
+        let url = q.sign_url(&keyring(), NOW + 60).unwrap();
+        assert!(url.starts_with("https://abc.com/f?a=1&a=2&b=%7E&c=3&expires="));
+        assert!(UrlEncodedData::parse_signed(&url, &keyring(), NOW).is_ok());
+    }
+
+    #[test]
+    fn test_missing_and_duplicated() {
+        let url = UrlEncodedData::parse_str("https://abc.com/?a=1")
+            .sign_url(&keyring(), NOW + 60)
+            .unwrap();
+        let sig = url.split("&sig=").nth(1).unwrap();
+        let cases = [
+            ("https://abc.com/?a=1", Error::Missing("expires")),
+            (&*format!("{}&kid=k1", url), Error::Duplicated("kid")),
+            (&*format!("{}&sig={}", url, sig), Error::Duplicated("sig")),
+            (&*url.replace("&kid=k2", ""), Error::Missing("kid")),
+        ];
+        for (url, error) in cases.iter() {
+            assert_eq!(
+                &UrlEncodedData::parse_signed(url, &keyring(), NOW).unwrap_err(),
+                error
+            );
+        }
+    }
+}
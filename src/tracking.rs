@@ -0,0 +1,505 @@
+//! # Tracking-parameter stripping
+//!
+//! A `RuleSet` removes the keys matching its rules: exact keys, key prefixes and key regexes
+//! (all case-insensitive). A rule set may be restricted to a domain and its subdomains (matched against
+//! the host of the prefix) or to urls matching a regex, and may have exception regexes; both regexes
+//! are matched against the whole url, query included.
+//!
+//! `Rules::default()` strips common tracking parameters (`utm_*`, `fbclid`, `gclid`, `mc_eid`, ...),
+//! `Rules::from_clearurls_file` loads [ClearURLs](https://docs.clearurls.xyz/latest/specs/rules/) rule files.
+//!
+//! ```rust
+//! use url_encoded_data::tracking::{Rules, RuleSet};
+//! use url_encoded_data::UrlEncodedData;
+//!
+//! let rules = Rules::default().with(RuleSet::new("shop").domain("shop.com").exact("ref"));
+//! let q = UrlEncodedData::parse_str("https://www.shop.com/item?id=42&utm_source=news&ref=home&fbclid=abc");
+//! let stripped = q.strip_tracking(&rules);
+//! assert_eq!(stripped.to_string(), "https://www.shop.com/item?id=42");
+//!
+//! let removed: Vec<_> = stripped.removed.iter().map(|r| (r.key.as_str(), r.rule.as_str())).collect();
+//! assert_eq!(removed, [("utm_source", "prefix:utm_"), ("ref", "exact:ref"), ("fbclid", "exact:fbclid")]);
+//!
+//! // "ref" is only stripped on shop.com
+//! let q = UrlEncodedData::parse_str("https://abc.com/?ref=home&gclid=1");
+//! assert_eq!(q.strip_tracking(&rules).data.to_string_of_original_order(), "https://abc.com/?ref=home");
+//! ```
+
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+use std::hash::BuildHasher;
+use std::path::Path;
+
+use regex::{Regex, RegexBuilder};
+use serde_json::Value;
+
+use crate::canonical::{ordered_pairs, original_raw_pairs, KeyOrder, ValueOrder};
+use crate::{codec, UrlEncodedData};
+
+/// # Errors of rule loading
+#[derive(Debug)]
+pub enum Error {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    /// valid JSON, but not of the ClearURLs format
+    Format(String),
+    /// a regex does not compile
+    Regex {
+        pattern: String,
+        error: regex::Error,
+    },
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "cannot read rules: {}", e),
+            Error::Json(e) => write!(f, "invalid JSON: {}", e),
+            Error::Format(e) => write!(f, "invalid ClearURLs rules: {}", e),
+            Error::Regex { pattern, error } => write!(f, "invalid regex {:?}: {}", pattern, error),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::Json(e) => Some(e),
+            Error::Format(_) => None,
+            Error::Regex { error, .. } => Some(error),
+        }
+    }
+}
+
+fn case_insensitive_regex(pattern: &str) -> Result<Regex, Error> {
+    RegexBuilder::new(pattern)
+        .case_insensitive(true)
+        .build()
+        .map_err(|error| Error::Regex {
+            pattern: pattern.to_string(),
+            error,
+        })
+}
+
+/// # How a rule matches a key, case-insensitive
+#[derive(Clone, Debug)]
+pub enum KeyRule {
+    Exact(String),
+    Prefix(String),
+    /// matches the whole key
+    Regex(Regex),
+}
+
+impl KeyRule {
+    pub fn matches(&self, key: &str) -> bool {
+        match self {
+            KeyRule::Exact(exact) => key.eq_ignore_ascii_case(exact),
+            KeyRule::Prefix(prefix) => key
+                .get(..prefix.len())
+                .is_some_and(|start| start.eq_ignore_ascii_case(prefix)),
+            KeyRule::Regex(regex) => regex.is_match(key),
+        }
+    }
+}
+
+impl Display for KeyRule {
+    /// `exact:<key>`, `prefix:<prefix>` or `regex:<pattern>`
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KeyRule::Exact(exact) => write!(f, "exact:{}", exact),
+            KeyRule::Prefix(prefix) => write!(f, "prefix:{}", prefix),
+            // strip the anchoring added by `RuleSet::regex`
+            KeyRule::Regex(regex) => {
+                let pattern = regex.as_str();
+                let pattern = pattern
+                    .strip_prefix("^(?:")
+                    .and_then(|p| p.strip_suffix(")$"))
+                    .unwrap_or(pattern);
+                write!(f, "regex:{}", pattern)
+            }
+        }
+    }
+}
+
+/// # Named set of key rules, with the urls it applies to
+#[derive(Clone, Debug)]
+pub struct RuleSet {
+    pub name: String,
+    /// applies to this domain and its subdomains only
+    pub domain: Option<String>,
+    /// applies to urls (prefix and query) matching this regex only
+    pub url_pattern: Option<Regex>,
+    /// does not apply to urls (prefix and query) matching any of these
+    pub exceptions: Vec<Regex>,
+    pub rules: Vec<KeyRule>,
+}
+
+impl RuleSet {
+    /// Rule set applying to every url
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            domain: None,
+            url_pattern: None,
+            exceptions: Vec::new(),
+            rules: Vec::new(),
+        }
+    }
+
+    /// Apply to `domain` and its subdomains only
+    pub fn domain(mut self, domain: impl Into<String>) -> Self {
+        self.domain = Some(domain.into().to_ascii_lowercase());
+        self
+    }
+
+    pub fn exact(mut self, key: impl Into<String>) -> Self {
+        self.rules.push(KeyRule::Exact(key.into()));
+        self
+    }
+
+    pub fn prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.rules.push(KeyRule::Prefix(prefix.into()));
+        self
+    }
+
+    /// Keys fully matching `pattern`
+    pub fn regex(mut self, pattern: &str) -> Result<Self, Error> {
+        let regex = case_insensitive_regex(&format!("^(?:{})$", pattern))?;
+        self.rules.push(KeyRule::Regex(regex));
+        Ok(self)
+    }
+
+    /// Apply to urls matching `pattern` only
+    pub fn url_pattern(mut self, pattern: &str) -> Result<Self, Error> {
+        self.url_pattern = Some(case_insensitive_regex(pattern)?);
+        Ok(self)
+    }
+
+    /// Do not apply to urls matching `pattern`
+    pub fn exception(mut self, pattern: &str) -> Result<Self, Error> {
+        self.exceptions.push(case_insensitive_regex(pattern)?);
+        Ok(self)
+    }
+
+    /// Does the rule set apply to `url` (prefix and query) of host `host`?
+    fn applies_to(&self, url: &str, host: &str) -> bool {
+        let domain_matches = match &self.domain {
+            None => true,
+            Some(domain) => {
+                host == domain
+                    || host
+                        .strip_suffix(domain.as_str())
+                        .is_some_and(|sub| sub.ends_with('.'))
+            }
+        };
+        let url_matches = match &self.url_pattern {
+            None => true,
+            Some(pattern) => pattern.is_match(url),
+        };
+        domain_matches && url_matches && !self.exceptions.iter().any(|e| e.is_match(url))
+    }
+}
+
+/// Lowercased host of an url prefix (eg: `https://user@WWW.abc.com:8080/path?` -> `www.abc.com`)
+fn host_of(prefix: &str) -> String {
+    let rest = match prefix.split_once("://") {
+        Some((_, rest)) => rest,
+        None => return String::new(),
+    };
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    let host = authority
+        .rsplit_once('@')
+        .map_or(authority, |(_, host)| host);
+    let host = match host.rfind(':') {
+        Some(idx) if !host[idx..].contains(']') => &host[..idx],
+        _ => host,
+    };
+    host.to_ascii_lowercase()
+}
+
+/// # Rule sets, applied together
+#[derive(Clone, Debug)]
+pub struct Rules {
+    pub sets: Vec<RuleSet>,
+}
+
+impl Default for Rules {
+    /// Common tracking parameters, for every url
+    fn default() -> Self {
+        let mut common = RuleSet::new("common").prefix("utm_");
+        for key in [
+            "fbclid",
+            "gclid",
+            "gclsrc",
+            "dclid",
+            "gbraid",
+            "wbraid",
+            "msclkid",
+            "mc_eid",
+            "mc_cid",
+            "_ga",
+            "_gl",
+            "igshid",
+            "yclid",
+            "twclid",
+            "ttclid",
+            "li_fat_id",
+            "_hsenc",
+            "_hsmi",
+            "mkt_tok",
+            "oly_anon_id",
+            "oly_enc_id",
+            "vero_id",
+            "wickedid",
+        ]
+        .iter()
+        {
+            common = common.exact(*key);
+        }
+        Self { sets: vec![common] }
+    }
+}
+
+impl Rules {
+    /// No rule set
+    pub fn empty() -> Self {
+        Self { sets: Vec::new() }
+    }
+
+    pub fn with(mut self, set: RuleSet) -> Self {
+        self.sets.push(set);
+        self
+    }
+
+    /// # Load rules of the ClearURLs format
+    ///
+    /// Each provider becomes a rule set: `urlPattern`, `exceptions`, `rules` and `referralMarketing` are used.
+    /// `rawRules`, `redirections` and `completeProvider` act on whole urls, not on pairs, they are ignored.
+    /// ```rust
+    /// use url_encoded_data::tracking::Rules;
+    /// use url_encoded_data::UrlEncodedData;
+    /// let rules = Rules::from_clearurls_json(r#"{"providers": {"example": {
+    ///     "urlPattern": "^https?://(?:[a-z0-9-]+\\.)*?example\\.com",
+    ///     "rules": ["ref_?[a-z]*", "tag"],
+    ///     "exceptions": ["^https?://example\\.com/keep"]
+    /// }}}"#).unwrap();
+    /// let q = UrlEncodedData::parse_str("https://www.example.com/?ref_src=x&TAG=1&id=2");
+    /// assert_eq!(q.strip_tracking(&rules).data.to_string_of_original_order(), "https://www.example.com/?id=2");
+    /// let q = UrlEncodedData::parse_str("https://example.com/keep?tag=1");
+    /// assert!(q.strip_tracking(&rules).removed.is_empty());
+    /// ```
+    pub fn from_clearurls_json(json: &str) -> Result<Self, Error> {
+        let root: Value = serde_json::from_str(json).map_err(Error::Json)?;
+        let providers = root
+            .get("providers")
+            .and_then(Value::as_object)
+            .ok_or_else(|| Error::Format("no \"providers\" object".to_string()))?;
+
+        let mut sets = Vec::with_capacity(providers.len());
+        for (name, provider) in providers.iter() {
+            let strings = |field: &str| -> Result<Vec<&str>, Error> {
+                match provider.get(field) {
+                    None => Ok(Vec::new()),
+                    Some(Value::Array(values)) => values
+                        .iter()
+                        .map(|v| {
+                            v.as_str().ok_or_else(|| {
+                                Error::Format(format!("{}.{}: expected strings", name, field))
+                            })
+                        })
+                        .collect(),
+                    Some(_) => Err(Error::Format(format!(
+                        "{}.{}: expected an array",
+                        name, field
+                    ))),
+                }
+            };
+
+            let mut set = RuleSet::new(name.as_str());
+            if let Some(pattern) = provider.get("urlPattern").and_then(Value::as_str) {
+                set = set.url_pattern(pattern)?;
+            }
+            for pattern in strings("exceptions")? {
+                set = set.exception(pattern)?;
+            }
+            for pattern in strings("rules")?
+                .into_iter()
+                .chain(strings("referralMarketing")?)
+            {
+                set = set.regex(pattern)?;
+            }
+            sets.push(set);
+        }
+        Ok(Self { sets })
+    }
+
+    /// # Load a ClearURLs rule file, see `from_clearurls_json`
+    pub fn from_clearurls_file(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let json = std::fs::read_to_string(path).map_err(Error::Io)?;
+        Self::from_clearurls_json(&json)
+    }
+}
+
+/// # A pair removed by `strip_tracking`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Removed {
+    pub key: String,
+    pub value: String,
+    /// name of the rule set
+    pub rule_set: String,
+    /// the matching rule, see `KeyRule`'s `Display`
+    pub rule: String,
+}
+
+/// # Result of `strip_tracking`
+///
+/// `Display` writes the prefix and the kept pairs in their original sequence, interleaved keys
+/// included; `data` groups the values of a key like any `UrlEncodedData` changed after parsing.
+#[derive(Clone, Debug)]
+pub struct Stripped<'a, S> {
+    pub data: UrlEncodedData<'a, S>,
+    /// removed pairs, in original order
+    pub removed: Vec<Removed>,
+    /// kept pairs, in original order
+    kept: Vec<(Cow<'a, str>, Cow<'a, str>)>,
+}
+
+impl<'a, S: BuildHasher + Clone> UrlEncodedData<'a, S> {
+    /// # Remove the keys matched by the rules which apply to the prefix
+    ///
+    /// The pairs are taken in their original sequence while it is known, see
+    /// `UrlEncodedData::as_pairs_of_original_order` otherwise.
+    pub fn strip_tracking(&self, rules: &Rules) -> Stripped<'a, S> {
+        let host = host_of(self.prefix);
+        // url patterns and exceptions may test the query, as in ClearURLs
+        let url = match original_raw_pairs(self) {
+            Some(_) => [self.prefix, self.original_data_str].concat(),
+            None => self.to_string_of_original_order(),
+        };
+        let applying: Vec<&RuleSet> = rules
+            .sets
+            .iter()
+            .filter(|set| set.applies_to(&url, &host))
+            .collect();
+
+        let pairs: Vec<(Cow<'a, str>, Cow<'a, str>)> = match original_raw_pairs(self) {
+            Some(raw) => raw
+                .map(|(k, v)| (codec::decode(k), codec::decode(v)))
+                .collect(),
+            None => ordered_pairs(self, KeyOrder::Original, ValueOrder::Original)
+                .into_iter()
+                .map(|(k, v)| (Cow::Owned(k.to_string()), Cow::Owned(v.to_string())))
+                .collect(),
+        };
+        let mut removed = Vec::new();
+        let mut removed_keys: HashSet<String> = HashSet::new();
+        let mut kept = Vec::with_capacity(pairs.len());
+        for (key, value) in pairs {
+            let matched = applying.iter().find_map(|set| {
+                set.rules
+                    .iter()
+                    .find(|rule| rule.matches(&key))
+                    .map(|rule| (set, rule))
+            });
+            match matched {
+                Some((set, rule)) => {
+                    removed.push(Removed {
+                        key: key.to_string(),
+                        value: value.into_owned(),
+                        rule_set: set.name.clone(),
+                        rule: rule.to_string(),
+                    });
+                    removed_keys.insert(key.into_owned());
+                }
+                None => kept.push((key, value)),
+            }
+        }
+
+        let mut data = self.clone();
+        data.map.retain(|k, _| !removed_keys.contains(k.as_ref()));
+        data.original_keys_in_order
+            .retain(|k| !removed_keys.contains(k.as_ref()));
+        if !removed.is_empty() {
+            // the raw data is not the stripped data anymore
+            data.original_data_str = "";
+            data.raw_is_current = false;
+        }
+        Stripped {
+            data,
+            removed,
+            kept,
+        }
+    }
+}
+
+impl<'a, S> Display for Stripped<'a, S> {
+    /// prefix + kept pairs, in original sequence
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut data = String::new();
+        for (k, v) in self.kept.iter() {
+            codec::append_pair(&mut data, k, v);
+        }
+        write!(f, "{}{}", self.data.prefix, data)
+    }
+}
+
+impl<'a, S> Stripped<'a, S> {
+    /// Was anything removed?
+    pub fn is_changed(&self) -> bool {
+        !self.removed.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_host_of() {
+        assert_eq!(
+            host_of("https://user:pw@WWW.Abc.com:8080/p?"),
+            "www.abc.com"
+        );
+        assert_eq!(host_of("http://[::1]:80/?"), "[::1]");
+        assert_eq!(host_of("http://abc.com?"), "abc.com");
+        assert_eq!(host_of("a=1"), "");
+    }
+
+    #[test]
+    fn test_domain_matching() {
+        let set = RuleSet::new("x").domain("Abc.com");
+        assert!(set.applies_to("", "abc.com"));
+        assert!(set.applies_to("", "www.abc.com"));
+        assert!(!set.applies_to("", "notabc.com"));
+        assert!(!set.applies_to("", "abc.com.evil.org"));
+    }
+
+    #[test]
+    fn test_unchanged_data_is_kept() {
+        let q = UrlEncodedData::parse_str("https://abc.com/?b=1&a=2");
+        let stripped = q.strip_tracking(&Rules::default());
+        assert!(!stripped.is_changed());
+        assert_eq!(stripped.data.original_data_str, "b=1&a=2");
+        assert_eq!(
+            stripped.data.to_string_of_original_order(),
+            "https://abc.com/?b=1&a=2"
+        );
+    }
+
+    #[test]
+    fn test_interleaved_keys_keep_their_sequence() {
+        let q = UrlEncodedData::parse_str("https://abc.com/?a=1&utm_source=x&b=%7e&a=2&gclid=y");
+        let stripped = q.strip_tracking(&Rules::default());
+        assert_eq!(stripped.to_string(), "https://abc.com/?a=1&b=%7E&a=2");
+        let removed: Vec<_> = stripped.removed.iter().map(|r| r.key.as_str()).collect();
+        assert_eq!(removed, ["utm_source", "gclid"]);
+
+        // changed after parsing: grouped by key in original order
+        let mut q = UrlEncodedData::parse_str("https://abc.com/?a=1&utm_source=x&b=2&a=3");
+        q.push("c", "4");
+        let stripped = q.strip_tracking(&Rules::default());
+        assert_eq!(stripped.to_string(), "https://abc.com/?a=1&a=3&b=2&c=4");
+    }
+}
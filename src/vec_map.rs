@@ -0,0 +1,203 @@
+//! # Ordered-vector-backed map
+//!
+//! Stands in for `HashMap` in the `no_std` build of `UrlEncodedData`.
+//! Keys are kept in insertion order and looked up linearly, which is fine for the few keys of a query string.
+//! The hasher parameter `S` is only kept so the signatures match the `std` build.
+
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::marker::PhantomData;
+
+/// # A map of keys to values in insertion order
+/// ```rust
+/// use url_encoded_data::vec_map::VecMap;
+/// use url_encoded_data::hasher::FnvBuildHasher;
+/// let mut m: VecMap<&str, i32, FnvBuildHasher> = VecMap::default();
+/// m.insert("b", 1);
+/// m.insert("a", 2);
+/// *m.entry("b").or_default() += 10;
+/// assert_eq!(m.get("b"), Some(&11));
+/// assert_eq!(m.keys().copied().collect::<Vec<_>>(), vec!["b", "a"]);
+/// ```
+#[derive(Clone, Debug)]
+pub struct VecMap<K, V, S> {
+    entries: Vec<(K, V)>,
+    hash_builder: S,
+}
+
+impl<K, V, S: Default> Default for VecMap<K, V, S> {
+    fn default() -> Self {
+        Self::with_hasher(S::default())
+    }
+}
+
+impl<K, V, S> VecMap<K, V, S> {
+    pub fn with_hasher(hash_builder: S) -> Self {
+        Self {
+            entries: Vec::new(),
+            hash_builder,
+        }
+    }
+
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.entries.iter().map(|(k, v)| (k, v))
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.entries.iter().map(|(k, _)| k)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.entries.iter().map(|(_, v)| v)
+    }
+}
+
+impl<K: Eq, V, S> VecMap<K, V, S> {
+    fn position<Q>(&self, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+    {
+        self.entries.iter().position(|(k, _)| k.borrow() == key)
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+    {
+        self.position(key).map(|idx| &self.entries[idx].1)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+    {
+        let idx = self.position(key)?;
+        Some(&mut self.entries[idx].1)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+    {
+        self.position(key).is_some()
+    }
+
+    /// Insert the value, the key keeps its position if it exists already
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        match self.position(&key) {
+            Some(idx) => Some(core::mem::replace(&mut self.entries[idx].1, value)),
+            None => {
+                self.entries.push((key, value));
+                None
+            }
+        }
+    }
+
+    /// Remove the key, the other keys keep their order
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+    {
+        let idx = self.position(key)?;
+        Some(self.entries.remove(idx).1)
+    }
+
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V> {
+        match self.position(&key) {
+            Some(idx) => Entry::Occupied(&mut self.entries[idx].1, PhantomData),
+            None => Entry::Vacant(&mut self.entries, key),
+        }
+    }
+}
+
+/// A view into a single entry of `VecMap`
+pub enum Entry<'m, K, V> {
+    Occupied(&'m mut V, PhantomData<K>),
+    Vacant(&'m mut Vec<(K, V)>, K),
+}
+
+impl<'m, K, V> Entry<'m, K, V> {
+    pub fn or_insert(self, default: V) -> &'m mut V {
+        match self {
+            Entry::Occupied(v, _) => v,
+            Entry::Vacant(entries, key) => {
+                entries.push((key, default));
+                let last = entries.len() - 1;
+                &mut entries[last].1
+            }
+        }
+    }
+
+    pub fn or_default(self) -> &'m mut V
+    where
+        V: Default,
+    {
+        self.or_insert(V::default())
+    }
+}
+
+impl<'m, K, V, S> IntoIterator for &'m VecMap<K, V, S> {
+    type Item = (&'m K, &'m V);
+    type IntoIter =
+        core::iter::Map<core::slice::Iter<'m, (K, V)>, fn(&'m (K, V)) -> (&'m K, &'m V)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.iter().map(|(k, v)| (k, v))
+    }
+}
+
+impl<K, V, S> IntoIterator for VecMap<K, V, S> {
+    type Item = (K, V);
+    type IntoIter = alloc::vec::IntoIter<(K, V)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hasher::FnvBuildHasher;
+
+    #[test]
+    fn test_insertion_order_is_kept() {
+        let mut m: VecMap<&str, i32, FnvBuildHasher> = VecMap::default();
+        m.insert("c", 1);
+        m.insert("a", 2);
+        m.insert("b", 3);
+        assert_eq!(m.insert("a", 20), Some(2));
+        assert_eq!(m.keys().copied().collect::<Vec<_>>(), ["c", "a", "b"]);
+        assert_eq!(m.remove("a"), Some(20));
+        assert_eq!(m.remove("a"), None);
+        m.entry("d").or_insert(4);
+        *m.entry("c").or_insert(100) += 1;
+        let pairs: Vec<_> = (&m).into_iter().map(|(k, v)| (*k, *v)).collect();
+        assert_eq!(pairs, [("c", 2), ("b", 3), ("d", 4)]);
+        assert!(m.contains_key("b"));
+        assert_eq!(m.len(), 3);
+        m.clear();
+        assert!(m.is_empty());
+    }
+}
@@ -0,0 +1,256 @@
+//! # Signature verification of form-encoded webhooks (Twilio style)
+//!
+//! The signature (eg: the `X-Twilio-Signature` header) is the base64 HMAC-SHA1, keyed by the auth token, of
+//! the full request url followed by the POST form parameters: keys sorted case-sensitively (byte order),
+//! then for each key its values, sorted, each one appended as `key` + `value` without separator.
+//!
+//! Senders disagree on repeated keys and on the port: the values of a repeated key are tried
+//! sorted and deduplicated, sorted only, and in received order (older SDKs do not sort them);
+//! the url is tried as received, with its default port added, and with its port removed.
+//!
+//! ```rust
+//! use url_encoded_data::webhook::WebhookVerifier;
+//! use url_encoded_data::UrlEncodedData;
+//!
+//! let url = "https://mycompany.com/myapp.php?foo=1&bar=2";
+//! let body = UrlEncodedData::parse_str(
+//!     "CallSid=CA1234567890ABCDE&Caller=%2B14158675309&Digits=1234&From=%2B14158675309&To=%2B18005551212",
+//! );
+//! let verifier = WebhookVerifier::new("12345");
+//! assert_eq!(verifier.signature(url, &body), "RSOYDt4T1cUTdK1PDd93/VVr8B8=");
+//! assert!(verifier.verify(url, &body, "RSOYDt4T1cUTdK1PDd93/VVr8B8=").is_ok());
+//! ```
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::{Display, Formatter};
+use core::hash::BuildHasher;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+
+use crate::UrlEncodedData;
+
+/// # Errors of verification
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// the signature is not base64
+    MalformedSignature,
+    /// the signature does not match any accepted form of the request
+    BadSignature,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::MalformedSignature => write!(f, "signature is not base64"),
+            Error::BadSignature => write!(f, "bad signature"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
+/// Url as received, with the default port added (if it has no port) and without port (if it has one)
+fn url_variants(url: &str) -> Vec<String> {
+    let mut variants = alloc::vec![String::from(url)];
+    let (scheme, rest) = match url.split_once("://") {
+        Some(split) => split,
+        None => return variants,
+    };
+    let authority_end = rest.find(['/', '?', '#']).unwrap_or(rest.len());
+    let (authority, tail) = rest.split_at(authority_end);
+    // the port is after the host, which is after the userinfo, and never inside an ipv6 literal
+    let host_start = authority.rfind('@').map_or(0, |idx| idx + 1);
+    let port_start = authority[host_start..]
+        .rfind(':')
+        .map(|idx| host_start + idx)
+        .filter(|idx| !authority[*idx..].contains(']'));
+    match port_start {
+        Some(idx) => variants.push([scheme, "://", &authority[..idx], tail].concat()),
+        None => {
+            let default_port = match scheme.to_ascii_lowercase().as_str() {
+                "https" => ":443",
+                "http" => ":80",
+                _ => return variants,
+            };
+            variants.push([scheme, "://", authority, default_port, tail].concat());
+        }
+    }
+    variants
+}
+
+/// Order of the values of a repeated key in the signed message
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ValueOrder {
+    SortedDeduplicated,
+    Sorted,
+    /// as received, not deduplicated
+    Received,
+}
+
+/// # Verifier of webhook signatures, keyed by the auth token
+#[derive(Clone, Copy)]
+pub struct WebhookVerifier<'t> {
+    auth_token: &'t str,
+}
+
+impl<'t> core::fmt::Debug for WebhookVerifier<'t> {
+    /// the auth token is not printed
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.write_str("WebhookVerifier")
+    }
+}
+
+impl<'t> WebhookVerifier<'t> {
+    pub fn new(auth_token: &'t str) -> Self {
+        Self { auth_token }
+    }
+
+    /// `order`: of the values of a repeated key
+    fn mac<S: BuildHasher + Clone>(
+        &self,
+        url: &str,
+        body: &UrlEncodedData<'_, S>,
+        order: ValueOrder,
+    ) -> Hmac<Sha1> {
+        let mut mac = Hmac::<Sha1>::new_from_slice(self.auth_token.as_bytes())
+            .expect("HMAC accepts keys of any length");
+        mac.update(url.as_bytes());
+        let mut entries: Vec<_> = body.map.iter().collect();
+        entries.sort_unstable_by_key(|&(key, _)| key);
+        for (key, values) in entries {
+            let mut values: Vec<&str> = values.iter().map(|v| v.as_ref()).collect();
+            if order != ValueOrder::Received {
+                values.sort_unstable();
+            }
+            if order == ValueOrder::SortedDeduplicated {
+                values.dedup();
+            }
+            for value in values {
+                mac.update(key.as_bytes());
+                mac.update(value.as_bytes());
+            }
+        }
+        mac
+    }
+
+    /// # Base64 signature of the request, repeated identical pairs counted once
+    pub fn signature<S: BuildHasher + Clone>(
+        &self,
+        url: &str,
+        body: &UrlEncodedData<'_, S>,
+    ) -> String {
+        BASE64.encode(
+            self.mac(url, body, ValueOrder::SortedDeduplicated)
+                .finalize()
+                .into_bytes(),
+        )
+    }
+
+    /// # Verify the signature header of a request, in constant time
+    ///
+    /// `url` is the full url the request was sent to, query included.
+    /// ```rust
+    /// use url_encoded_data::webhook::{Error, WebhookVerifier};
+    /// use url_encoded_data::UrlEncodedData;
+    /// let verifier = WebhookVerifier::new("token");
+    /// let body = UrlEncodedData::parse_str("Body=hi");
+    /// let signature = verifier.signature("https://abc.com:443/sms", &body);
+    /// assert!(verifier.verify("https://abc.com/sms", &body, &signature).is_ok());
+    /// assert_eq!(verifier.verify("https://abc.com/mms", &body, &signature), Err(Error::BadSignature));
+    /// assert_eq!(verifier.verify("https://abc.com/sms", &body, "?"), Err(Error::MalformedSignature));
+    /// ```
+    pub fn verify<S: BuildHasher + Clone>(
+        &self,
+        url: &str,
+        body: &UrlEncodedData<'_, S>,
+        signature: &str,
+    ) -> Result<(), Error> {
+        let signature = BASE64
+            .decode(signature.trim())
+            .map_err(|_| Error::MalformedSignature)?;
+        // only the orders giving another message than the sorted and deduplicated one
+        let mut orders = alloc::vec![ValueOrder::SortedDeduplicated];
+        let has_duplicated_pairs = body.map.values().any(|values| {
+            let mut values: Vec<&str> = values.iter().map(|v| v.as_ref()).collect();
+            values.sort_unstable();
+            values.windows(2).any(|w| w[0] == w[1])
+        });
+        if has_duplicated_pairs {
+            orders.push(ValueOrder::Sorted);
+        }
+        let has_unsorted_values = body
+            .map
+            .values()
+            .any(|values| values.windows(2).any(|w| w[0] > w[1]));
+        if has_unsorted_values {
+            orders.push(ValueOrder::Received);
+        }
+        // every candidate is checked, whatever matched before
+        let mut valid = false;
+        for url in url_variants(url).iter() {
+            for order in orders.iter() {
+                valid |= self.mac(url, body, *order).verify_slice(&signature).is_ok();
+            }
+        }
+        if valid {
+            Ok(())
+        } else {
+            Err(Error::BadSignature)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_url_variants() {
+        assert_eq!(
+            url_variants("https://abc.com/a?b=1"),
+            ["https://abc.com/a?b=1", "https://abc.com:443/a?b=1"]
+        );
+        assert_eq!(
+            url_variants("http://u:p@abc.com:8080?x=1"),
+            ["http://u:p@abc.com:8080?x=1", "http://u:p@abc.com?x=1"]
+        );
+        assert_eq!(
+            url_variants("https://[::1]/a"),
+            ["https://[::1]/a", "https://[::1]:443/a"]
+        );
+        assert_eq!(
+            url_variants("https://[::1]:8443/a"),
+            ["https://[::1]:8443/a", "https://[::1]/a"]
+        );
+        assert_eq!(url_variants("/relative"), ["/relative"]);
+    }
+
+    #[test]
+    fn test_duplicated_pairs_in_both_forms() {
+        let verifier = WebhookVerifier::new("token");
+        let body = UrlEncodedData::parse_str("a=1&a=1&a=0");
+        let url = "https://abc.com/";
+        let deduplicated = verifier.signature(url, &body);
+        let counted_twice = BASE64.encode(
+            verifier
+                .mac(url, &body, ValueOrder::Sorted)
+                .finalize()
+                .into_bytes(),
+        );
+        assert_ne!(deduplicated, counted_twice);
+        assert!(verifier.verify(url, &body, &deduplicated).is_ok());
+        assert!(verifier.verify(url, &body, &counted_twice).is_ok());
+        let received = BASE64.encode(
+            verifier
+                .mac(url, &body, ValueOrder::Received)
+                .finalize()
+                .into_bytes(),
+        );
+        assert!(verifier.verify(url, &body, &received).is_ok());
+    }
+}
@@ -0,0 +1,44 @@
+//! AWS Signature Version 4 test suite vectors (query string cases, `tests/fixtures/aws-sig-v4`)
+#![cfg(feature = "sigv4")]
+
+use serde_json::Value;
+use url_encoded_data::sigv4::{
+    canonical_request, signature, string_to_sign, Scope, EMPTY_PAYLOAD_SHA256,
+};
+use url_encoded_data::UrlEncodedData;
+
+const SECRET_ACCESS_KEY: &str = "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY";
+const AMZ_DATE: &str = "20150830T123600Z";
+
+#[test]
+fn test_query_vectors() {
+    let cases: Vec<Value> =
+        serde_json::from_str(include_str!("fixtures/aws-sig-v4/query-vectors.json")).unwrap();
+    let cases: Vec<&Value> = cases.iter().filter(|case| case.is_object()).collect();
+    assert!(!cases.is_empty());
+
+    let scope = Scope::new(AMZ_DATE, "us-east-1", "service").unwrap();
+    let signing_key = scope.signing_key(SECRET_ACCESS_KEY);
+    for case in cases {
+        let name = case["name"].as_str().unwrap();
+        let query = case["query"].as_str().unwrap();
+        let q = UrlEncodedData::parse_str(query);
+        let query = q.sigv4_canonical_query();
+        assert_eq!(query, case["canonical_query"].as_str().unwrap(), "{}", name);
+
+        let request = canonical_request(
+            "GET",
+            "/",
+            &query,
+            &[("Host", "example.amazonaws.com"), ("X-Amz-Date", AMZ_DATE)],
+            EMPTY_PAYLOAD_SHA256,
+        );
+        let to_sign = string_to_sign(AMZ_DATE, &scope, &request);
+        assert_eq!(
+            signature(&signing_key, &to_sign),
+            case["signature"].as_str().unwrap(),
+            "{}",
+            name
+        );
+    }
+}
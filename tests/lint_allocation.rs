@@ -0,0 +1,53 @@
+//! Linting a clean input allocates nothing, counted by a global allocator
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use url_encoded_data::lint::Linter;
+use url_encoded_data::{UrlEncodedData, UrlEncodedDataPairScanner};
+
+struct Counting;
+
+thread_local! {
+    // per thread: the test harness allocates on its own threads
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+fn allocations<T>(f: impl FnOnce() -> T) -> (usize, T) {
+    let before = ALLOCATIONS.with(Cell::get);
+    let result = f();
+    (ALLOCATIONS.with(Cell::get) - before, result)
+}
+
+#[test]
+fn test_clean_input_does_not_allocate() {
+    let linter = Linter::default();
+    let clean = "https://abc.com/?q=rust+lint&page=2&name=%C3%A9t%C3%A9&tag=a&tag=b&empty=&flag";
+    let q = UrlEncodedData::parse_str(clean);
+    let scanner = UrlEncodedDataPairScanner::from(clean);
+
+    let (count, findings) = allocations(|| linter.lint(&q));
+    assert!(findings.is_empty());
+    assert_eq!(count, 0);
+    let (count, findings) = allocations(|| linter.lint_scanner(&scanner));
+    assert!(findings.is_empty());
+    assert_eq!(count, 0);
+
+    // findings are still reported
+    let q = UrlEncodedData::parse_str("a=1&next=%0D%0ASet-Cookie:x");
+    assert_eq!(linter.lint(&q)[0].index, 1);
+}
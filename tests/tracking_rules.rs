@@ -0,0 +1,123 @@
+//! Tracking-parameter stripping with ClearURLs rules loaded from `tests/fixtures/clearurls`
+#![cfg(feature = "tracking")]
+
+use url_encoded_data::tracking::{Error, Rules};
+use url_encoded_data::UrlEncodedData;
+
+fn rules() -> Rules {
+    let path = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/clearurls/data.min.json"
+    );
+    Rules::from_clearurls_file(path).unwrap()
+}
+
+#[test]
+fn test_clearurls_rules() {
+    let rules = rules();
+    let cases = [
+        (
+            "https://example.com/post?id=7&utm_source=newsletter&utm_medium=email&fbclid=IwAR0",
+            "https://example.com/post?id=7",
+        ),
+        (
+            "https://www.amazon.com/dp/B0?pd_rd_w=abc&th=1&tag=affiliate-20&keep=1",
+            "https://www.amazon.com/dp/B0?keep=1",
+        ),
+        // "th" is an amazon rule only
+        (
+            "https://example.com/?th=1&gclid=x",
+            "https://example.com/?th=1",
+        ),
+        (
+            "https://www.google.com/search?q=rust&ei=abc&ved=0ahUKE&sxsrf=x",
+            "https://www.google.com/search?q=rust",
+        ),
+        // an exception testing the query: the google rules do not apply to map searches
+        (
+            "https://www.google.com/s?tbm=map&gs_lcp=1&ei=x",
+            "https://www.google.com/s?tbm=map&gs_lcp=1&ei=x",
+        ),
+        (
+            "https://www.google.com/s?tbm=isch&gs_lcp=1&ei=x",
+            "https://www.google.com/s?tbm=isch",
+        ),
+        // exception of the global rules
+        (
+            "https://accounts.google.com/signin?utm_source=x&continue=y",
+            "https://accounts.google.com/signin?utm_source=x&continue=y",
+        ),
+        // keys are matched case-insensitively and as a whole
+        (
+            "https://example.com/?UTM_Campaign=x&not_utm_source=1&mc_eid=2",
+            "https://example.com/?not_utm_source=1",
+        ),
+    ];
+    for (url, expected) in cases.iter() {
+        let stripped = UrlEncodedData::parse_str(url).strip_tracking(&rules);
+        assert_eq!(
+            stripped.data.to_string_of_original_order(),
+            *expected,
+            "url: {}",
+            url
+        );
+    }
+}
+
+#[test]
+fn test_removal_report() {
+    let q = UrlEncodedData::parse_str(
+        "https://www.amazon.de/dp/B0?tag=affiliate-21&id=1&utm_source=a&utm_source=b",
+    );
+    let stripped = q.strip_tracking(&rules());
+    let removed: Vec<_> = stripped
+        .removed
+        .iter()
+        .map(|r| {
+            (
+                r.key.as_str(),
+                r.value.as_str(),
+                r.rule_set.as_str(),
+                r.rule.as_str(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        removed,
+        [
+            ("tag", "affiliate-21", "amazon", "regex:tag"),
+            (
+                "utm_source",
+                "a",
+                "globalRules",
+                "regex:(?:%3F)?utm(?:_[a-z_]*)?"
+            ),
+            (
+                "utm_source",
+                "b",
+                "globalRules",
+                "regex:(?:%3F)?utm(?:_[a-z_]*)?"
+            ),
+        ]
+    );
+}
+
+#[test]
+fn test_loading_errors() {
+    assert!(matches!(
+        Rules::from_clearurls_file("/nonexistent/data.min.json"),
+        Err(Error::Io(_))
+    ));
+    assert!(matches!(
+        Rules::from_clearurls_json("{"),
+        Err(Error::Json(_))
+    ));
+    assert!(matches!(
+        Rules::from_clearurls_json(r#"{"rules": []}"#),
+        Err(Error::Format(_))
+    ));
+    assert!(matches!(
+        Rules::from_clearurls_json(r#"{"providers": {"x": {"rules": ["("]}}}"#),
+        Err(Error::Regex { .. })
+    ));
+}
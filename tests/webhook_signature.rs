@@ -0,0 +1,31 @@
+//! Twilio style webhook signatures, published examples in `tests/fixtures/webhook` (sources cited there)
+#![cfg(feature = "webhook")]
+
+use serde_json::Value;
+use url_encoded_data::webhook::WebhookVerifier;
+use url_encoded_data::UrlEncodedData;
+
+#[test]
+fn test_published_signatures() {
+    let cases: Vec<Value> =
+        serde_json::from_str(include_str!("fixtures/webhook/twilio.json")).unwrap();
+    let cases: Vec<&Value> = cases.iter().filter(|case| case.is_object()).collect();
+    assert!(!cases.is_empty());
+
+    for case in cases {
+        let name = case["name"].as_str().unwrap();
+        let verifier = WebhookVerifier::new(case["auth_token"].as_str().unwrap());
+        let url = case["url"].as_str().unwrap();
+        let body = UrlEncodedData::parse_str(case["body"].as_str().unwrap());
+        let signature = case["signature"].as_str().unwrap();
+
+        let result = verifier.verify(url, &body, signature);
+        assert_eq!(
+            result.is_ok(),
+            case["valid"].as_bool().unwrap(),
+            "{}: {:?}",
+            name,
+            result
+        );
+    }
+}
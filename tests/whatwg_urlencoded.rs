@@ -0,0 +1,100 @@
+//! WHATWG urlencoded test vectors (vendored from web-platform-tests into `tests/fixtures/whatwg`)
+
+use serde_json::Value;
+use url_encoded_data::search_params::UrlSearchParams;
+use url_encoded_data::{codec, UrlEncodedDataPairScanner};
+
+/// Test cases of a fixture file, the leading string is the origin note
+fn cases(json: &str) -> Vec<Value> {
+    let cases: Vec<Value> = serde_json::from_str(json).unwrap();
+    cases.into_iter().filter(|case| case.is_object()).collect()
+}
+
+fn expected_pairs(case: &Value) -> Vec<(String, String)> {
+    case["output"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|pair| {
+            (
+                pair[0].as_str().unwrap().to_string(),
+                pair[1].as_str().unwrap().to_string(),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn test_urlencoded_parser() {
+    let cases = cases(include_str!("fixtures/whatwg/urlencoded-parser.json"));
+    assert!(!cases.is_empty());
+    for case in cases.iter() {
+        let input = case["input"].as_str().unwrap();
+        let expected = expected_pairs(case);
+
+        let params = UrlSearchParams::parse(input);
+        let got: Vec<_> = params
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        assert_eq!(got, expected, "UrlSearchParams, input: {:?}", input);
+        assert_eq!(params.size(), expected.len());
+
+        let got: Vec<_> = codec::parse(input)
+            .map(|(k, v)| (k.into_owned(), v.into_owned()))
+            .collect();
+        assert_eq!(got, expected, "codec::parse, input: {:?}", input);
+
+        let scanner = UrlEncodedDataPairScanner::from(input);
+        let got: Vec<_> = scanner
+            .iter()
+            .map(|(k, v)| (k.into_owned(), v.into_owned()))
+            .collect();
+        assert_eq!(
+            got, expected,
+            "UrlEncodedDataPairScanner, input: {:?}",
+            input
+        );
+    }
+}
+
+#[test]
+fn test_urlsearchparams_sort() {
+    let cases = cases(include_str!("fixtures/whatwg/urlsearchparams-sort.json"));
+    assert!(!cases.is_empty());
+    for case in cases.iter() {
+        let input = case["input"].as_str().unwrap();
+        let mut params = UrlSearchParams::parse(input);
+        params.sort();
+        let got: Vec<_> = params
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        assert_eq!(got, expected_pairs(case), "input: {:?}", input);
+    }
+}
+
+#[test]
+fn test_urlsearchparams_stringifier() {
+    let cases = cases(include_str!(
+        "fixtures/whatwg/urlsearchparams-stringifier.json"
+    ));
+    assert!(!cases.is_empty());
+    for case in cases.iter() {
+        let name = case["name"].as_str().unwrap();
+        let value = case["value"].as_str().unwrap();
+        let mut params = UrlSearchParams::default();
+        params.append(name, value);
+        assert_eq!(
+            params.to_string(),
+            case["output"].as_str().unwrap(),
+            "name: {:?}, value: {:?}",
+            name,
+            value
+        );
+
+        // and it parses back
+        let parsed = UrlSearchParams::parse(case["output"].as_str().unwrap());
+        assert_eq!(parsed.get(name), Some(value));
+    }
+}